@@ -0,0 +1,119 @@
+//! `--asn <N>`: keep only hosts with an answer IP in one of the given ASNs
+//! (feature `asn`, off by default).
+//!
+//! The IP-to-ASN table is the tab-separated format published by iptoasn.com
+//! (`ip2asn-combined.tsv[.gz]`), given with `--asn-db <PATH>`:
+//!
+//! ```text
+//! <range_start>\t<range_end>\t<as_number>\t<country>\t<description>
+//! ```
+//!
+//! Only the first three columns are used; ranges with AS number 0 (not routed) are skipped.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
+
+#[derive(Default)]
+pub struct AsnTable {
+    /// (start, end, asn), sorted by start, non-overlapping
+    v4: Vec<(u32, u32, u32)>,
+    v6: Vec<(u128, u128, u32)>,
+}
+
+impl AsnTable {
+    pub fn parse(text: &str) -> Self {
+        let mut t = AsnTable::default();
+        for line in text.lines() {
+            let mut f = line.split('\t');
+            let (Some(start), Some(end), Some(asn)) = (f.next(), f.next(), f.next()) else { continue };
+            let Ok(asn) = asn.trim().trim_start_matches("AS").parse::<u32>() else { continue };
+            if asn == 0 { continue; }
+            match (start.trim().parse::<IpAddr>(), end.trim().parse::<IpAddr>()) {
+                (Ok(IpAddr::V4(s)), Ok(IpAddr::V4(e))) => t.v4.push((s.into(), e.into(), asn)),
+                (Ok(IpAddr::V6(s)), Ok(IpAddr::V6(e))) => t.v6.push((s.into(), e.into(), asn)),
+                _ => {}
+            }
+        }
+        t.v4.sort_unstable();
+        t.v6.sort_unstable();
+        t
+    }
+
+    /// Plain or gzip-compressed (`.gz`) table file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read(path)
+            .with_context(|| format!("cannot read ASN table {}", path.display()))?;
+        let text = if raw.starts_with(&[0x1f, 0x8b]) {
+            let mut s = String::new();
+            flate2::read::MultiGzDecoder::new(&raw[..]).read_to_string(&mut s)?;
+            s
+        } else {
+            String::from_utf8_lossy(&raw).into_owned()
+        };
+        let t = Self::parse(&text);
+        if t.is_empty() { anyhow::bail!("ASN table {} has no usable ranges", path.display()); }
+        Ok(t)
+    }
+
+    pub fn len(&self) -> usize { self.v4.len() + self.v6.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<u32> {
+        fn find<T: Ord + Copy>(ranges: &[(T, T, u32)], x: T) -> Option<u32> {
+            // last range starting at or before x
+            let i = ranges.partition_point(|r| r.0 <= x).checked_sub(1)?;
+            let (_, end, asn) = ranges[i];
+            (x <= end).then_some(asn)
+        }
+        match ip {
+            IpAddr::V4(v4) => find(&self.v4, u32::from(v4)),
+            IpAddr::V6(v6) => find(&self.v6, u128::from(v6)),
+        }
+    }
+
+    /// First ASN from `allow` that any of `ips` belongs to.
+    pub fn matching(&self, ips: &[String], allow: &[u32]) -> Option<u32> {
+        ips.iter()
+            .filter_map(|ip| ip.parse::<IpAddr>().ok())
+            .filter_map(|ip| self.lookup(ip))
+            .find(|asn| allow.contains(asn))
+    }
+}
+
+/// `15169` or `AS15169` (case-insensitive).
+pub fn parse_asn(s: &str) -> Result<u32, String> {
+    let t = s.trim();
+    let digits = t.strip_prefix("AS").or_else(|| t.strip_prefix("as")).unwrap_or(t);
+    digits.parse::<u32>().map_err(|_| format!("invalid ASN: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "1.0.0.0\t1.0.0.255\t13335\tUS\tCLOUDFLARENET\n\
+                         1.1.1.0\t1.1.1.255\t13335\tUS\tCLOUDFLARENET\n\
+                         8.8.4.0\t8.8.4.255\t15169\tUS\tGOOGLE\n\
+                         8.8.8.0\t8.8.8.255\t15169\tUS\tGOOGLE\n\
+                         9.9.9.0\t9.9.9.255\t19281\tUS\tQUAD9-AS-1\n\
+                         10.0.0.0\t10.255.255.255\t0\tNone\tNot routed\n\
+                         2001:4860::\t2001:4860:ffff:ffff:ffff:ffff:ffff:ffff\t15169\tUS\tGOOGLE\n";
+
+    #[test]
+    fn known_ip_to_asn_mappings() {
+        let t = AsnTable::parse(TABLE);
+        assert_eq!(t.lookup("8.8.8.8".parse().unwrap()), Some(15169));
+        assert_eq!(t.lookup("1.1.1.1".parse().unwrap()), Some(13335));
+        assert_eq!(t.lookup("2001:4860:4860::8888".parse().unwrap()), Some(15169));
+        assert_eq!(t.lookup("8.8.5.1".parse().unwrap()), None);
+        assert_eq!(t.lookup("10.1.2.3".parse().unwrap()), None);
+        let ips = vec!["192.0.2.1".to_string(), "9.9.9.9".to_string(), "8.8.8.8".to_string()];
+        assert_eq!(t.matching(&ips, &[15169]), Some(15169));
+        assert_eq!(t.matching(&ips, &[13335]), None);
+        assert_eq!(parse_asn("AS15169"), Ok(15169));
+        assert!(parse_asn("google").is_err());
+    }
+}
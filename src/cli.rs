@@ -7,18 +7,56 @@ use std::path::PathBuf;
     version,
     about = "rusub - 高速异步子域枚举工具",
     long_about = "NAME:\n  rusub - 高速异步子域枚举工具\n\nUSAGE:\n  rusub enum [OPTIONS] [DOMAIN]...\n\n说明:\n  - 默认启发式扫描，可通过 -f 指定字典文件\n  - 自动启用断点续传、泛解析过滤\n  - json/jsonl 格式自动纯净输出、仅显示存活域名\n\n示例:\n  # 启发式扫描\n  rusub enum example.com --heuristic-max 1024 --output-type jsonl\n  \n  # 字典扫描\n  rusub enum example.com -f subdomain.txt --output-type jsonl",
-    after_help = "参数说明:\n\n输入:\n  -d, --domain [DOMAIN]...       指定域名(可重复)\n      [DOMAIN]...                位置参数域名\n      --stdin                    从标准输入读取域名\n  -f, --filename <PATH>          字典文件(指定则使用字典模式，否则使用启发式)\n      --domain-list <PATH>       根域列表文件\n\n输出:\n  -o, --output <PATH>            输出文件(.gz后缀自动压缩)\n      --output-type <FMT>        输出格式: txt/json/jsonl/csv (默认 jsonl)\n      --gzip                     强制 gzip 压缩\n      --not-print                不在终端打印结果\n      --pure-output              纯净模式(仅结果行)\n      --only-alive               仅输出存活域名\n\n速率:\n  -b, --band <EXPR>              速率: 支持 K/M/G (默认 3m)\n      --timeout <SEC>            超时秒数 (默认 6)\n      --retry <N>                重试次数 (默认 3)\n\n启发式:\n      --heuristic-max <N>        启发式最大候选数 (默认 512)\n\n解析器:\n  -r, --resolvers <IP>...        指定解析器(可重复)\n  -c, --concurrency <N>          并发数 (默认 500)\n\n其他:\n      --log-level <LEVEL>        日志级别: error|warn|info|debug|silent\n"
+    after_help = "参数说明:\n\n输入:\n  -d, --domain [DOMAIN]...       指定域名(可重复)\n      [DOMAIN]...                位置参数域名\n      --stdin                    从标准输入读取域名\n  -f, --filename <PATH>          字典文件(可重复，合并去重；指定则使用字典模式，否则使用启发式)\n      --domain-list <PATH>       根域列表文件\n      --exclude-domain-suffix <SUFFIX>  排除以该后缀结尾的根域(可重复)\n      --exclude-domains-file <PATH>  排除后缀列表文件\n      --domain-chunk <N>         根域分批处理，每批 N 个\n      --shard <I/N>              只扫描候选词分片 (下标 % N == I)\n      --list-words               打印内置字典后退出\n      --reverse                  反向解析: 输入 IP/CIDR，查询 PTR\n      --preserve-case            保留字典标签原始大小写(去重仍不区分大小写)\n      --allow-invalid-labels     不丢弃非法候选标签\n\n输出:\n  -o, --output <PATH>            输出文件(.gz后缀自动压缩)\n      --output-type <FMT>        输出格式: txt/json/jsonl/csv/sqlite/table/label (默认 jsonl)\n      --gzip                     强制 gzip 压缩\n      --gzip-index               分块 gzip 并写 .idx 索引(可随机读取)\n      --not-print                不在终端打印结果\n      --pure-output              纯净模式(仅结果行)\n  -q, --quiet                    不显示实时进度行(保留警告与结束汇总)\n      --only-alive               仅输出存活域名\n      --emit-negative            输出 NXDOMAIN/SERVFAIL/REFUSED/TIMEOUT 结果(带 rcode)\n      --include-wildcard         输出泛解析命中结果(标记 wildcard)\n      --wildcard <MODE>          泛解析检测: off/basic/advanced (默认 advanced)\n      --wildcard-report          打印泛解析集合到 stderr，命中结果标记输出不过滤\n      --wildcard-threshold <RATIO>  advanced 判定阈值 (默认 0.6)\n      --wildcard-probes <N>      每个根域泛解析探测次数 (默认 basic 3 / advanced 6)\n      --split-by-rtype <DIR>     按记录类型拆分输出到目录\n      --dedup-output             全程输出去重 (json/jsonl 默认开启)\n      --no-dedup-output          关闭输出去重\n      --append                   追加到输出文件 (去重开启时跳过文件中已有子域)\n      --sort-output              结束时按域名层级排序输出 (缓存全部结果)\n      --partial-flush <SECS>     缓冲输出的中途快照间隔 (写 <输出>.partial.jsonl)\n      --tree                     结果附带 parent / depth (发现树)\n      --output-hash              结束时写 <输出>.sha256 (gzip 按解压内容)\n      --max-answers <N>          每个主机最多输出 N 个地址 (其余标记 ...+K more)\n      --no-result-marker <TEXT>  txt 输出无应答结果的标记 (默认 [no-result])\n      --skip-empty               txt 输出省略无应答结果的行\n      --output-flush-ms <MS>     文件输出刷盘间隔 (默认 1000，0 = 每行刷盘)\n      --timings                  结果附带解析器与查询耗时\n      --takeover-check           标记 CNAME 指向易被接管服务的结果\n      --takeover-fingerprints <PATH>  接管指纹文件(替换内置列表)\n      --cname-is-alive           悬空 CNAME (目标 NXDOMAIN/SERVFAIL) 也作为结果输出\n      --asn <N>                  ASN 白名单(可重复，需 asn feature 与 --asn-db)\n      --asn-db <PATH>            IP->ASN 表 (iptoasn TSV，可 .gz)\n      --probe-ports <LIST>       探测存活主机端口 (如 80,443)\n      --probe-rate <N>           端口探测速率 connect/s (默认 500)\n\n速率:\n  -b, --band <EXPR>              速率: K/M/G、Mbps、MBps/MB/s、pps (默认 3m)\n      --burst <N>                令牌桶容量 (默认 0 = 100ms 的量，最平滑)\n      --timeout <SEC>            超时秒数 (默认 6)\n      --host-timeout <SECS>      单个主机总耗时上限(含重试，0 = 不限)\n      --retry <N>                重试次数 (默认 3)\n      --retry-transient <N>      临时错误额外重试次数 (默认 1)\n      --recheck-failed           结束前以 1/4 速率复查失败主机\n      --a-only                   仅查询 A 记录(跳过 AAAA/CNAME)\n      --rd / --no-rd             查询头 RD 位 (默认 RD=1)\n      --cd                       设置 CD 位 (跳过 DNSSEC 校验)\n      --ad                       设置 AD 位\n      --dnssec                   设置 DO 位，结果记录 AD 标志与 RRSIG\n      --recv-buffer <BYTES>      UDP 接收缓冲区 (默认 4096)\n      --source-ip <IP>           查询使用的本地源地址(多网卡/策略路由)\n      --raw                      原始套接字发包(Linux，需 CAP_NET_RAW，高 pps)\n      --ecs <CIDR>               EDNS Client Subnet (可重复，多个时记录各子网应答)\n      --max-queries <N>          查询预算，达到后停止派发并正常收尾\n      --respect-ttl              已解析主机在 TTL 内不重复查询\n\n启发式:\n      --heuristic / --no-heuristic  启发式扩展开关 (默认未指定 -f 时启用)\n      --heuristic-max <N>        启发式最大候选数 (默认 512)\n\n解析器:\n  -r, --resolvers <IP>...        指定解析器(可重复)\n      --resolvers-file <PATH>    解析器列表文件(支持 IP,权重 与 # 注释)\n  -c, --concurrency <N>          并发数 (默认 500)\n      --resolver-primary <IP>    首选解析器(可重复)，全部禁用时才用常规解析器\n      --trusted-resolvers <IP>   可信解析器(可重复)\n      --trusted-resolvers-file <PATH>  可信解析器列表文件\n      --confirm-hits             命中结果经可信解析器复核\n      --consensus <K>            命中结果由 K 个解析器多数表决 (查询量约 K 倍)\n      --merge-resolvers <K>      合并 K 个解析器的应答记录 (查询量约 K 倍)\n      --use-hosts                优先使用系统 hosts 文件记录\n      --authoritative            直接查询根域权威 NS (RD=0)\n      --resolver-cooldown <SECS>  禁用解析器的冷却时间 (默认 60)\n      --resolver-disable-min <N>  按失败率禁用前的最少样本数 (默认 20)\n      --resolver-fail-ratio <RATIO>  失败率超过该值禁用解析器 (默认 0.8)\n\n其他:\n      --log-level <LEVEL>        日志级别: error|warn|info|debug|silent\n      --seed <N>                 随机种子(可复现扫描)\n      --always-success           总是返回退出码 0\n      --json-errors              致命错误以单行 JSON 输出到 stderr\n      --no-state                 禁用状态文件(断点续传)\n      --clean-state-on-success   扫描完成后删除状态文件\n      --manifest <PATH>          写入扫描参数清单 JSON\n      --progress-json <PATH>     进度快照 JSON(含每个解析器统计)\n      --progress-json-interval <SEC>  进度快照间隔 (默认 5)\n      --progress-stream [TARGET]  逐行 JSON 进度事件: stderr(默认)/stdout/FD\n      --heartbeat <SECS>         每 N 秒向 stderr 写一行心跳 JSON (纯净模式也输出)\n      --resolver-stats <PATH>    解析器统计 JSON(含禁用次数/时长)\n      --resolver-stats-interval <SEC>  解析器统计间隔 (默认 10)\n      --control-socket <PATH>    运行时控制 socket: set-rate/pause/resume/stats (需 control-socket feature)\n      --result-socket <ADDR>     结果流 socket: tcp:HOST:PORT / unix:PATH，逐行 JSON (需 result-socket feature)\n\n信号 (Unix):\n  SIGUSR1 切换暂停/恢复  SIGUSR2 恢复 (暂停时不派发新查询)\n\n退出码:\n  0 有结果  1 出错(配置/解析器等)  2 扫描完成但无结果\n"
 )]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// 致命错误以单行 JSON 输出到 stderr：{"error":"...","kind":"config|io|network"} (供外部编排工具解析)
+    #[arg(long = "json-errors", global = true)]
+    pub json_errors: bool,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// 枚举域名 (enum) - 主扫描流程：启发式/字典爆破、原始UDP、泛解析过滤与断点续传
     #[command(alias = "e")]
-    Enum(EnumArgs),
+    Enum(Box<EnumArgs>),
+    /// 单次解析 (resolve)：对给定域名各查询一次并输出记录，不做字典爆破 / 泛解析过滤 / 断点续传
+    Resolve(ResolveArgs),
+    /// 列出本机网卡与地址 (供 --source-ip 选择)，并显示是否具备 CAP_NET_RAW
+    Interfaces(InterfacesArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ResolveArgs {
+    /// 域名、解析器、并发与日志级别
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// 查询的记录类型；A 与扫描相同 (A 无结果时查 AAAA、追踪 CNAME)，其他类型只查一次
+    #[arg(long = "rtype", default_value = "A", value_parser = crate::lookup::RTYPES, ignore_case = true)]
+    pub rtype: String,
+
+    /// 输出格式: txt/json/jsonl/table (默认 txt，带记录明细)
+    #[arg(long = "output-type", default_value = "txt", value_parser = ["txt", "json", "jsonl", "table"])]
+    pub output_type: String,
+
+    /// 超时 (秒，至少 1)
+    #[arg(long = "timeout", default_value_t = 6, value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: u64,
+
+    /// 查询使用的本地源地址
+    #[arg(long = "source-ip", value_name = "IP")]
+    pub source_ip: Option<std::net::IpAddr>,
+}
+
+#[derive(Args, Debug)]
+pub struct InterfacesArgs {
+    /// 同时列出链路未启用 (down) 的网卡
+    #[arg(long = "all")]
+    pub all: bool,
 }
 
 /// Common args reused by multiple subcommands
@@ -41,7 +79,7 @@ pub struct CommonArgs {
     pub resolvers: Vec<String>,
 
     /// 并发数（备用）
-    #[arg(short = 'c', long = "concurrency", default_value_t = 500)]
+    #[arg(short = 'c', long = "concurrency", default_value_t = 500, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
     pub concurrency: usize,
 
     /// 日志级别: error|warn|info|debug|silent
@@ -55,15 +93,48 @@ pub struct EnumArgs {
     #[command(flatten)]
     pub common: CommonArgs,
 
-    /// 词表文件路径
+    /// 词表文件路径 (可重复，多个文件合并去重；gzip 文件自动解压)
     #[arg(short = 'f', long = "filename")]
-    pub filename: Option<PathBuf>,
+    pub filename: Vec<PathBuf>,
 
-    /// 读取域名的列表文件
+    /// 读取域名的列表文件 (gzip 文件自动解压)
     #[arg(long = "domain-list", alias = "ds")]
     pub domain_list: Option<PathBuf>,
 
-    /// 是否读取根域 NS 并加入其 A/AAAA 记录 IP 到解析器池 (实验特性)
+    /// 排除以该后缀结尾的输入根域 (可重复，如 parked.example 同时排除 a.parked.example)
+    #[arg(long = "exclude-domain-suffix", value_name = "SUFFIX")]
+    pub exclude_domain_suffix: Vec<String>,
+
+    /// 排除后缀列表文件，每行一个 (支持 # 注释，gzip 文件自动解压)
+    #[arg(long = "exclude-domains-file", value_name = "PATH")]
+    pub exclude_domains_file: Option<PathBuf>,
+
+    /// 根域分批处理：每批 N 个根域依次完成泛解析探测与枚举，批间共享状态文件 / 解析器池 / 输出
+    #[arg(long = "domain-chunk", value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub domain_chunk: Option<u64>,
+
+    /// 只扫描候选词的一个分片：I/N 取下标 % N == I 的词 (I 从 0 开始)，用于多台机器分摊同一字典；
+    /// 各机器使用相同的字典与扩展参数时，N 个分片恰好覆盖全部候选
+    #[arg(long = "shard", value_name = "I/N", value_parser = crate::options::parse_shard)]
+    pub shard: Option<(usize, usize)>,
+
+    /// 打印内置字典后退出 (每行一个，可直接管道给其他工具)
+    #[arg(long = "list-words")]
+    pub list_words: bool,
+
+    /// 反向解析模式：输入为 IP 或 CIDR (如 192.0.2.0/24)，发 PTR 查询并输出 ip -> 主机名
+    #[arg(long = "reverse")]
+    pub reverse: bool,
+
+    /// 保留字典中标签的原始大小写 (默认统一转为小写；去重始终不区分大小写)
+    #[arg(long = "preserve-case")]
+    pub preserve_case: bool,
+
+    /// 不校验候选标签 (默认丢弃超长、含非法字符或首尾连字符的标签)
+    #[arg(long = "allow-invalid-labels")]
+    pub allow_invalid_labels: bool,
+
+    /// 是否读取根域 NS 并加入其 A/AAAA 记录 IP 到解析器池 (实验特性；仅加入通过递归探测的 IP)
     #[arg(long = "ns")]
     pub ns: bool,
 
@@ -71,7 +142,7 @@ pub struct EnumArgs {
     #[arg(short = 'o', long = "output")]
     pub output: Option<PathBuf>,
 
-    /// 输出类型: txt/json/jsonl/csv
+    /// 输出类型: txt/json/jsonl/csv/sqlite/table/label (table 为对齐表格，结束时统一输出；label 仅输出相对根域的标签)
     #[arg(long = "output-type", default_value = "jsonl")]
     pub output_type: String,
 
@@ -79,33 +150,336 @@ pub struct EnumArgs {
     #[arg(long = "gzip")] 
     pub gzip: bool,
 
+    /// 分块 gzip 输出 (隐含 --gzip)，并写 <output>.idx 索引，便于按行号随机读取
+    #[arg(long = "gzip-index")]
+    pub gzip_index: bool,
+
     /// 不在终端打印
     #[arg(long = "not-print")]
     pub not_print: bool,
 
-    /// 带宽表示 (支持 K/M/G 后缀，示例: 100M, 3m)
-    #[arg(short = 'b', long = "band", default_value = "3m")]
+    /// 带宽表示 (K/M/G 比特、MBps 或 MB/s 字节、pps 包速率，示例: 100M, 3m, 2MB/s)
+    #[arg(short = 'b', long = "band", default_value = "3m", value_parser = crate::options::parse_band)]
     pub band: String,
 
-    /// 超时 (秒)
-    #[arg(long = "timeout", default_value_t = 6)]
+    /// 令牌桶容量：空闲后一次最多放出 N 个查询。默认 0 = 每 100ms 补充的量，发包最均匀；
+    /// 调大则允许突发 (如 --burst 等于每秒速率即旧的逐秒补充行为)
+    #[arg(long = "burst", value_name = "N", default_value_t = 0)]
+    pub burst: u64,
+
+    /// 超时 (秒，至少 1)
+    #[arg(long = "timeout", default_value_t = 6, value_parser = clap::value_parser!(u64).range(1..))]
     pub timeout: u64,
 
-    /// 重试次数 (默认 3)
+    /// 单个主机的总耗时上限 (秒)：含全部重试，超出即放弃并记为失败；--timeout 仍为单次查询超时。0 = 不限制
+    #[arg(long = "host-timeout", value_name = "SECS", default_value_t = 0)]
+    pub host_timeout: u64,
+
+    /// 重试次数 (默认 3)：任何失败都可消耗；NXDOMAIN/NODATA 不重试
     #[arg(long = "retry", default_value_t = 3)]
     pub retry: i32,
 
+    /// 临时错误额外重试次数 (默认 1)：仅在超时/SERVFAIL/REFUSED 且 --retry 用尽后追加
+    #[arg(long = "retry-transient", default_value_t = 1)]
+    pub retry_transient: u32,
+
+    /// 主扫描结束后，以 1/4 速率把仍失败 (超时 / SERVFAIL 等) 的主机再查询一次，解析成功的照常输出
+    #[arg(long = "recheck-failed")]
+    pub recheck_failed: bool,
+
+    /// 仅查询 A 记录，跳过 AAAA / CNAME 追查 (同等速率下吞吐更高)
+    #[arg(long = "a-only")]
+    pub a_only: bool,
+
+    /// 设置 RD 位 (默认)
+    #[arg(long = "rd", overrides_with = "no_rd")]
+    pub rd: bool,
+
+    /// 清除 RD 位：不请求递归 (解析器只返回缓存 / 自身权威数据)；--authoritative 始终为 RD=0
+    #[arg(long = "no-rd")]
+    pub no_rd: bool,
+
+    /// 设置 CD 位：要求验证型解析器跳过 DNSSEC 校验 (可拿到校验失败的应答)
+    #[arg(long = "cd")]
+    pub cd: bool,
+
+    /// 设置 AD 位：请求应答中返回 AD 标志 (RFC 6840，不设置 DO)
+    #[arg(long = "ad")]
+    pub ad: bool,
+
+    /// 设置 EDNS0 DO 位：结果记录应答的 AD 标志 (dnssec_validated) 与是否带 RRSIG (rrsig)，用于标出已签名区域中的主机
+    #[arg(long = "dnssec")]
+    pub dnssec: bool,
+
+    /// 查询预算：已发送查询数达到 N 后不再派发新任务，正常收尾 (写出结果、保存状态)
+    #[arg(long = "max-queries", value_name = "N")]
+    pub max_queries: Option<u64>,
+
+    /// 运行内正向缓存：已解析的主机在其记录最小 TTL 内被再次访问 (预测轮次等) 时不再查询
+    #[arg(long = "respect-ttl")]
+    pub respect_ttl: bool,
+
+    /// 运行时控制 socket (Unix)：接受 `set-rate N` / `pause` / `resume` / `stats`，每行一条命令
+    #[cfg(all(unix, feature = "control-socket"))]
+    #[arg(long = "control-socket", value_name = "PATH")]
+    pub control_socket: Option<PathBuf>,
+
+    /// 结果流 socket：tcp:HOST:PORT / unix:PATH / HOST:PORT；每个连接的客户端从连接时起逐行收到 JSON 结果，
+    /// 落后超过缓冲的慢客户端被断开，扫描结束时关闭连接
+    #[cfg(feature = "result-socket")]
+    #[arg(long = "result-socket", value_name = "ADDR", value_parser = clap::value_parser!(crate::resultsock::ListenAddr))]
+    pub result_socket: Option<crate::resultsock::ListenAddr>,
+
+    /// UDP 接收缓冲区大小 (字节，512-65535)，过小会截断大应答
+    #[arg(long = "recv-buffer", default_value_t = 4096)]
+    pub recv_buffer: usize,
+
+    /// 查询 socket 绑定的本地源地址 (多网卡 / 策略路由时指定出口)，须为本机已配置的地址；泛解析探测同样使用该地址
+    #[arg(long = "source-ip", value_name = "IP")]
+    pub source_ip: Option<std::net::IpAddr>,
+
+    /// 原始套接字发包 (仅 Linux，需 CAP_NET_RAW)：自行构造 IPv4/UDP 帧，所有查询共用一个源端口，按 DNS id 关联应答，
+    /// 省去每个查询创建 socket 的开销；权限不足时警告并回退普通 UDP。IPv6 解析器仍走普通 UDP
+    #[arg(long = "raw")]
+    pub raw: bool,
+
+    /// EDNS Client Subnet (CIDR，可重复)：模拟不同地区客户端；多个时逐个子网查询并在结果中记录各自应答
+    #[arg(long = "ecs", value_name = "CIDR")]
+    pub ecs: Vec<crate::dns::ClientSubnet>,
+
     /// 纯净输出：仅输出结果
     #[arg(long = "pure-output")]
     pub pure_output: bool,
 
+    /// 安静模式：不显示实时进度行，但保留警告、解析器禁用提示与结束汇总 (适合 cron)；
+    /// 不同于 --pure-output / --log-level silent，日志级别不变
+    #[arg(short = 'q', long = "quiet")]
+    pub quiet: bool,
+
     /// 只输出存活的域名
     #[arg(long = "only-alive")]
     pub only_alive: bool,
 
+    /// 输出否定结果：NXDOMAIN / SERVFAIL / REFUSED / TIMEOUT 的主机也单独输出 (json 中为 rcode 字段，文本中为 [NXDOMAIN] 等标记)，
+    /// 不受 --only-alive 限制；用于统计区域覆盖率与解析器可靠性
+    #[arg(long = "emit-negative")]
+    pub emit_negative: bool,
+
+    /// 全程输出去重：同一主机应答不变时只输出一次 (json/jsonl 默认开启)
+    #[arg(long = "dedup-output", overrides_with = "no_dedup_output")]
+    pub dedup_output: bool,
+
+    /// 关闭输出去重
+    #[arg(long = "no-dedup-output")]
+    pub no_dedup_output: bool,
+
+    /// 追加写入输出文件而不是覆盖；开启输出去重时先读取文件中已有的子域 (含 .gz)，只追加新结果
+    #[arg(long = "append")]
+    pub append: bool,
+
+    /// 结果全部缓存到内存，结束时按域名层级排序后统一输出 (结果多时内存占用较高)
+    #[arg(long = "sort-output")]
+    pub sort_output: bool,
+
+    /// 缓冲输出 (--sort-output / table) 每隔 SECS 秒把已得到的结果落盘到 <输出路径>.partial.jsonl，
+    /// 崩溃或被杀时不至于全部丢失；正常结束后删除，最终输出文件才是完整结果。需要 --output
+    #[arg(long = "partial-flush", value_name = "SECS", default_value_t = 0)]
+    pub partial_flush: u64,
+
+    /// 结果附带发现树位置：parent 为最近的上级根域，depth 为相对最外层根域的层数 (json/jsonl 可见)，
+    /// 下游可据此重建枚举树
+    #[arg(long = "tree")]
+    pub tree: bool,
+
+    /// 扫描结束、输出文件全部落盘后计算其 SHA-256，写入 <输出路径>.sha256 (sha256sum 格式)；
+    /// gzip 输出对解压后的内容计算。需要 --output
+    #[arg(long = "output-hash")]
+    pub output_hash: bool,
+
+    /// 每个主机最多输出 N 个 A/AAAA 地址，多出的以 "...+K more" 标记 (json 中为 truncated 字段)；
+    /// 只影响输出，去重与状态文件仍使用完整应答。默认不限制
+    #[arg(long = "max-answers", value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_answers: Option<u64>,
+
+    /// txt 输出中无应答结果的标记，替换默认的 "[no-result]" (--emit-negative 的 rcode 标记不变)；
+    /// 不能为空或包含 tab / 换行
+    #[arg(long = "no-result-marker", value_name = "TEXT", value_parser = crate::options::parse_no_result_marker)]
+    pub no_result_marker: Option<String>,
+
+    /// txt 输出省略没有任何应答记录的行，而不是输出无结果标记
+    #[arg(long = "skip-empty", conflicts_with = "no_result_marker")]
+    pub skip_empty: bool,
+
+    /// 文件输出刷盘间隔 (毫秒)：结果先进写缓冲，按此间隔与结束时落盘；0 = 每行刷盘 (tail -f 实时)。终端输出始终按行
+    #[arg(long = "output-flush-ms", value_name = "MS", default_value_t = crate::output::DEFAULT_FLUSH_MS)]
+    pub output_flush_ms: u64,
+
+    /// 按记录类型拆分输出到目录 (a.txt / aaaa.txt / cname.txt / txt.txt)
+    #[arg(long = "split-by-rtype", value_name = "DIR")]
+    pub split_by_rtype: Option<PathBuf>,
+
+    /// 对存活主机的 IP 做 TCP 端口探测，结果附带 open_ports (如 80,443,8000-8010)
+    #[arg(long = "probe-ports", value_name = "LIST")]
+    pub probe_ports: Option<String>,
+
+    /// 端口探测速率 (connect/s)，独立于 DNS 速率
+    #[arg(long = "probe-rate", default_value_t = 500)]
+    pub probe_rate: i64,
+
+    /// 结果附带应答解析器与查询耗时 (resolver / latency_ms)
+    #[arg(long = "timings")]
+    pub timings: bool,
+
+    /// 子域接管提示：CNAME 指向易被接管的服务 (github.io、s3、herokudns 等) 时在结果中标记 takeover_candidate
+    #[arg(long = "takeover-check")]
+    pub takeover_check: bool,
+
+    /// 接管指纹文件 (每行一个 CNAME 后缀，# 注释)，替换内置列表；隐含 --takeover-check
+    #[arg(long = "takeover-fingerprints", value_name = "PATH")]
+    pub takeover_fingerprints: Option<PathBuf>,
+
+    /// 悬空 CNAME 视为存活：CNAME 目标 NXDOMAIN/SERVFAIL 时仍输出 (answers 为空，records 含 CNAME) 并记为完成，不再丢弃或重试
+    #[arg(long = "cname-is-alive")]
+    pub cname_is_alive: bool,
+
+    /// ASN 白名单 (可重复，如 15169 或 AS15169)：只输出应答 IP 属于这些 AS 的主机，结果附带 asn
+    #[cfg(feature = "asn")]
+    #[arg(long = "asn", value_name = "N", value_parser = crate::asn::parse_asn, requires = "asn_db")]
+    pub asn: Vec<u32>,
+
+    /// IP -> ASN 数据表 (iptoasn.com 的 ip2asn-combined.tsv，支持 .gz)
+    #[cfg(feature = "asn")]
+    #[arg(long = "asn-db", value_name = "PATH")]
+    pub asn_db: Option<PathBuf>,
+
+    /// 仍输出被泛解析过滤的结果，并标记为 wildcard
+    #[arg(long = "include-wildcard")]
+    pub include_wildcard: bool,
+
+    /// 泛解析检测模式：off 关闭 / basic 少量探测取并集 / advanced 多次探测按出现比例判定
+    #[arg(long = "wildcard", value_name = "MODE", default_value = "advanced", value_parser = ["off", "basic", "advanced"])]
+    pub wildcard: String,
+
+    /// 泛解析审计：把每个根域检测到的泛解析 IP 集合打印到 stderr，命中结果标记 wildcard 照常输出而不过滤
+    #[arg(long = "wildcard-report")]
+    pub wildcard_report: bool,
+
+    /// advanced 模式阈值 (0-1]：IP 出现在至少该比例的随机探测中才算泛解析
+    #[arg(long = "wildcard-threshold", value_name = "RATIO", default_value_t = crate::wildcard::DEFAULT_THRESHOLD, value_parser = crate::wildcard::parse_threshold)]
+    pub wildcard_threshold: f64,
+
+    /// 每个根域的泛解析随机探测次数 (默认 basic 3 / advanced 6)：轮换 IP 池的泛解析可调高，礼貌扫描可调低
+    #[arg(long = "wildcard-probes", value_name = "N", value_parser = clap::value_parser!(u64).range(1..=1000))]
+    pub wildcard_probes: Option<u64>,
+
+    /// 启用启发式扩展 (默认仅在未指定 -f 时启用)：与 -f 同用时在自定义字典基础上扩展
+    #[arg(long = "heuristic", overrides_with = "no_heuristic")]
+    pub heuristic: bool,
+
+    /// 关闭启发式扩展：只扫描字典 (内置或 -f) 本身
+    #[arg(long = "no-heuristic")]
+    pub no_heuristic: bool,
+
     /// 启发式生成的最大条目数 (默认 512)
     #[arg(long = "heuristic-max", default_value_t = 512)]
     pub heuristic_max: usize,
+
+    /// 解析器列表文件（每行 `IP[,权重] [# 注释]`，权重默认 1.0），与 -r 合并
+    #[arg(long = "resolvers-file", value_name = "PATH")]
+    pub resolvers_file: Option<PathBuf>,
+
+    /// 权威模式：解析每个根域的 NS，直接向其发送非递归 (RD=0) 查询；遇到子域委派时改用递归解析器
+    #[arg(long = "authoritative")]
+    pub authoritative: bool,
+
+    /// 首选解析器（可重复）：只要有一个未被健康检查禁用就只向首选解析器查询，
+    /// -r / --resolvers-file 等常规解析器仅在首选全部禁用时作为后备
+    #[arg(long = "resolver-primary", value_name = "IP")]
+    pub resolver_primary: Vec<String>,
+
+    /// 可信解析器（可重复），用于 --confirm-hits 复核
+    #[arg(long = "trusted-resolvers")]
+    pub trusted_resolvers: Vec<String>,
+
+    /// 可信解析器列表文件（每行一个）
+    #[arg(long = "trusted-resolvers-file")]
+    pub trusted_resolvers_file: Option<PathBuf>,
+
+    /// 命中结果需经可信解析器复核后才输出
+    #[arg(long = "confirm-hits")]
+    pub confirm_hits: bool,
+
+    /// 多数一致：每个命中再向 K-1 个不同解析器查询 (共 K 个)，过半数给出相同 IP 集合才输出；查询量约为 K 倍。
+    /// 可用解析器不足 K 个时按实际数量投票
+    #[arg(long = "consensus", value_name = "K", value_parser = clap::value_parser!(u64).range(2..=16))]
+    pub consensus: Option<u64>,
+
+    /// 合并应答：每个命中再向 K-1 个不同解析器查询 (共 K 个)，记录去重合并后输出，结果带 merged_from (返回记录的解析器数)；
+    /// 用于分区视图 / anycast 下单个解析器只返回部分 IP 的情况。查询量约为 K 倍，不能与 --consensus 同用
+    #[arg(long = "merge-resolvers", value_name = "K", value_parser = clap::value_parser!(u64).range(2..=16), conflicts_with = "consensus")]
+    pub merge_resolvers: Option<u64>,
+
+    /// 优先使用系统 hosts 文件 (/etc/hosts 或 Windows 等价文件) 中的记录，命中则不发查询
+    #[arg(long = "use-hosts")]
+    pub use_hosts: bool,
+
+    /// 被健康检查禁用的解析器冷却多少秒后重新启用：网络抖动时调大避免反复禁用/恢复，网络稳定时调小更快恢复容量
+    #[arg(long = "resolver-cooldown", value_name = "SECS", default_value_t = crate::resolver_pool::DEFAULT_COOLDOWN_SECS)]
+    pub resolver_cooldown: u64,
+
+    /// 健康检查：解析器累计多少次成功/失败后才按失败率判定禁用
+    #[arg(long = "resolver-disable-min", value_name = "N", default_value_t = crate::resolver_pool::DisablePolicy::DEFAULT_MIN_TOTAL, value_parser = clap::value_parser!(u64).range(1..))]
+    pub resolver_disable_min: u64,
+
+    /// 健康检查：失败率超过该值 (0-1] 即禁用解析器
+    #[arg(long = "resolver-fail-ratio", value_name = "RATIO", default_value_t = crate::resolver_pool::DisablePolicy::DEFAULT_FAIL_RATIO, value_parser = crate::resolver_pool::parse_fail_ratio)]
+    pub resolver_fail_ratio: f64,
+
+    /// 解析器统计 JSON 文件 (ok/fail/禁用状态/禁用次数)，周期写入并在结束时更新
+    #[arg(long = "resolver-stats", value_name = "PATH")]
+    pub resolver_stats: Option<PathBuf>,
+
+    /// 解析器统计写入间隔 (秒)
+    #[arg(long = "resolver-stats-interval", default_value_t = 10)]
+    pub resolver_stats_interval: u64,
+
+    /// 进度快照 JSON 文件（含每个解析器的统计）
+    #[arg(long = "progress-json")]
+    pub progress_json: Option<PathBuf>,
+
+    /// 进度快照写入间隔 (秒)
+    #[arg(long = "progress-json-interval", default_value_t = 5)]
+    pub progress_json_interval: u64,
+
+    /// 进度事件流：每秒输出一行 JSON 进度快照 (字段同 --progress-json，不含逐解析器列表)，结束时输出 done=true 的最后一行；
+    /// 目标为 stderr (默认)、stdout 或已继承的文件描述符编号 (Unix)。输出到 stderr 时不再显示 \r 进度行
+    #[arg(long = "progress-stream", value_name = "TARGET", num_args = 0..=1, default_missing_value = "stderr")]
+    pub progress_stream: Option<crate::metrics::ProgressTarget>,
+
+    /// 每 N 秒向 stderr 写一行心跳 JSON (heartbeat 时间戳、elapsed、sent、finished、total、found)，
+    /// 纯净模式 / json 输出下照常输出，供编排器判断进程存活；stdout 只有结果
+    #[arg(long = "heartbeat", value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub heartbeat: Option<u64>,
+
+    /// 不使用状态文件 (不读取也不写入断点进度)
+    #[arg(long = "no-state")]
+    pub no_state: bool,
+
+    /// 扫描正常结束后删除状态文件 (中断时仍保留以便续扫)
+    #[arg(long = "clean-state-on-success", conflicts_with = "no_state")]
+    pub clean_state_on_success: bool,
+
+    /// 总是以退出码 0 结束 (默认: 0 = 有结果, 2 = 无结果, 1 = 出错)
+    #[arg(long = "always-success")]
+    pub always_success: bool,
+
+    /// 随机种子：固定泛解析探测、解析器选择与查询 ID，便于复现扫描
+    #[arg(long = "seed")]
+    pub seed: Option<u64>,
+
+    /// 扫描开始时写入 JSON 清单：解析器、速率、超时、重试、字典来源与哈希等参数
+    #[arg(long = "manifest", value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
 }
 
 
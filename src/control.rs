@@ -0,0 +1,162 @@
+//! `--control-socket <PATH>`: tune a running scan over a Unix socket
+//! (feature `control-socket`, off by default).
+//!
+//! One plain-text command per line, one reply line each:
+//! `set-rate <pps>`, `pause`, `resume`, `stats`.
+//!
+//! ```text
+//! echo "set-rate 5000" | nc -U /tmp/rusub.sock
+//! ```
+
+use crate::metrics::Metrics;
+use crate::ratelimit::RateLimiter;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+/// Pause after a failed accept (e.g. EMFILE) so a persistent error doesn't spin the loop.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Handles shared with the scan loop.
+#[derive(Clone)]
+pub struct ControlState {
+    pub rate: RateLimiter,
+    pub paused: Arc<AtomicBool>,
+    pub metrics: Arc<Metrics>,
+}
+
+/// Execute one command line and return the reply (without newline).
+pub fn handle_command(line: &str, st: &ControlState) -> String {
+    let mut it = line.split_whitespace();
+    match (it.next(), it.next()) {
+        (Some("set-rate"), Some(n)) => match n.parse::<i64>() {
+            Ok(v) if v > 0 => {
+                st.rate.set_rate(v);
+                info!("[control] rate set to {} pkt/s", v);
+                format!("ok rate={}", v)
+            }
+            _ => format!("error: invalid rate {:?}", n),
+        },
+        (Some("pause"), None) => {
+            st.paused.store(true, Ordering::Relaxed);
+            info!("[control] paused");
+            "ok paused".into()
+        }
+        (Some("resume"), None) => {
+            st.paused.store(false, Ordering::Relaxed);
+            info!("[control] resumed");
+            "ok resumed".into()
+        }
+        (Some("stats"), None) => {
+            let m = &st.metrics;
+            format!(
+                "total={} sent={} ok={} failed={} filtered={} timeouts={} rate={} paused={}",
+                m.total.load(Ordering::Relaxed), m.sent.load(Ordering::Relaxed), m.ok.load(Ordering::Relaxed),
+                m.failed.load(Ordering::Relaxed), m.filtered.load(Ordering::Relaxed), m.timeouts.load(Ordering::Relaxed),
+                st.rate.get_rate(), st.paused.load(Ordering::Relaxed),
+            )
+        }
+        (None, _) => String::new(),
+        _ => format!("error: unknown command {:?} (set-rate <pps> | pause | resume | stats)", line.trim()),
+    }
+}
+
+/// Remove a socket file left behind by an earlier run; anything else at `path` is an error,
+/// so a mistyped path never deletes user data.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(m) if m.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("cannot remove stale control socket {}", path.display())),
+        Ok(_) => bail!("control socket path {} exists and is not a socket; refusing to replace it", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("cannot inspect control socket path {}", path.display())),
+    }
+}
+
+/// Bind `path` (replacing a stale socket file) and serve until the task is aborted.
+pub fn serve(path: PathBuf, st: ControlState) -> Result<JoinHandle<()>> {
+    remove_stale_socket(&path)?;
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("cannot bind control socket {}", path.display()))?;
+    info!("[control] listening on {}", path.display());
+    Ok(tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("[control] accept error: {}", e);
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            };
+            let st = st.clone();
+            tokio::spawn(async move {
+                let (rd, mut wr) = stream.into_split();
+                let mut lines = BufReader::new(rd).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let reply = handle_command(&line, &st);
+                    if reply.is_empty() { continue; }
+                    if let Err(e) = wr.write_all(format!("{}\n", reply).as_bytes()).await {
+                        warn!("[control] write error: {}", e);
+                        break;
+                    }
+                }
+            });
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_mutate_shared_state() {
+        let st = ControlState { rate: RateLimiter::new(100), paused: Arc::new(AtomicBool::new(false)), metrics: Metrics::new() };
+        assert_eq!(handle_command("set-rate 5000", &st), "ok rate=5000");
+        assert_eq!(st.rate.get_rate(), 5000);
+        assert!(handle_command("set-rate -1", &st).starts_with("error"));
+        assert_eq!(handle_command("pause", &st), "ok paused");
+        assert!(st.paused.load(Ordering::Relaxed));
+        assert!(handle_command("stats", &st).ends_with("rate=5000 paused=true"));
+        assert_eq!(handle_command("resume", &st), "ok resumed");
+        assert!(!st.paused.load(Ordering::Relaxed));
+        assert!(handle_command("reboot", &st).starts_with("error: unknown command"));
+    }
+
+    #[tokio::test]
+    async fn socket_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ctl.sock");
+        let st = ControlState { rate: RateLimiter::new(100), paused: Arc::new(AtomicBool::new(false)), metrics: Metrics::new() };
+        let task = serve(path.clone(), st.clone()).unwrap();
+        let mut s = tokio::net::UnixStream::connect(&path).await.unwrap();
+        s.write_all(b"set-rate 42\n").await.unwrap();
+        let mut lines = BufReader::new(s).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "ok rate=42");
+        assert_eq!(st.rate.get_rate(), 42);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn refuses_to_replace_a_regular_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        std::fs::write(&path, "keep me").unwrap();
+        let st = ControlState { rate: RateLimiter::new(100), paused: Arc::new(AtomicBool::new(false)), metrics: Metrics::new() };
+        let err = serve(path.clone(), st.clone()).unwrap_err();
+        assert!(err.to_string().contains("is not a socket"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        // a stale socket from an earlier run is replaced
+        let sock = dir.path().join("ctl.sock");
+        drop(std::os::unix::net::UnixListener::bind(&sock).unwrap());
+        serve(sock, st).unwrap().abort();
+    }
+}
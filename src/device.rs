@@ -0,0 +1,92 @@
+//! 本机网卡枚举与权限检测 (`rusub interfaces`)：查看可用于 `--source-ip` 的地址，
+//! 以及当前进程是否具备 CAP_NET_RAW。
+
+use anyhow::Result;
+use std::net::IpAddr;
+
+/// One local interface with every address assigned to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub addrs: Vec<IpAddr>,
+    pub up: bool,
+    pub loopback: bool,
+}
+
+/// Local interfaces in system order, addresses grouped per interface name.
+/// `up_only` drops interfaces whose link is reported down.
+pub fn list_interfaces(up_only: bool) -> Result<Vec<Interface>> {
+    let mut out: Vec<Interface> = Vec::new();
+    for ifa in get_if_addrs::get_if_addrs()? {
+        match out.iter_mut().find(|i| i.name == ifa.name) {
+            Some(i) => i.addrs.push(ifa.ip()),
+            None => out.push(Interface { up: is_up(&ifa.name), loopback: ifa.is_loopback(), name: ifa.name.clone(), addrs: vec![ifa.ip()] }),
+        }
+    }
+    if up_only { out.retain(|i| i.up); }
+    Ok(out)
+}
+
+/// Link state from `/sys/class/net/<name>/operstate`. `unknown` (loopback, tun devices) counts
+/// as up; where the file does not exist (non-Linux) every interface with an address is up.
+fn is_up(name: &str) -> bool {
+    match std::fs::read_to_string(format!("/sys/class/net/{}/operstate", name)) {
+        Ok(state) => matches!(state.trim(), "up" | "unknown"),
+        Err(_) => true,
+    }
+}
+
+/// CAP_NET_RAW 位号 (linux/capability.h)
+const CAP_NET_RAW: u32 = 13;
+
+/// Whether the process may open raw sockets: CAP_NET_RAW in the effective set (root has it
+/// unless dropped). Always `false` off Linux.
+pub fn has_cap_net_raw() -> bool {
+    std::fs::read_to_string("/proc/self/status").is_ok_and(|s| cap_net_raw_in_status(&s))
+}
+
+fn cap_net_raw_in_status(status: &str) -> bool {
+    status.lines()
+        .find_map(|l| l.strip_prefix("CapEff:"))
+        .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+        .is_some_and(|caps| caps & (1 << CAP_NET_RAW) != 0)
+}
+
+/// `rusub interfaces` 的输出：每个网卡一行，地址逗号分隔。
+pub fn format_interfaces(ifs: &[Interface]) -> String {
+    let name_w = ifs.iter().map(|i| i.name.len()).max().unwrap_or(0).max("NAME".len());
+    let mut out = format!("{:<name_w$}  {:<8}  ADDRESSES\n", "NAME", "STATE");
+    for i in ifs {
+        let state = match (i.up, i.loopback) {
+            (true, true) => "loopback",
+            (true, false) => "up",
+            (false, _) => "down",
+        };
+        let addrs: Vec<String> = i.addrs.iter().map(|a| a.to_string()).collect();
+        out.push_str(&format!("{:<name_w$}  {:<8}  {}\n", i.name, state, addrs.join(", ")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cap_net_raw_is_read_from_effective_set() {
+        let status = |eff: &str| format!("Name:\trusub\nCapInh:\t0000000000000000\nCapPrm:\t000001ffffffffff\nCapEff:\t{}\n", eff);
+        assert!(cap_net_raw_in_status(&status("000001ffffffffff")));
+        assert!(cap_net_raw_in_status(&status("0000000000002000")));
+        assert!(!cap_net_raw_in_status(&status("0000000000000000")));
+        assert!(!cap_net_raw_in_status("Name:\trusub\n"));
+    }
+
+    #[test]
+    fn interfaces_are_listed_one_per_line() {
+        let ifs = vec![
+            Interface { name: "lo".into(), addrs: vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()], up: true, loopback: true },
+            Interface { name: "eth1".into(), addrs: vec!["10.0.8.2".parse().unwrap()], up: false, loopback: false },
+        ];
+        assert_eq!(format_interfaces(&ifs), "NAME  STATE     ADDRESSES\nlo    loopback  127.0.0.1, ::1\neth1  down      10.0.8.2\n");
+    }
+}
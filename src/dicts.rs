@@ -1,8 +1,8 @@
 //! Embedded default wordlists.
-//! Using include_str! to embed wordlists/subdomain.txt at compile time.
+//! Using include_str! to embed wordlists/subdomain.txt and wordlists/takeover.txt at compile time.
 
 /// Embedded subdomain wordlist from wordlists/subdomain.txt
-const EMBEDDED_SUBDOMAIN_TXT: &str = include_str!("../wordlists/subdomain.txt");
+pub const EMBEDDED_SUBDOMAIN_TXT: &str = include_str!("../wordlists/subdomain.txt");
 
 /// Return the embedded wordlist as owned Strings (one per line).
 pub fn default_wordlist() -> Vec<String> {
@@ -12,3 +12,6 @@ pub fn default_wordlist() -> Vec<String> {
         .map(|line| line.trim().to_string())
         .collect()
 }
+
+/// Embedded takeover fingerprint list (CNAME target suffixes) from wordlists/takeover.txt
+pub const EMBEDDED_TAKEOVER_TXT: &str = include_str!("../wordlists/takeover.txt");
@@ -26,7 +26,8 @@ pub fn dynamic_extend(discovered: &[String], base: &[String], top_n: usize) -> V
         }
     }
     let mut items: Vec<(&str, u32)> = freq.into_iter().collect();
-    items.sort_by(|a,b| b.1.cmp(&a.1));
+    // frequency desc, then label asc: equal counts must not depend on HashMap order
+    items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
     let mut out: Vec<String> = Vec::new();
     let base_set: std::collections::HashSet<&str> = base.iter().map(|s| s.as_str()).collect();
     for (label, _) in items.into_iter().take(top_n) {
@@ -67,15 +68,59 @@ mod tests {
         // base seeds should not be duplicated
         assert!(!extended.contains(&"api".into()));
     }
+
+    #[test]
+    fn dynamic_extend_tie_break_is_stable() {
+        // four labels with the same count; top 2 must always be the lexicographically first two
+        let discovered: Vec<String> = ["zulu", "yankee", "alpha", "bravo"].iter()
+            .flat_map(|l| [format!("{l}.example.com"), format!("{l}.example.org")])
+            .collect();
+        for _ in 0..16 {
+            let out = dynamic_extend(&discovered, &[], 2);
+            assert!(out.contains(&"alpha".to_string()) && out.contains(&"bravo".to_string()));
+            assert!(!out.contains(&"yankee".to_string()) && !out.contains(&"zulu".to_string()));
+        }
+    }
 }
 use std::collections::HashSet;
 
+/// Second-level labels that are part of a country-code suffix (`co.uk`, `com.cn`, ...).
+const SECOND_LEVEL: [&str; 8] = ["com", "co", "net", "org", "gov", "edu", "ac", "or"];
+
+/// Tokens from the registrable name of a root domain, e.g. `acme-corp.com` ->
+/// `acme-corp`, `acmecorp`, `acme`, `corp`. No public suffix list: a 2-letter TLD
+/// preceded by a common second-level label is treated as a two-label suffix.
+pub fn domain_tokens(domain: &str) -> Vec<String> {
+    let d = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = d.split('.').filter(|l| !l.is_empty()).collect();
+    if labels.len() < 2 { return Vec::new(); }
+    let n = labels.len();
+    let suffix_len = if n >= 3 && labels[n - 1].len() == 2 && SECOND_LEVEL.contains(&labels[n - 2]) { 2 } else { 1 };
+    if n <= suffix_len { return Vec::new(); }
+    let name = labels[n - suffix_len - 1];
+    let parts: Vec<&str> = name.split(['-', '_']).filter(|p| p.len() >= 2).collect();
+    let mut out = vec![name.to_string()];
+    if parts.len() > 1 {
+        out.push(parts.concat());
+        out.extend(parts.iter().map(|p| p.to_string()));
+    }
+    let mut seen = HashSet::new();
+    out.retain(|t| seen.insert(t.clone()));
+    out
+}
+
 /// Generate heuristic candidate labels based on an existing wordlist and some
 /// common patterns. This is intentionally conservative and deterministic.
 ///
 /// - `words`: existing base words (from wordlist/predict seeds)
 /// - `max`: maximum number of heuristics to generate
 pub fn generate_heuristics(words: &[String], max: usize) -> Vec<String> {
+    generate_heuristics_for(words, &[], max)
+}
+
+/// Like `generate_heuristics`, but seeds the set with tokens of the target root
+/// domains first (`acme`, `acmecorp`, `corp-api`, ...), so they survive the `max` cap.
+pub fn generate_heuristics_for(words: &[String], domains: &[String], max: usize) -> Vec<String> {
     let mut set: HashSet<String> = HashSet::new();
 
     // common environment/service tokens and regions
@@ -91,6 +136,28 @@ pub fn generate_heuristics(words: &[String], max: usize) -> Vec<String> {
         set.len() >= max
     }
 
+    // company-specific tokens from the targets themselves
+    let mut tokens: Vec<String> = domains.iter().flat_map(|d| domain_tokens(d)).collect();
+    tokens.dedup();
+    // breadth-first (all bare tokens, then token x service, then token x env) so a
+    // small `max` still covers every token instead of exhausting the first one
+    'domain: {
+        for t in tokens.iter() {
+            if push_unique(&mut set, t.clone(), max) { break 'domain; }
+        }
+        for svc in services.iter() {
+            for t in tokens.iter() {
+                if push_unique(&mut set, format!("{}-{}", t, svc), max) { break 'domain; }
+                if push_unique(&mut set, format!("{}-{}", svc, t), max) { break 'domain; }
+            }
+        }
+        for env in envs.iter() {
+            for t in tokens.iter() {
+                if push_unique(&mut set, format!("{}-{}", t, env), max) { break 'domain; }
+            }
+        }
+    }
+
     // seed from supplied words: use first token chunks (split non-alnum)
     for w in words.iter().take(500) {
         if set.len() >= max { break; }
@@ -146,3 +213,28 @@ pub fn generate_heuristics(words: &[String], max: usize) -> Vec<String> {
     v.truncate(max);
     v
 }
+
+#[cfg(test)]
+mod heuristic_tests {
+    use super::*;
+
+    #[test]
+    fn domain_tokens_from_registrable_name() {
+        assert_eq!(domain_tokens("acme-corp.com"), ["acme-corp", "acmecorp", "acme", "corp"]);
+        assert_eq!(domain_tokens("www.example.co.uk"), ["example"]);
+        assert_eq!(domain_tokens("ab-cd-ab.com"), ["ab-cd-ab", "abcdab", "ab", "cd"]);
+        assert_eq!(domain_tokens("shop.acme.com.cn."), ["acme"]);
+        assert!(domain_tokens("localhost").is_empty());
+    }
+
+    #[test]
+    fn heuristics_include_domain_tokens_within_max() {
+        let words = vec!["www".to_string(), "mail".to_string()];
+        let h = generate_heuristics_for(&words, &["acme-corp.com".to_string()], 64);
+        assert_eq!(h.len(), 64);
+        for want in ["acme", "acmecorp", "corp-api", "api-acme"] {
+            assert!(h.contains(&want.to_string()), "missing {want}");
+        }
+        assert!(generate_heuristics_for(&words, &["acme-corp.com".to_string()], 5).len() <= 5);
+    }
+}
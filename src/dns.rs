@@ -1,43 +1,239 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use trust_dns_proto::op::{Message, MessageType, OpCode, Query};
-use trust_dns_proto::rr::{Name, RecordType};
+use trust_dns_proto::rr::Name;
+pub use trust_dns_proto::rr::RecordType;
 use trust_dns_proto::serialize::binary::{BinEncoder, BinEncodable, BinDecodable};
-use std::net::UdpSocket;
-use std::time::Duration;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use crate::rng::{next_id, SharedRng};
 
 #[derive(Debug, Clone)]
 pub struct RawRecord {
     pub rtype: String,
     pub data: String,
+    pub ttl: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DnsAnswer {
     pub records: Vec<RawRecord>,
     pub rcode: String,
+    /// AD flag of the reply (the A reply for `udp_query_full`)
+    pub authentic_data: bool,
+    /// the answer section carried RRSIG records (`--dnssec` sets DO so signed zones include them)
+    pub rrsig: bool,
+}
+
+/// Per-scan knobs for `udp_query_full`, built once and shared by every host task.
+#[derive(Clone)]
+pub struct QueryOptions {
+    pub rng: SharedRng,
+    /// Single A query; skip the AAAA / CNAME follow-ups (`--a-only`).
+    pub a_only: bool,
+    /// RD bit; cleared when querying authoritative servers directly (`--authoritative`).
+    pub recursion_desired: bool,
+    /// CD bit (`--cd`): ask validating resolvers to skip DNSSEC checks, so bogus answers come back.
+    pub checking_disabled: bool,
+    /// AD bit (`--ad`): ask for the AD flag in replies without setting DO (RFC 6840 5.7).
+    pub authentic_data: bool,
+    /// EDNS0 DO bit (`--dnssec`): signed zones return RRSIGs and validating resolvers set AD.
+    pub dnssec_ok: bool,
+    /// UDP receive buffer in bytes (`--recv-buffer`), advertised as EDNS0 payload size on every query.
+    pub recv_buffer: usize,
+    /// EDNS Client Subnet attached to every query (`--ecs`).
+    pub ecs: Option<ClientSubnet>,
+    /// CNAME hops followed when the answer carries no address (1 = a single chase).
+    pub cname_depth: usize,
+    /// Local address query sockets bind to (`--source-ip`); `None` = `0.0.0.0:0`.
+    pub source_ip: Option<IpAddr>,
+    /// `--raw`: IPv4 queries go out through the shared raw-socket transport instead of a socket each.
+    #[cfg(target_os = "linux")]
+    pub raw: Option<std::sync::Arc<crate::rawsock::RawTransport>>,
+}
+
+/// Marker record appended when a CNAME chain revisits a name; `data` is the repeated name.
+pub const CNAME_LOOP: &str = "CNAME-LOOP";
+
+/// Pseudo-rcode the scanner uses for a query that never left the host (see [`BindError`]).
+pub const BIND_ERROR: &str = "BindError";
+
+/// Binding the local UDP socket failed even after retries (ephemeral ports exhausted,
+/// EADDRNOTAVAIL under extreme concurrency): a local problem, not the resolver's fault.
+#[derive(Debug)]
+pub struct BindError(pub std::io::Error);
+
+impl std::fmt::Display for BindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "local UDP socket bind failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for BindError {}
+
+/// Bind attempts before giving up with [`BindError`]; waits 2ms, 8ms between tries.
+const BIND_ATTEMPTS: u32 = 3;
+
+fn bind_udp(source: Option<IpAddr>) -> Result<UdpSocket> {
+    let local = SocketAddr::new(source.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)), 0);
+    let mut attempt = 0;
+    loop {
+        match UdpSocket::bind(local) {
+            Ok(s) => return Ok(s),
+            Err(e) if attempt + 1 >= BIND_ATTEMPTS => return Err(BindError(e).into()),
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(2 << (2 * attempt)));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// `--source-ip` must be an address configured on a local interface: binding it once up
+/// front turns a typo into a clear startup error instead of every query failing to bind.
+pub fn check_source_ip(ip: IpAddr) -> Result<()> {
+    UdpSocket::bind(SocketAddr::new(ip, 0))
+        .map(drop)
+        .with_context(|| format!("--source-ip {} is not usable on this host (not assigned to any local interface?)", ip))
+}
+
+/// RFC 7871 client subnet, e.g. `203.0.113.0/24`. A bare address means a full-length prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientSubnet {
+    pub addr: std::net::IpAddr,
+    pub prefix: u8,
+}
+
+impl std::str::FromStr for ClientSubnet {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let (ip, prefix) = match s.trim().split_once('/') {
+            Some((ip, p)) => (ip, Some(p)),
+            None => (s.trim(), None),
+        };
+        let addr: std::net::IpAddr = ip.parse().map_err(|_| format!("invalid ECS subnet: {}", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().ok().filter(|p| *p <= max).ok_or_else(|| format!("invalid ECS prefix length: {}", s))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl std::fmt::Display for ClientSubnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl ClientSubnet {
+    /// OPTION-DATA: family, source prefix, scope prefix 0, address truncated to the prefix (host bits zeroed).
+    pub fn option_data(&self) -> Vec<u8> {
+        let (family, mut octets): (u16, Vec<u8>) = match self.addr {
+            std::net::IpAddr::V4(a) => (1, a.octets().to_vec()),
+            std::net::IpAddr::V6(a) => (2, a.octets().to_vec()),
+        };
+        let n = (self.prefix as usize).div_ceil(8);
+        octets.truncate(n);
+        let rem = self.prefix % 8;
+        if rem > 0 {
+            if let Some(last) = octets.last_mut() { *last &= 0xffu8 << (8 - rem); }
+        }
+        let mut data = Vec::with_capacity(4 + n);
+        data.extend_from_slice(&family.to_be_bytes());
+        data.push(self.prefix);
+        data.push(0);
+        data.extend_from_slice(&octets);
+        data
+    }
+}
+
+/// Receive buffer used when no `QueryOptions` is in play.
+pub const DEFAULT_RECV_BUFFER: usize = 4096;
+
+impl QueryOptions {
+    pub fn new(rng: SharedRng) -> Self {
+        Self {
+            rng, a_only: false, recursion_desired: true, checking_disabled: false, authentic_data: false, dnssec_ok: false, recv_buffer: DEFAULT_RECV_BUFFER, ecs: None, cname_depth: 1, source_ip: None,
+            #[cfg(target_os = "linux")]
+            raw: None,
+        }
+    }
+}
+
+/// Raw transport and IPv4 target when `--raw` is active; IPv6 resolvers keep the socket path.
+#[cfg(target_os = "linux")]
+fn raw_target<'a>(qo: &'a QueryOptions, server: &str) -> Option<(&'a crate::rawsock::RawTransport, std::net::SocketAddrV4)> {
+    let raw = qo.raw.as_deref()?;
+    match server_addr(server).parse::<SocketAddr>().ok()? {
+        SocketAddr::V4(addr) => Some((raw, addr)),
+        SocketAddr::V6(_) => None,
+    }
+}
+
+/// `ip` -> `ip:53`; an explicit `ip:port` is kept as is.
+fn server_addr(server: &str) -> String {
+    if server.parse::<std::net::SocketAddr>().is_ok() { server.to_string() } else { format!("{}:53", server) }
 }
 
 pub fn build_query(domain: &str, qtype: RecordType) -> Result<Vec<u8>> {
+    build_query_with_id(domain, qtype, rand::random::<u16>())
+}
+
+/// Same as `build_query` but with a caller-supplied message id (seeded scans).
+pub fn build_query_with_id(domain: &str, qtype: RecordType, id: u16) -> Result<Vec<u8>> {
+    build_query_rd(domain, qtype, id, true)
+}
+
+/// `recursion_desired: false` builds an RD=0 query for authoritative servers.
+pub fn build_query_rd(domain: &str, qtype: RecordType, id: u16, recursion_desired: bool) -> Result<Vec<u8>> {
+    encode(&query_message(domain, qtype, id, recursion_desired)?)
+}
+
+/// Query shaped by `qo`: RD / CD / AD header bits and an EDNS0 OPT record that advertises
+/// `recv_buffer` as UDP payload size (without it servers truncate at 512 bytes), plus the
+/// client subnet (`--ecs`) and the DO bit (`--dnssec`).
+pub fn build_query_opts(domain: &str, qtype: RecordType, id: u16, qo: &QueryOptions) -> Result<Vec<u8>> {
+    use trust_dns_proto::op::Edns;
+    use trust_dns_proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+    let mut msg = query_message(domain, qtype, id, qo.recursion_desired)?;
+    msg.set_checking_disabled(qo.checking_disabled);
+    msg.set_authentic_data(qo.authentic_data);
+    let mut edns = Edns::new();
+    edns.set_max_payload(qo.recv_buffer.min(u16::MAX as usize) as u16);
+    edns.set_dnssec_ok(qo.dnssec_ok);
+    if let Some(ecs) = &qo.ecs {
+        edns.options_mut().insert(EdnsOption::Unknown(u16::from(EdnsCode::Subnet), ecs.option_data()));
+    }
+    msg.set_edns(edns);
+    encode(&msg)
+}
+
+fn query_message(domain: &str, qtype: RecordType, id: u16, recursion_desired: bool) -> Result<Message> {
     let mut msg = Message::new();
-    msg.set_id(rand::random::<u16>());
+    msg.set_id(id);
     msg.set_message_type(MessageType::Query);
     msg.set_op_code(OpCode::Query);
-    msg.set_recursion_desired(true);
+    msg.set_recursion_desired(recursion_desired);
     let name = Name::from_utf8(domain)?;
     let query = Query::query(name, qtype);
     msg.add_query(query);
+    Ok(msg)
+}
+
+fn encode(msg: &Message) -> Result<Vec<u8>> {
     let mut buf: Vec<u8> = Vec::with_capacity(512);
     let mut encoder = BinEncoder::new(&mut buf);
     msg.emit(&mut encoder)?;
     Ok(buf)
 }
 
-pub fn udp_query(domain: &str, server: &str, timeout_ms: u64) -> Result<Vec<String>> {
-    let packet = build_query(domain, RecordType::A)?;
-    let sock = UdpSocket::bind("0.0.0.0:0")?;
+pub fn udp_query(domain: &str, server: &str, timeout_ms: u64, rng: &SharedRng, source: Option<IpAddr>) -> Result<Vec<String>> {
+    let packet = build_query_with_id(domain, RecordType::A, next_id(rng))?;
+    let sock = bind_udp(source)?;
     sock.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
-    sock.send_to(&packet, format!("{}:53", server))?;
-    let mut recv = [0u8; 2048];
+    sock.send_to(&packet, server_addr(server))?;
+    let mut recv = [0u8; DEFAULT_RECV_BUFFER];
     match sock.recv(&mut recv) {
         Ok(n) => {
             let bytes = &recv[..n];
@@ -60,12 +256,12 @@ pub fn udp_query(domain: &str, server: &str, timeout_ms: u64) -> Result<Vec<Stri
     }
 }
 
-pub fn udp_query_typed(domain: &str, server: &str, timeout_ms: u64) -> Result<Vec<RawRecord>> {
+pub fn udp_query_typed(domain: &str, server: &str, timeout_ms: u64, source: Option<IpAddr>) -> Result<Vec<RawRecord>> {
     let packet = build_query(domain, RecordType::A)?;
-    let sock = UdpSocket::bind("0.0.0.0:0")?;
+    let sock = bind_udp(source)?;
     sock.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
     sock.send_to(&packet, format!("{}:53", server))?;
-    let mut recv = [0u8; 2048];
+    let mut recv = [0u8; DEFAULT_RECV_BUFFER];
     match sock.recv(&mut recv) {
         Ok(n) => {
             let bytes = &recv[..n];
@@ -75,10 +271,10 @@ pub fn udp_query_typed(domain: &str, server: &str, timeout_ms: u64) -> Result<Ve
                 if let Some(data) = rec.data() {
                     use trust_dns_proto::rr::RData;
                     match data {
-                        RData::A(ip) => records.push(RawRecord{ rtype: "A".into(), data: ip.to_string()}),
-                        RData::AAAA(ip) => records.push(RawRecord{ rtype: "AAAA".into(), data: ip.to_string()}),
-                        RData::CNAME(c) => records.push(RawRecord{ rtype: "CNAME".into(), data: c.to_utf8()}),
-                        RData::TXT(txt) => records.push(RawRecord{ rtype: "TXT".into(), data: txt.to_string()}),
+                        RData::A(ip) => records.push(RawRecord{ rtype: "A".into(), data: ip.to_string(), ttl: rec.ttl() }),
+                        RData::AAAA(ip) => records.push(RawRecord{ rtype: "AAAA".into(), data: ip.to_string(), ttl: rec.ttl() }),
+                        RData::CNAME(c) => records.push(RawRecord{ rtype: "CNAME".into(), data: c.to_utf8(), ttl: rec.ttl() }),
+                        RData::TXT(txt) => records.push(RawRecord{ rtype: "TXT".into(), data: txt.to_string(), ttl: rec.ttl() }),
                         _ => {}
                     }
                 }
@@ -89,69 +285,206 @@ pub fn udp_query_typed(domain: &str, server: &str, timeout_ms: u64) -> Result<Ve
     }
 }
 
-pub fn udp_query_full(domain: &str, server: &str, timeout_ms: u64) -> Result<DnsAnswer> {
-    // Helper to send one query of given type and parse answers
-    fn send_and_parse(domain: &str, server: &str, timeout_ms: u64, qtype: RecordType) -> Result<(Vec<RawRecord>, String)> {
-        let packet = build_query(domain, qtype)?;
-        let sock = UdpSocket::bind("0.0.0.0:0")?;
-        sock.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
-        sock.send_to(&packet, format!("{}:53", server))?;
-        let mut recv = [0u8; 2048];
-        match sock.recv(&mut recv) {
-            Ok(n) => {
-                let bytes = &recv[..n];
-                let msg = Message::from_bytes(bytes)?;
-                let rcode = format!("{:?}", msg.response_code());
-                let mut records = Vec::new();
-                for rec in msg.answers() {
-                    if let Some(data) = rec.data() {
-                        use trust_dns_proto::rr::RData;
-                        match data {
-                            RData::A(ip) => records.push(RawRecord{ rtype: "A".into(), data: ip.to_string()}),
-                            RData::AAAA(ip) => records.push(RawRecord{ rtype: "AAAA".into(), data: ip.to_string()}),
-                            RData::CNAME(c) => records.push(RawRecord{ rtype: "CNAME".into(), data: c.to_utf8()}),
-                            RData::TXT(txt) => records.push(RawRecord{ rtype: "TXT".into(), data: txt.to_string()}),
-                            _ => {}
-                        }
-                    }
-                }
-                Ok((records, rcode))
+/// Non-authoritative NOERROR with no answers but NS records in the authority section:
+/// the server delegates the name to another zone instead of answering it (RD=0 queries).
+fn is_referral(msg: &Message) -> bool {
+    use trust_dns_proto::op::ResponseCode;
+    msg.response_code() == ResponseCode::NoError
+        && !msg.authoritative()
+        && msg.answers().is_empty()
+        && msg.name_servers().iter().any(|r| r.record_type() == RecordType::NS)
+}
+
+fn parse_answer(bytes: &[u8]) -> Result<(u16, DnsAnswer)> {
+    use trust_dns_proto::rr::RData;
+    let msg = Message::from_bytes(bytes)?;
+    let rcode = if is_referral(&msg) { "Referral".to_string() } else { format!("{:?}", msg.response_code()) };
+    let rrsig = msg.answers().iter().any(|r| r.record_type() == RecordType::RRSIG);
+    let mut records = Vec::new();
+    for rec in msg.answers() {
+        if let Some(data) = rec.data() {
+            match data {
+                RData::A(ip) => records.push(RawRecord{ rtype: "A".into(), data: ip.to_string(), ttl: rec.ttl() }),
+                RData::AAAA(ip) => records.push(RawRecord{ rtype: "AAAA".into(), data: ip.to_string(), ttl: rec.ttl() }),
+                RData::CNAME(c) => records.push(RawRecord{ rtype: "CNAME".into(), data: c.to_utf8(), ttl: rec.ttl() }),
+                RData::TXT(txt) => records.push(RawRecord{ rtype: "TXT".into(), data: txt.to_string(), ttl: rec.ttl() }),
+                RData::PTR(p) => records.push(RawRecord{ rtype: "PTR".into(), data: p.to_utf8().trim_end_matches('.').to_string(), ttl: rec.ttl() }),
+                RData::MX(mx) => records.push(RawRecord{ rtype: "MX".into(), data: format!("{} {}", mx.preference(), mx.exchange().to_utf8()), ttl: rec.ttl() }),
+                RData::NS(ns) => records.push(RawRecord{ rtype: "NS".into(), data: ns.to_utf8(), ttl: rec.ttl() }),
+                RData::SRV(srv) => records.push(RawRecord{ rtype: "SRV".into(), data: format!("{} {} {} {}", srv.priority(), srv.weight(), srv.port(), srv.target().to_utf8()), ttl: rec.ttl() }),
+                RData::SOA(soa) => records.push(RawRecord{ rtype: "SOA".into(), data: format!("{} {} {} {} {} {} {}", soa.mname().to_utf8(), soa.rname().to_utf8(), soa.serial(), soa.refresh(), soa.retry(), soa.expire(), soa.minimum()), ttl: rec.ttl() }),
+                _ => {}
             }
-            Err(_) => Ok((Vec::new(), "TIMEOUT".into()))
         }
     }
+    Ok((msg.id(), DnsAnswer { records, rcode, authentic_data: msg.authentic_data(), rrsig }))
+}
+
+// Send one query of given type and parse answers
+fn send_and_parse(domain: &str, server: &str, timeout_ms: u64, qtype: RecordType, qo: &QueryOptions) -> Result<DnsAnswer> {
+    let packet = build_query_opts(domain, qtype, next_id(&qo.rng), qo)?;
+    #[cfg(target_os = "linux")]
+    if let Some((raw, addr)) = raw_target(qo, server) {
+        let reply = raw.exchange(vec![packet], addr, Duration::from_millis(timeout_ms))?.pop().flatten();
+        return match reply {
+            Some(bytes) => parse_answer(&bytes).map(|(_, ans)| ans),
+            None => Ok(timed_out()),
+        };
+    }
+    let sock = bind_udp(qo.source_ip)?;
+    sock.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
+    sock.send_to(&packet, server_addr(server))?;
+    let mut recv = vec![0u8; qo.recv_buffer];
+    match sock.recv(&mut recv) {
+        Ok(n) => parse_answer(&recv[..n]).map(|(_, ans)| ans),
+        Err(_) => Ok(timed_out())
+    }
+}
+
+fn timed_out() -> DnsAnswer {
+    DnsAnswer { rcode: "TIMEOUT".into(), ..Default::default() }
+}
+
+type Reply = DnsAnswer;
+
+/// A 与 AAAA 在同一 socket 上并发发出，按 message id 关联应答；超时窗口内未到的一侧返回 None。
+/// A 已带回 IP 时不再等 AAAA (反正会被丢弃)，丢弃 AAAA 的解析器不会拖满整个超时。
+/// 共发出两个包，调用方按两个速率令牌计费 (scanner::lookup_permits)。
+fn send_pair(domain: &str, server: &str, timeout_ms: u64, qo: &QueryOptions) -> Result<(Option<Reply>, Option<Reply>)> {
+    let rng = &qo.rng;
+    let id_a = next_id(rng);
+    let mut id_aaaa = next_id(rng);
+    while id_aaaa == id_a { id_aaaa = next_id(rng); }
+    #[cfg(target_os = "linux")]
+    if let Some((raw, addr)) = raw_target(qo, server) {
+        // replies come back in request order; the transport already matched them by id
+        let packets = vec![build_query_opts(domain, RecordType::A, id_a, qo)?, build_query_opts(domain, RecordType::AAAA, id_aaaa, qo)?];
+        let mut replies = raw.exchange(packets, addr, Duration::from_millis(timeout_ms))?.into_iter()
+            .map(|r| r.and_then(|bytes| parse_answer(&bytes).ok()).map(|(_, ans)| ans));
+        return Ok((replies.next().flatten(), replies.next().flatten()));
+    }
+    let sock = bind_udp(qo.source_ip)?;
+    let target = server_addr(server);
+    sock.send_to(&build_query_opts(domain, RecordType::A, id_a, qo)?, &target)?;
+    sock.send_to(&build_query_opts(domain, RecordType::AAAA, id_aaaa, qo)?, &target)?;
 
-    // 1) Query A
-    let (mut records, rcode_a) = send_and_parse(domain, server, timeout_ms, RecordType::A)?;
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let (mut a, mut aaaa) = (None, None);
+    let mut recv = vec![0u8; qo.recv_buffer];
+    while a.is_none() || aaaa.is_none() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() { break; }
+        sock.set_read_timeout(Some(left))?;
+        let n = match sock.recv(&mut recv) { Ok(n) => n, Err(_) => break };
+        // 无法解析或 id 不匹配的包直接丢弃，继续等待
+        let Ok((id, ans)) = parse_answer(&recv[..n]) else { continue };
+        if id == id_a && a.is_none() { a = Some(ans); }
+        else if id == id_aaaa && aaaa.is_none() { aaaa = Some(ans); }
+        if a.as_ref().is_some_and(has_ip) { break; }
+    }
+    Ok((a, aaaa))
+}
+
+fn has_ip(ans: &DnsAnswer) -> bool {
+    ans.records.iter().any(|r| r.rtype == "A" || r.rtype == "AAAA")
+}
+
+pub fn udp_query_full(domain: &str, server: &str, timeout_ms: u64, qo: &QueryOptions) -> Result<DnsAnswer> {
+    if qo.a_only {
+        return send_and_parse(domain, server, timeout_ms, RecordType::A, qo);
+    }
+    // 1) A + AAAA pipelined; a side that didn't arrive in the window is retried sequentially
+    let (a, aaaa) = send_pair(domain, server, timeout_ms, qo)?;
+    if a.is_none() && aaaa.is_none() {
+        // 两个都没回来：解析器无响应，不再顺序重发
+        return Ok(timed_out());
+    }
+    let DnsAnswer { mut records, rcode: rcode_a, authentic_data, mut rrsig } = match a {
+        Some(r) => r,
+        None => send_and_parse(domain, server, timeout_ms, RecordType::A, qo)?,
+    };
     let has_ip = records.iter().any(|r| r.rtype == "A" || r.rtype == "AAAA");
     let cname_target = records.iter().find(|r| r.rtype == "CNAME").map(|r| r.data.clone());
 
-    // 2) If no IPs found, query AAAA
+    // 2) AAAA only counts when A gave no IPs (same result shape as the sequential path)
     if !has_ip {
-        let (mut rec_aaaa, _rcode_aaaa) = send_and_parse(domain, server, timeout_ms, RecordType::AAAA)?;
-        if !rec_aaaa.is_empty() { records.append(&mut rec_aaaa); }
+        let mut aaaa = match aaaa {
+            Some(r) => r,
+            None => send_and_parse(domain, server, timeout_ms, RecordType::AAAA, qo)?,
+        };
+        rrsig |= aaaa.rrsig;
+        records.append(&mut aaaa.records);
     }
 
-    // 3) If still no IPs and have a CNAME, chase it once with A
-    let has_ip_now = records.iter().any(|r| r.rtype == "A" || r.rtype == "AAAA");
-    if !has_ip_now {
-        if let Some(cn) = cname_target {
-            if let Ok((mut rec_cname_a, _)) = send_and_parse(&cn, server, timeout_ms, RecordType::A) {
-                if !rec_cname_a.is_empty() { records.append(&mut rec_cname_a); }
-            }
+    // 3) If still no IPs and have a CNAME, chase it with A (up to `cname_depth` hops);
+    //    a name seen twice means a loop: stop and leave a CNAME-LOOP marker instead of burning queries
+    let mut has_ip_now = records.iter().any(|r| r.rtype == "A" || r.rtype == "AAAA");
+    let mut visited = std::collections::HashSet::from([chain_key(domain)]);
+    let mut next = cname_target;
+    let mut hops = 0;
+    while !has_ip_now {
+        let Some(cn) = next.take() else { break };
+        if !visited.insert(chain_key(&cn)) {
+            records.push(RawRecord { rtype: CNAME_LOOP.into(), data: cn, ttl: 0 });
+            break;
         }
+        if hops >= qo.cname_depth { break; }
+        hops += 1;
+        let Ok(DnsAnswer { records: mut rec_cname_a, .. }) = send_and_parse(&cn, server, timeout_ms, RecordType::A, qo) else { break };
+        has_ip_now = rec_cname_a.iter().any(|r| r.rtype == "A" || r.rtype == "AAAA");
+        next = rec_cname_a.iter().find(|r| r.rtype == "CNAME").map(|r| r.data.clone());
+        records.append(&mut rec_cname_a);
     }
 
-    Ok(DnsAnswer { records, rcode: rcode_a })
+    Ok(DnsAnswer { records, rcode: rcode_a, authentic_data, rrsig })
+}
+
+fn chain_key(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Single query of any type, no AAAA / CNAME follow-ups (`rusub resolve --rtype`).
+pub fn udp_query_type(domain: &str, server: &str, timeout_ms: u64, qtype: RecordType, qo: &QueryOptions) -> Result<DnsAnswer> {
+    send_and_parse(domain, server, timeout_ms, qtype, qo)
+}
+
+/// Single PTR query for an `in-addr.arpa` / `ip6.arpa` name (reverse mode).
+pub fn udp_query_ptr(name: &str, server: &str, timeout_ms: u64, qo: &QueryOptions) -> Result<DnsAnswer> {
+    send_and_parse(name, server, timeout_ms, RecordType::PTR, qo)
+}
+
+/// DNS transport used by the scanner and the wildcard probes. [`UdpClient`] is the real one;
+/// [`crate::mockdns::MockDnsClient`] answers from a script so the retry / wildcard / dedup
+/// logic can be tested without sockets.
+///
+/// `rtype` [`RecordType::A`] is a host lookup the way the scanner does it (A, plus AAAA and the
+/// CNAME chain unless `qo.a_only`, see [`udp_query_full`]); any other type is a single query.
+/// An unanswered query is `Ok` with rcode `TIMEOUT`; `Err` is for queries that never left
+/// (e.g. [`BindError`]). Called from the blocking pool.
+pub trait DnsClient: Send + Sync {
+    fn query(&self, host: &str, resolver: &str, rtype: RecordType, timeout_ms: u64, qo: &QueryOptions) -> Result<DnsAnswer>;
 }
 
-pub fn query_ns_names(domain: &str, server: &str, timeout_ms: u64) -> Result<Vec<String>> {
+/// Plain UDP (or the shared raw socket with `--raw`), one socket per query.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UdpClient;
+
+impl DnsClient for UdpClient {
+    fn query(&self, host: &str, resolver: &str, rtype: RecordType, timeout_ms: u64, qo: &QueryOptions) -> Result<DnsAnswer> {
+        match rtype {
+            RecordType::A => udp_query_full(host, resolver, timeout_ms, qo),
+            other => send_and_parse(host, resolver, timeout_ms, other, qo),
+        }
+    }
+}
+
+pub fn query_ns_names(domain: &str, server: &str, timeout_ms: u64, source: Option<IpAddr>) -> Result<Vec<String>> {
     use trust_dns_proto::rr::RData;
     let packet = build_query(domain, RecordType::NS)?;
-    let sock = UdpSocket::bind("0.0.0.0:0")?;
+    let sock = bind_udp(source)?;
     sock.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
     sock.send_to(&packet, format!("{}:53", server))?;
-    let mut recv = [0u8; 2048];
+    let mut recv = [0u8; DEFAULT_RECV_BUFFER];
     match sock.recv(&mut recv) {
         Ok(n) => {
             let bytes = &recv[..n];
@@ -168,14 +501,15 @@ pub fn query_ns_names(domain: &str, server: &str, timeout_ms: u64) -> Result<Vec
     }
 }
 
-pub async fn fetch_ns_ips(domain: &str, resolvers: &Vec<String>, timeout_secs: u64) -> Vec<String> {
+/// NS 地址发现；`source` 为 `--source-ip`，与扫描查询走同一源地址
+pub async fn fetch_ns_ips(domain: &str, resolvers: &Vec<String>, timeout_secs: u64, source: Option<IpAddr>) -> Vec<String> {
     use tokio::net::lookup_host;
     let server = resolvers.get(0).cloned().unwrap_or_else(|| "8.8.8.8".to_string());
     let timeout_ms = timeout_secs * 1000;
     let names = match tokio::task::spawn_blocking({
         let d = domain.to_string();
         let s = server.clone();
-        move || query_ns_names(&d, &s, timeout_ms)
+        move || query_ns_names(&d, &s, timeout_ms, source)
     }).await {
         Ok(Ok(v)) => v,
         _ => vec![],
@@ -190,3 +524,263 @@ pub async fn fetch_ns_ips(domain: &str, resolvers: &Vec<String>, timeout_secs: u
     ips.sort(); ips.dedup();
     ips
 }
+
+/// 递归探测用的固定名称：任何递归解析器都能应答，且不属于被扫描的区域
+const RECURSION_PROBE_NAME: &str = "a.root-servers.net";
+
+/// 应答是否来自可用的递归解析器：RA=1 且未被拒绝 / SERVFAIL
+pub fn reply_is_recursive(bytes: &[u8]) -> bool {
+    use trust_dns_proto::op::ResponseCode;
+    match Message::from_bytes(bytes) {
+        Ok(msg) => msg.message_type() == MessageType::Response
+            && msg.recursion_available()
+            && !matches!(msg.response_code(), ResponseCode::Refused | ResponseCode::ServFail),
+        Err(_) => false,
+    }
+}
+
+fn probe_recursive_addr(addr: &str, timeout_ms: u64, source: Option<IpAddr>) -> bool {
+    let Ok(packet) = build_query(RECURSION_PROBE_NAME, RecordType::A) else { return false };
+    let Ok(sock) = bind_udp(source) else { return false };
+    if sock.set_read_timeout(Some(Duration::from_millis(timeout_ms))).is_err() { return false; }
+    if sock.send_to(&packet, addr).is_err() { return false; }
+    let mut recv = [0u8; DEFAULT_RECV_BUFFER];
+    match sock.recv(&mut recv) {
+        Ok(n) => reply_is_recursive(&recv[..n]),
+        Err(_) => false,
+    }
+}
+
+/// 发一次 RD=1 查询，判断 server 是否愿意为我们递归 (权威 NS 通常 REFUSED 或 RA=0)
+pub fn probe_recursive(server: &str, timeout_ms: u64, source: Option<IpAddr>) -> bool {
+    probe_recursive_addr(&format!("{}:53", server), timeout_ms, source)
+}
+
+/// 并发探测，只保留可递归的 IPv4 地址 (与批量查询的 0.0.0.0 socket 一致)
+pub async fn filter_recursive(ips: Vec<String>, timeout_secs: u64, source: Option<IpAddr>) -> Vec<String> {
+    let timeout_ms = timeout_secs * 1000;
+    let checks = ips.into_iter()
+        .filter(|ip| ip.parse::<std::net::Ipv4Addr>().is_ok())
+        .map(|ip| async move {
+            let s = ip.clone();
+            let ok = tokio::task::spawn_blocking(move || probe_recursive(&s, timeout_ms, source)).await.unwrap_or(false);
+            if ok { Some(ip) } else { tracing::debug!("[ns] {} does not recurse, not used as resolver", ip); None }
+        });
+    futures::future::join_all(checks).await.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trust_dns_proto::op::ResponseCode;
+
+    /// 本地假 DNS：对收到的第一个查询回一个由 `fill` 定制的应答
+    fn fake_server_with(fill: impl FnOnce(&Message, &mut Message) + Send + 'static) -> String {
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = sock.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            if let Ok((n, peer)) = sock.recv_from(&mut buf) {
+                let q = Message::from_bytes(&buf[..n]).unwrap();
+                let mut r = Message::new();
+                r.set_id(q.id());
+                r.set_message_type(MessageType::Response);
+                r.set_recursion_desired(true);
+                r.add_queries(q.queries().to_vec());
+                fill(&q, &mut r);
+                let _ = sock.send_to(&r.to_bytes().unwrap(), peer);
+            }
+        });
+        addr
+    }
+
+    fn fake_server(recursion_available: bool, rcode: ResponseCode) -> String {
+        fake_server_with(move |_, r| {
+            r.set_recursion_available(recursion_available);
+            r.set_response_code(rcode);
+        })
+    }
+
+    #[test]
+    fn large_reply_is_not_truncated() {
+        use trust_dns_proto::rr::{RData, Record};
+        let advertised = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let seen = advertised.clone();
+        let server = fake_server_with(move |q, r| {
+            seen.store(q.extensions().as_ref().map_or(0, |e| e.max_payload() as usize), std::sync::atomic::Ordering::SeqCst);
+            let name = q.queries()[0].name().clone();
+            for i in 0..200u8 {
+                r.add_answer(Record::from_rdata(name.clone(), 60, RData::A(std::net::Ipv4Addr::new(10, 0, i, 1))));
+            }
+        });
+        let qo = QueryOptions { a_only: true, ..QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID)) };
+        let ans = udp_query_full("big.example.com", &server, 2000, &qo).unwrap();
+        assert_eq!(ans.rcode, "NoError");
+        assert_eq!(ans.records.len(), 200, "a ~3.2KB reply must survive the default 4096-byte buffer");
+        // a real server only sends more than 512 bytes if the query advertises the size
+        assert_eq!(advertised.load(std::sync::atomic::Ordering::SeqCst), DEFAULT_RECV_BUFFER, "OPT advertises the receive buffer");
+    }
+
+    #[test]
+    fn pair_does_not_wait_for_aaaa_once_a_has_ips() {
+        use trust_dns_proto::rr::{RData, Record};
+        // answers only the first query (A); the AAAA query is dropped
+        let server = fake_server_with(|q, r| {
+            r.add_answer(Record::from_rdata(q.queries()[0].name().clone(), 60, RData::A(std::net::Ipv4Addr::new(192, 0, 2, 9))));
+        });
+        let qo = QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID));
+        let started = Instant::now();
+        let ans = udp_query_full("www.example.com", &server, 3000, &qo).unwrap();
+        assert_eq!(ans.records[0].data, "192.0.2.9");
+        assert!(started.elapsed() < Duration::from_millis(1500), "waited {:?} for a discarded AAAA", started.elapsed());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn raw_transport_round_trip() {
+        use trust_dns_proto::rr::{RData, Record};
+        // needs CAP_NET_RAW; nothing to check without it
+        let Ok(raw) = crate::rawsock::RawTransport::open(None) else { return };
+        let server = fake_server_with(|q, r| {
+            r.add_answer(Record::from_rdata(q.queries()[0].name().clone(), 60, RData::A(std::net::Ipv4Addr::new(192, 0, 2, 7))));
+        });
+        let qo = QueryOptions { a_only: true, raw: Some(raw), ..QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID)) };
+        let ans = udp_query_full("raw.example.com", &server, 2000, &qo).unwrap();
+        assert_eq!(ans.rcode, "NoError");
+        assert_eq!(ans.records[0].data, "192.0.2.7");
+    }
+
+    #[test]
+    fn bind_error_is_typed() {
+        let e: anyhow::Error = BindError(std::io::Error::from(std::io::ErrorKind::AddrNotAvailable)).into();
+        assert!(e.is::<BindError>());
+        assert!(e.to_string().starts_with("local UDP socket bind failed"));
+        assert!(bind_udp(None).is_ok());
+    }
+
+    #[test]
+    fn source_ip_is_bound_and_checked() {
+        let lo = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        assert_eq!(bind_udp(Some(lo)).unwrap().local_addr().unwrap().ip(), lo);
+        assert!(check_source_ip(lo).is_ok());
+        // TEST-NET-1 is never assigned to a local interface
+        let err = check_source_ip("192.0.2.123".parse().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("--source-ip 192.0.2.123"));
+    }
+
+    #[test]
+    fn ecs_option_is_encoded() {
+        use trust_dns_proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+        let ecs: ClientSubnet = "203.0.113.77/22".parse().unwrap();
+        assert_eq!(ecs.option_data(), vec![0, 1, 22, 0, 203, 0, 112]);
+        assert_eq!("2001:db8::1/32".parse::<ClientSubnet>().unwrap().option_data(), vec![0, 2, 32, 0, 0x20, 0x01, 0x0d, 0xb8]);
+        assert_eq!("198.51.100.1".parse::<ClientSubnet>().unwrap().to_string(), "198.51.100.1/32");
+        assert!("10.0.0.0/33".parse::<ClientSubnet>().is_err());
+        let qo = QueryOptions { ecs: Some(ecs), ..QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID)) };
+        let msg = Message::from_bytes(&build_query_opts("www.example.com", RecordType::A, 7, &qo).unwrap()).unwrap();
+        let edns = msg.extensions().as_ref().expect("OPT record");
+        assert_eq!(edns.max_payload() as usize, DEFAULT_RECV_BUFFER);
+        match edns.option(EdnsCode::Subnet) {
+            Some(EdnsOption::Unknown(8, data)) => assert_eq!(data, &ecs.option_data()),
+            other => panic!("unexpected ECS option: {:?}", other),
+        }
+        let plain = Message::from_bytes(&build_query_opts("www.example.com", RecordType::A, 7, &QueryOptions { ecs: None, ..qo }).unwrap()).unwrap();
+        assert!(plain.extensions().as_ref().expect("OPT record").option(EdnsCode::Subnet).is_none());
+    }
+
+    #[test]
+    fn cname_two_cycle_is_cut_short() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use trust_dns_proto::rr::{RData, Record};
+        // a.loop.test -> b.loop.test -> a.loop.test, answering every query until idle
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = sock.local_addr().unwrap().to_string();
+        let queries = std::sync::Arc::new(AtomicUsize::new(0));
+        let seen = queries.clone();
+        sock.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = sock.recv_from(&mut buf) {
+                seen.fetch_add(1, Ordering::SeqCst);
+                let q = Message::from_bytes(&buf[..n]).unwrap();
+                let name = q.queries()[0].name().clone();
+                let target = if name.to_utf8().starts_with("a.") { "b.loop.test." } else { "a.loop.test." };
+                let mut r = Message::new();
+                r.set_id(q.id());
+                r.set_message_type(MessageType::Response);
+                r.add_queries(q.queries().to_vec());
+                r.add_answer(Record::from_rdata(name, 60, RData::CNAME(Name::from_utf8(target).unwrap())));
+                let _ = sock.send_to(&r.to_bytes().unwrap(), peer);
+            }
+        });
+        let qo = QueryOptions { cname_depth: 8, ..QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID)) };
+        let ans = udp_query_full("a.loop.test", &server, 2000, &qo).unwrap();
+        let kinds: Vec<(&str, &str)> = ans.records.iter().map(|r| (r.rtype.as_str(), r.data.as_str())).collect();
+        // A and AAAA both carry a's CNAME, then the chased b points back at a
+        assert_eq!(kinds, [("CNAME", "b.loop.test."), ("CNAME", "b.loop.test."), ("CNAME", "a.loop.test."), (CNAME_LOOP, "a.loop.test.")]);
+        // A + AAAA for a, one chase for b; the loop stops it long before the depth limit
+        assert_eq!(queries.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn dnssec_sets_do_and_reads_ad_and_rrsig() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use trust_dns_proto::rr::{rdata::NULL, RData, Record};
+        let saw_do = Arc::new(AtomicBool::new(false));
+        let seen = saw_do.clone();
+        let server = fake_server_with(move |q, r| {
+            seen.store(q.extensions().as_ref().is_some_and(|e| e.dnssec_ok()), Ordering::SeqCst);
+            let name = Name::from_utf8("www.example.com").unwrap();
+            r.set_authentic_data(true);
+            r.add_answer(Record::from_rdata(name.clone(), 300, RData::A("192.0.2.1".parse().unwrap())));
+            r.add_answer(Record::from_rdata(name, 300, RData::Unknown { code: u16::from(RecordType::RRSIG), rdata: NULL::with(vec![0; 18]) }));
+        });
+        let qo = QueryOptions { a_only: true, dnssec_ok: true, ..QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID)) };
+        let ans = udp_query_full("www.example.com", &server, 2000, &qo).unwrap();
+        assert!(saw_do.load(Ordering::SeqCst), "DO bit set in the OPT record");
+        assert_eq!((ans.authentic_data, ans.rrsig), (true, true));
+        assert_eq!(ans.records.len(), 1, "RRSIG is a flag, not a result record");
+        let plain = Message::from_bytes(&build_query_opts("www.example.com", RecordType::A, 7, &QueryOptions { dnssec_ok: false, ..qo }).unwrap()).unwrap();
+        assert!(!plain.extensions().as_ref().expect("OPT record").dnssec_ok());
+    }
+
+    #[test]
+    fn header_flags_follow_query_options() {
+        let qo = |rd: bool, cd: bool, ad: bool| QueryOptions { recursion_desired: rd, checking_disabled: cd, authentic_data: ad, ..QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID)) };
+        let flags = |qo: &QueryOptions| {
+            let m = Message::from_bytes(&build_query_opts("www.example.com", RecordType::A, 7, qo).unwrap()).unwrap();
+            (m.recursion_desired(), m.checking_disabled(), m.authentic_data())
+        };
+        assert_eq!(flags(&QueryOptions::new(crate::rng::shared_rng(Some(1), crate::rng::STREAM_QUERY_ID))), (true, false, false), "defaults: RD=1 only");
+        assert_eq!(flags(&qo(false, false, false)), (false, false, false));
+        assert_eq!(flags(&qo(true, true, false)), (true, true, false));
+        assert_eq!(flags(&qo(true, false, true)), (true, false, true));
+        assert_eq!(flags(&qo(false, true, true)), (false, true, true));
+    }
+
+    #[test]
+    fn rd_bit_and_referral_detection() {
+        let q = Message::from_bytes(&build_query_rd("www.example.com", RecordType::A, 7, false).unwrap()).unwrap();
+        assert!(!q.recursion_desired());
+        assert!(Message::from_bytes(&build_query_with_id("www.example.com", RecordType::A, 7).unwrap()).unwrap().recursion_desired());
+
+        use trust_dns_proto::rr::{RData, Record};
+        let mut r = Message::new();
+        r.set_message_type(MessageType::Response);
+        r.add_name_server(Record::from_rdata(Name::from_utf8("dev.example.com").unwrap(), 300, RData::NS(Name::from_utf8("ns1.dev.example.com").unwrap())));
+        let (_, ans) = parse_answer(&r.to_bytes().unwrap()).unwrap();
+        assert!(ans.records.is_empty());
+        assert_eq!(ans.rcode, "Referral");
+        // same shape but authoritative (AA=1) is NODATA, not a referral
+        r.set_authoritative(true);
+        assert_eq!(parse_answer(&r.to_bytes().unwrap()).unwrap().1.rcode, "NoError");
+    }
+
+    #[test]
+    fn authoritative_only_ns_is_not_recursive() {
+        assert!(!probe_recursive_addr(&fake_server(false, ResponseCode::Refused), 2000, None));
+        assert!(!probe_recursive_addr(&fake_server(false, ResponseCode::NoError), 2000, None));
+        assert!(probe_recursive_addr(&fake_server(true, ResponseCode::NoError), 2000, None));
+    }
+}
@@ -0,0 +1,165 @@
+//! Block-gzip output with a sidecar index (`--gzip-index`).
+//!
+//! The output file is a sequence of independent gzip members, each holding whole
+//! lines (about `BLOCK_SIZE` bytes uncompressed). Concatenated members are still a
+//! valid gzip file, so `zcat` / `gunzip` / `MultiGzDecoder` read it unchanged.
+//!
+//! `<output>.idx` is plain text with one line per member:
+//!
+//! ```text
+//! <compressed_offset> <uncompressed_offset> <first_line>
+//! ```
+//!
+//! Decimal, space separated, all 0-based. To read from line N: take the last entry
+//! with `first_line <= N`, seek the `.gz` file to `compressed_offset`, start a fresh
+//! gzip decoder there and skip `N - first_line` lines.
+//!
+//! `flush()` closes the pending (partial) block as its own member, so the periodic
+//! `--output-flush-ms` flush puts results and index entries on disk during the scan.
+//! With `--output-flush-ms 0` that means one member per line; keep a non-zero cadence.
+
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Uncompressed bytes per gzip member.
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+/// `results.jsonl.gz` -> `results.jsonl.gz.idx`
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".idx");
+    PathBuf::from(s)
+}
+
+pub struct BlockGzWriter {
+    out: BufWriter<File>,
+    index: BufWriter<File>,
+    buf: Vec<u8>,
+    block_size: usize,
+    compressed_off: u64,
+    uncompressed_off: u64,
+    lines: u64,
+}
+
+impl BlockGzWriter {
+    /// Truncates `path` and its index; appending to an indexed file is not supported.
+    pub fn create(path: &Path) -> Result<Self> {
+        Self::with_block_size(path, BLOCK_SIZE)
+    }
+
+    pub fn with_block_size(path: &Path, block_size: usize) -> Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            index: BufWriter::new(File::create(index_path(path))?),
+            buf: Vec::with_capacity(block_size + 4096),
+            block_size: block_size.max(1),
+            compressed_off: 0,
+            uncompressed_off: 0,
+            lines: 0,
+        })
+    }
+
+    fn emit_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() { return Ok(()); }
+        let mut enc = GzEncoder::new(Vec::with_capacity(self.buf.len() / 3), Compression::default());
+        enc.write_all(&self.buf)?;
+        let member = enc.finish()?;
+        self.out.write_all(&member)?;
+        self.out.flush()?;
+        writeln!(self.index, "{} {} {}", self.compressed_off, self.uncompressed_off, self.lines)?;
+        self.index.flush()?;
+        self.compressed_off += member.len() as u64;
+        self.uncompressed_off += self.buf.len() as u64;
+        self.lines += self.buf.iter().filter(|&&b| b == b'\n').count() as u64;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Write out the pending partial block.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.emit_block()?;
+        self.out.flush()
+    }
+}
+
+impl Write for BlockGzWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        // 只在行尾切块，保证每个 member 都从完整行开始
+        if self.buf.len() >= self.block_size && self.buf.ends_with(b"\n") {
+            self.emit_block()?;
+        }
+        Ok(data.len())
+    }
+
+    /// Emits the pending block early (only at a line end, so every member still starts on a
+    /// whole line) and pushes it and its index entry to disk.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.ends_with(b"\n") {
+            self.emit_block()?;
+        }
+        self.out.flush()
+    }
+}
+
+impl Drop for BlockGzWriter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::{GzDecoder, MultiGzDecoder};
+    use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+
+    #[test]
+    fn members_are_seekable_via_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.jsonl.gz");
+        let lines: Vec<String> = (0..2000).map(|i| format!("{{\"subdomain\":\"h{}.example.com\"}}", i)).collect();
+        {
+            let mut w = BlockGzWriter::with_block_size(&path, 4096).unwrap();
+            for l in lines.iter() { writeln!(w, "{}", l).unwrap(); }
+        }
+        // whole file still decodes as ordinary gzip
+        let mut all = String::new();
+        MultiGzDecoder::new(File::open(&path).unwrap()).read_to_string(&mut all).unwrap();
+        assert_eq!(all.lines().collect::<Vec<_>>(), lines);
+
+        let index = std::fs::read_to_string(index_path(&path)).unwrap();
+        let entries: Vec<Vec<u64>> = index.lines().map(|l| l.split(' ').map(|x| x.parse().unwrap()).collect()).collect();
+        assert!(entries.len() > 5);
+        let e = &entries[entries.len() / 2];
+        let mut f = File::open(&path).unwrap();
+        f.seek(SeekFrom::Start(e[0])).unwrap();
+        let first = BufReader::new(GzDecoder::new(f)).lines().next().unwrap().unwrap();
+        assert_eq!(first, lines[e[2] as usize]);
+        assert_eq!(e[1], lines[..e[2] as usize].iter().map(|l| l.len() as u64 + 1).sum::<u64>());
+    }
+
+    #[test]
+    fn flush_puts_pending_lines_and_index_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.jsonl.gz");
+        let mut w = BlockGzWriter::create(&path).unwrap();
+        writeln!(w, "a.example.com").unwrap();
+        writeln!(w, "b.example.com").unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0, "still buffered below the block size");
+        w.flush().unwrap();
+        // readable while the writer is still open
+        let mut got = String::new();
+        MultiGzDecoder::new(File::open(&path).unwrap()).read_to_string(&mut got).unwrap();
+        assert_eq!(got, "a.example.com\nb.example.com\n");
+        assert_eq!(std::fs::read_to_string(index_path(&path)).unwrap(), "0 0 0\n");
+        writeln!(w, "c.example.com").unwrap();
+        drop(w);
+        let index = std::fs::read_to_string(index_path(&path)).unwrap();
+        assert_eq!(index.lines().last().unwrap().split(' ').nth(2), Some("2"), "next member starts at line 2");
+    }
+}
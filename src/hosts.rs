@@ -0,0 +1,58 @@
+//! Static host overrides from the system hosts file (`--use-hosts`).
+//! Names found here are answered locally instead of being sent to a resolver.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// 小写主机名 -> IP 列表 (保持文件中的出现顺序，去重)
+pub type HostsMap = HashMap<String, Vec<String>>;
+
+pub fn system_hosts_path() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".into());
+        PathBuf::from(root).join(r"System32\drivers\etc\hosts")
+    }
+    #[cfg(not(windows))]
+    {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+/// Parse hosts-file text: `IP name [alias...]`, `#` comments, IPv4 and IPv6.
+pub fn parse_hosts(text: &str) -> HostsMap {
+    let mut map: HostsMap = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let Some(ip) = fields.next() else { continue };
+        // 带 zone 的链路本地地址 (fe80::1%lo0) 去掉 zone 后再校验
+        let Ok(ip) = ip.split('%').next().unwrap_or(ip).parse::<IpAddr>() else { continue };
+        for name in fields {
+            let ips = map.entry(name.trim_end_matches('.').to_ascii_lowercase()).or_default();
+            let ip = ip.to_string();
+            if !ips.contains(&ip) { ips.push(ip); }
+        }
+    }
+    map
+}
+
+pub fn load_system_hosts() -> Result<HostsMap> {
+    Ok(parse_hosts(&std::fs::read_to_string(system_hosts_path())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parses_v4_v6_and_aliases() {
+        let text = "# comment\n127.0.0.1 localhost\n10.0.0.5  api.staging.example.com  API2.staging.example.com. # trailing\n::1 localhost ip6-localhost\nbogus line\n10.0.0.6 api.staging.example.com\n";
+        let m = parse_hosts(text);
+        assert_eq!(m["localhost"], ["127.0.0.1", "::1"]);
+        assert_eq!(m["api.staging.example.com"], ["10.0.0.5", "10.0.0.6"]);
+        assert_eq!(m["api2.staging.example.com"], ["10.0.0.5"]);
+        assert!(!m.contains_key("line"));
+    }
+}
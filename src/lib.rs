@@ -1,3 +1,27 @@
+//! rusub — 高速异步子域枚举。
+//!
+//! 作为库使用时，[`scanner::enumerate`] 以 `Stream` 形式返回结果，不写文件、不打印 stdout：
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use rusub::options::Options;
+//!
+//! # async fn demo() {
+//! let opt = Options {
+//!     domains: vec!["example.com".into()],
+//!     resolvers: vec!["1.1.1.1".into(), "8.8.8.8".into()],
+//!     heuristic_max: 256,
+//!     ..Default::default()
+//! };
+//! let mut results = Box::pin(rusub::scanner::enumerate(opt));
+//! while let Some(r) = results.next().await {
+//!     println!("{} {:?}", r.subdomain, r.answers);
+//! }
+//! # }
+//! ```
+//!
+//! 命令行入口 `scanner::run` 只是在其上加了文件 / stdout 输出。
+
 pub mod cli;
 pub mod options;
 pub mod state;
@@ -10,3 +34,23 @@ pub mod metrics;
 pub mod discovery;
 pub mod resolver_pool;
 pub mod dicts;
+pub mod rng;
+pub mod manifest;
+pub mod logging;
+pub mod hosts;
+pub mod reverse;
+pub mod probe;
+pub mod gzindex;
+pub mod takeover;
+pub mod ttlcache;
+pub mod device;
+pub mod lookup;
+pub mod mockdns;
+#[cfg(target_os = "linux")]
+pub mod rawsock;
+#[cfg(feature = "asn")]
+pub mod asn;
+#[cfg(all(unix, feature = "control-socket"))]
+pub mod control;
+#[cfg(feature = "result-socket")]
+pub mod resultsock;
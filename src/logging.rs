@@ -0,0 +1,69 @@
+//! Diagnostics go through `tracing` to stderr; results stay on stdout.
+
+use tracing::level_filters::LevelFilter;
+
+/// Map `--log-level` onto a filter. In pure-output mode info/debug chatter is
+/// capped at warn so only problems reach stderr; `silent` turns logging off entirely.
+pub fn level_filter(level: &str, pure_output: bool) -> LevelFilter {
+    let lf = match level.to_ascii_lowercase().as_str() {
+        "silent" => LevelFilter::OFF,
+        "error" => LevelFilter::ERROR,
+        "warn" => LevelFilter::WARN,
+        "debug" => LevelFilter::DEBUG,
+        _ => LevelFilter::INFO,
+    };
+    if pure_output { lf.min(LevelFilter::WARN) } else { lf }
+}
+
+pub fn init(level: &str, pure_output: bool) {
+    use std::io::IsTerminal;
+    let _ = tracing_subscriber::fmt()
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_max_level(level_filter(level, pure_output))
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .without_time()
+        .try_init();
+}
+
+/// `--json-errors`: classify a fatal error by the first I/O error in its chain. Socket-level
+/// failures are `network`, other I/O (unreadable files, bad paths) `io`, and anything without an
+/// I/O cause (bad options, empty resolver list, ...) `config`.
+pub fn error_kind(e: &anyhow::Error) -> &'static str {
+    use std::io::ErrorKind::*;
+    let Some(io) = e.chain().find_map(|c| c.downcast_ref::<std::io::Error>()) else { return "config" };
+    match io.kind() {
+        ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected | AddrInUse
+        | AddrNotAvailable | TimedOut | HostUnreachable | NetworkUnreachable | NetworkDown => "network",
+        _ => "io",
+    }
+}
+
+/// One-line JSON object for a fatal error: `{"error":"<chain>","kind":"config|io|network"}`.
+pub fn error_json(e: &anyhow::Error) -> String {
+    serde_json::json!({ "error": format!("{:#}", e), "kind": error_kind(e) }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn pure_output_caps_verbosity() {
+        assert_eq!(level_filter("debug", false), LevelFilter::DEBUG);
+        assert_eq!(level_filter("debug", true), LevelFilter::WARN);
+        assert_eq!(level_filter("error", true), LevelFilter::ERROR);
+        assert_eq!(level_filter("silent", false), LevelFilter::OFF);
+    }
+
+    #[test]
+    fn fatal_errors_are_classified() {
+        use anyhow::Context;
+        let config = anyhow::anyhow!("no usable resolvers (given: none)");
+        assert_eq!(error_json(&config), r#"{"error":"no usable resolvers (given: none)","kind":"config"}"#);
+        let io: anyhow::Error = std::fs::read("/nonexistent/resolvers.txt").context("read resolvers file").unwrap_err();
+        assert_eq!(error_kind(&io), "io");
+        assert!(error_json(&io).starts_with(r#"{"error":"read resolvers file: "#));
+        let net = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::AddrNotAvailable)).context("--source-ip 192.0.2.1");
+        assert_eq!(error_kind(&net), "network");
+    }
+}
@@ -0,0 +1,104 @@
+//! `rusub resolve`：不经过字典 / 泛解析 / 状态文件，对给定名称各查询一次并输出记录 (类似 dig)。
+
+use crate::dns::{udp_query_full, udp_query_type, DnsAnswer, QueryOptions};
+use crate::options::Options;
+use crate::output::{ScanRecord, ScanResult};
+use crate::resolver_pool::ResolverPool;
+use crate::rng::{shared_rng, STREAM_QUERY_ID, STREAM_RESOLVER};
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use trust_dns_proto::rr::RecordType;
+
+/// Record types `--rtype` accepts.
+pub const RTYPES: [&str; 9] = ["A", "AAAA", "CNAME", "TXT", "MX", "NS", "SOA", "SRV", "PTR"];
+
+/// One lookup per name against the resolver pool, results in input order. `A` runs the
+/// scan's A/AAAA/CNAME logic (`udp_query_full`); any other type is a single query of that type.
+pub async fn resolve(opt: &Options, names: Vec<String>, rtype: RecordType) -> Result<Vec<ScanResult>> {
+    if opt.resolvers.is_empty() { anyhow::bail!("no resolvers to query"); }
+    let pool = ResolverPool::new(opt.resolvers.clone());
+    pool.set_rng(shared_rng(opt.seed, STREAM_RESOLVER));
+    let qo = QueryOptions { recv_buffer: opt.recv_buffer.clamp(512, 65535), source_ip: opt.source_ip, ..QueryOptions::new(shared_rng(opt.seed, STREAM_QUERY_ID)) };
+    let timeout_ms = opt.timeout * 1000;
+    let results = stream::iter(names)
+        .map(|name| {
+            let resolver = pool.choose_random().unwrap_or_default();
+            let qo = qo.clone();
+            async move {
+                let started = std::time::Instant::now();
+                let (n, r) = (name.clone(), resolver.clone());
+                let ans = tokio::task::spawn_blocking(move || match rtype {
+                    RecordType::A => udp_query_full(&n, &r, timeout_ms, &qo),
+                    other => udp_query_type(&n, &r, timeout_ms, other, &qo),
+                }).await;
+                let ans = joined_answer(&name, ans)?;
+                Ok(to_result(name, rtype, ans, resolver, started.elapsed().as_millis() as u64))
+            }
+        })
+        .buffered(opt.concurrency.max(1))
+        .collect::<Vec<Result<ScanResult>>>()
+        .await;
+    results.into_iter().collect()
+}
+
+/// A query that failed to go out reads as `TIMEOUT` like an unanswered one; a worker that
+/// panicked or was cancelled is a bug, not a network condition, and fails the lookup.
+fn joined_answer(name: &str, joined: std::result::Result<Result<DnsAnswer>, tokio::task::JoinError>) -> Result<DnsAnswer> {
+    match joined {
+        Ok(Ok(a)) => Ok(a),
+        Ok(Err(_)) => Ok(DnsAnswer { rcode: "TIMEOUT".into(), ..Default::default() }),
+        Err(e) => {
+            tracing::error!("[resolve] lookup worker for {} failed: {}", name, e);
+            Err(anyhow::anyhow!("lookup worker for {} failed: {}", name, e))
+        }
+    }
+}
+
+/// `answers` carries the data of the asked type (A also takes AAAA), so text output lists
+/// MX / TXT / ... values too; an empty answer is tagged with its rcode (`NODATA` for NOERROR).
+fn to_result(name: String, rtype: RecordType, ans: DnsAnswer, resolver: String, latency_ms: u64) -> ScanResult {
+    let asked = rtype.to_string();
+    let answers: Vec<String> = ans.records.iter()
+        .filter(|r| r.rtype == asked || (rtype == RecordType::A && r.rtype == "AAAA"))
+        .map(|r| r.data.clone())
+        .collect();
+    let rcode = if ans.records.is_empty() {
+        Some(crate::scanner::negative_rcode(&ans.rcode).unwrap_or("NODATA").to_string())
+    } else {
+        None
+    };
+    let records: Vec<ScanRecord> = ans.records.into_iter().map(|r| ScanRecord { rtype: r.rtype, data: r.data, ttl: Some(r.ttl) }).collect();
+    ScanResult { subdomain: name, answers, records: (!records.is_empty()).then_some(records), resolver: Some(resolver), latency_ms: Some(latency_ms), rcode, ..Default::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::RawRecord;
+
+    fn raw(rtype: &str, data: &str) -> RawRecord {
+        RawRecord { rtype: rtype.into(), data: data.into(), ttl: 300 }
+    }
+
+    #[test]
+    fn answers_follow_the_asked_type() {
+        let ans = DnsAnswer { records: vec![raw("CNAME", "mx.example.net."), raw("MX", "10 mail.example.com.")], rcode: "NoError".into(), ..Default::default() };
+        let r = to_result("example.com".into(), RecordType::MX, ans, "1.1.1.1".into(), 12);
+        assert_eq!(r.answers, ["10 mail.example.com."]);
+        assert_eq!(r.records.unwrap().len(), 2);
+        assert_eq!(r.rcode, None);
+        let ans = DnsAnswer { records: vec![raw("A", "192.0.2.1"), raw("AAAA", "2001:db8::1")], rcode: "NoError".into(), ..Default::default() };
+        assert_eq!(to_result("www.example.com".into(), RecordType::A, ans, "1.1.1.1".into(), 3).answers, ["192.0.2.1", "2001:db8::1"]);
+        let empty = |rcode: &str| DnsAnswer { rcode: rcode.into(), ..Default::default() };
+        assert_eq!(to_result("x.example.com".into(), RecordType::TXT, empty("NXDomain"), "1.1.1.1".into(), 3).rcode.as_deref(), Some("NXDOMAIN"));
+        assert_eq!(to_result("x.example.com".into(), RecordType::TXT, empty("NoError"), "1.1.1.1".into(), 3).rcode.as_deref(), Some("NODATA"));
+    }
+    #[tokio::test]
+    async fn worker_panic_is_an_error_not_a_timeout() {
+        let joined = tokio::task::spawn_blocking(|| -> Result<DnsAnswer> { panic!("boom") }).await;
+        let err = joined_answer("www.example.com", joined).unwrap_err();
+        assert!(err.to_string().contains("www.example.com"), "{}", err);
+        let joined = tokio::task::spawn_blocking(|| -> Result<DnsAnswer> { anyhow::bail!("send failed") }).await;
+        assert_eq!(joined_answer("www.example.com", joined).unwrap().rcode, "TIMEOUT");
+    }
+}
@@ -1,21 +1,94 @@
 use rusub::cli::{Cli, Commands};
-use rusub::options::{band2rate, get_resolvers, Options, OptionMethod};
+use rusub::options::{apply_resolver_hosts, band2rate, get_resolvers, resolve_resolver_hosts, normalize_domain, read_resolver_file, read_weighted_resolver_file, Options, OptionMethod};
+use rusub::output::OutputWriter;
 use rusub::scanner;
 use anyhow::Result;
 use clap::{Parser, CommandFactory};
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+use tracing::{debug, info, warn};
+
+/// 退出码：0 = 有结果，1 = 错误 (配置/解析器等)，2 = 扫描完成但没有结果
+const EXIT_ERROR: u8 = 1;
+const EXIT_EMPTY: u8 = 2;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+async fn main() -> ExitCode {
+    // 参数解析失败时还没有 Cli，直接在命令行里查找 --json-errors
+    let json_errors = std::env::args_os().any(|a| a == "--json-errors");
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if json_errors && e.use_stderr() => {
+            // clap 的渲染结果是 "error: <原因>\n\nUsage: ..."：只取原因这一行
+            let rendered = e.render().to_string();
+            let reason = rendered.lines().next().unwrap_or_default().trim_start_matches("error: ");
+            eprintln!("{}", rusub::logging::error_json(&anyhow::anyhow!("{}", reason)));
+            return ExitCode::from(EXIT_ERROR);
+        }
+        Err(e) => e.exit(),
+    };
+    let always_success = match &cli.command { Commands::Enum(args) => args.always_success, Commands::Resolve(_) | Commands::Interfaces(_) => false };
+    let code = match run_cli(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            if json_errors { eprintln!("{}", rusub::logging::error_json(&e)); } else { eprintln!("Error: {:?}", e); }
+            ExitCode::from(EXIT_ERROR)
+        }
+    };
+    if always_success { ExitCode::SUCCESS } else { code }
+}
 
+async fn run_cli(cli: Cli) -> Result<ExitCode> {
     match cli.command {
+        Commands::Resolve(args) => {
+            rusub::logging::init(&args.common.log_level, false);
+            let mut names: Vec<String> = args.common.domains.iter().chain(args.common.positional_domains.iter()).cloned().collect();
+            if args.common.stdin {
+                names.extend(io::stdin().lock().lines().map_while(|l| l.ok()));
+            }
+            let names: Vec<String> = names.iter().filter_map(|raw| match normalize_domain(raw) {
+                Ok(d) => d,
+                Err(reason) => { warn!("[input] skipping name: {}", reason); None }
+            }).collect();
+            if names.is_empty() { anyhow::bail!("resolve needs at least one name (positional, -d or --stdin)"); }
+            let mut resolvers = get_resolvers(&args.common.resolvers);
+            let hosts = resolve_resolver_hosts(&resolvers).await?;
+            apply_resolver_hosts(&mut resolvers, &hosts);
+            let opt = Options {
+                method: OptionMethod::Verify,
+                resolvers,
+                timeout: args.timeout,
+                concurrency: args.common.concurrency,
+                source_ip: args.source_ip,
+                ..Default::default()
+            };
+            let rtype: rusub::dns::RecordType = args.rtype.to_ascii_uppercase().parse()?;
+            let results = rusub::lookup::resolve(&opt, names, rtype).await?;
+            let writers = rusub::output::build_writers(None, &args.output_type, true, false, false, false, &rusub::output::FormatOptions { detail: true, ..Default::default() })?;
+            for r in &results { writers.write(r)?; }
+            writers.close()?;
+            let found = results.iter().any(|r| r.records.as_ref().is_some_and(|v| !v.is_empty()));
+            return Ok(if found { ExitCode::SUCCESS } else { ExitCode::from(EXIT_EMPTY) });
+        }
+        Commands::Interfaces(args) => {
+            let ifs = rusub::device::list_interfaces(!args.all)?;
+            print!("{}", rusub::device::format_interfaces(&ifs));
+            println!();
+            println!("CAP_NET_RAW: {}", if rusub::device::has_cap_net_raw() { "yes" } else { "no" });
+            return Ok(ExitCode::SUCCESS);
+        }
         Commands::Enum(args) => {
-            if args.common.domains.is_empty() && args.common.positional_domains.is_empty() && !args.common.stdin && args.domain_list.is_none() && args.filename.is_none() {
+            if args.list_words {
+                let stdout = io::stdout();
+                let mut out = io::BufWriter::new(stdout.lock());
+                for w in rusub::dicts::default_wordlist() { writeln!(out, "{}", w)?; }
+                out.flush()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            if args.common.domains.is_empty() && args.common.positional_domains.is_empty() && !args.common.stdin && args.domain_list.is_none() && args.filename.is_empty() {
                 let mut cmd = Cli::command();
                 if let Some(sc) = cmd.find_subcommand_mut("enum") { let _ = sc.print_help(); println!(); }
-                return Ok(());
+                return Ok(ExitCode::SUCCESS);
             }
             let mut domains: Vec<String> = vec![];
             if !args.common.domains.is_empty() { domains.extend(args.common.domains.clone()); }
@@ -27,8 +100,7 @@ async fn main() -> Result<()> {
                 }
             }
             if let Some(list) = &args.domain_list {
-                let f = File::open(list)?;
-                for line in io::BufReader::new(f).lines() {
+                for line in rusub::options::open_text_input(list)?.lines() {
                     if let Ok(s) = line { domains.push(s); }
                 }
             }
@@ -36,22 +108,71 @@ async fn main() -> Result<()> {
             let rate = band2rate(&args.band)?;
             // 自动纯净模式：当输出为 json/jsonl 时，默认关闭所有非结果输出
             let auto_pure = if args.pure_output { true } else { matches!(args.output_type.to_lowercase().as_str(), "json" | "jsonl") };
-            if !auto_pure { println!("band '{}' => rate {} pkt/s", args.band, rate); }
-            let mut resolvers = get_resolvers(&args.common.resolvers);
+            rusub::logging::init(&args.common.log_level, auto_pure);
+            info!("band '{}' => rate {} pkt/s", args.band, rate);
+            // 输入域名规整：URL / 端口 / 大小写 / 尾点等在此统一处理，非法条目告警跳过 (反向模式输入是 IP/CIDR，不处理)
+            if !args.reverse {
+                let before = domains.len();
+                domains = domains.iter().filter_map(|raw| match normalize_domain(raw) {
+                    Ok(d) => d,
+                    Err(reason) => { warn!("[input] skipping domain: {}", reason); None }
+                }).collect();
+                if domains.is_empty() && before > 0 { anyhow::bail!("no valid domains in input"); }
+                // 同一根域重复出现 (多个 -d / 列表文件) 会让整个字典跑两遍并重复输出
+                let (unique, dups) = rusub::options::dedup_domains(domains);
+                domains = unique;
+                if dups > 0 { warn!("[input] removed {} duplicate domain(s), {} left", dups, domains.len()); }
+                let mut suffixes = args.exclude_domain_suffix.clone();
+                if let Some(p) = &args.exclude_domains_file {
+                    for line in rusub::options::open_text_input(p)?.lines() { suffixes.push(line?); }
+                }
+                let (kept, excluded) = rusub::options::exclude_by_suffix(domains, &suffixes);
+                domains = kept;
+                if excluded > 0 {
+                    info!("[input] excluded {} domain(s) by suffix, {} left", excluded, domains.len());
+                    if domains.is_empty() { anyhow::bail!("every input domain is excluded by --exclude-domain-suffix / --exclude-domains-file"); }
+                }
+            }
+            let mut resolver_weights = match &args.resolvers_file { Some(p) => read_weighted_resolver_file(p)?, None => Vec::new() };
+            let mut user_resolvers = args.common.resolvers.clone();
+            user_resolvers.extend(resolver_weights.iter().map(|(a, _)| a.clone()));
+            let mut resolvers = get_resolvers(&user_resolvers);
+            // --ns 探测在扫描器之前就要用到解析器：主机名条目在这里先解析 (扫描器对剩余条目同样处理)
+            let names = resolve_resolver_hosts(&resolvers).await?;
+            apply_resolver_hosts(&mut resolvers, &names);
+            for (addr, _) in resolver_weights.iter_mut() {
+                if let Some((_, ip)) = names.iter().find(|(n, _)| n == addr) { *addr = ip.clone(); }
+            }
 
             let mut injected = 0usize;
             if args.ns {
+                // NS 发现与探测同样从 --source-ip 发出：地址无效时在这里就报错
+                if let Some(ip) = args.source_ip { rusub::dns::check_source_ip(ip)?; }
+                let mut candidates: Vec<String> = Vec::new();
                 for d in domains.iter() {
-                    let ns_ips = rusub::dns::fetch_ns_ips(d, &resolvers, args.timeout).await;
-                    for ip in ns_ips { if !resolvers.contains(&ip) { resolvers.push(ip); injected += 1; } }
+                    let ns_ips = rusub::dns::fetch_ns_ips(d, &resolvers, args.timeout, args.source_ip).await;
+                    for ip in ns_ips { if !resolvers.contains(&ip) && !candidates.contains(&ip) { candidates.push(ip); } }
                 }
+                // 权威 NS 多数不提供递归，直接加入会稳定地 REFUSED 拖累整个池：先探测
+                let found = candidates.len();
+                for ip in rusub::dns::filter_recursive(candidates, args.timeout, args.source_ip).await { resolvers.push(ip); injected += 1; }
                 resolvers.sort(); resolvers.dedup();
-                if !auto_pure {
-                    println!("[ns] injected {} new NS resolver IP(s), total now {}", injected, resolvers.len());
-                }
+                info!("[ns] injected {} of {} NS IP(s) that answer recursive queries, total now {}", injected, found, resolvers.len());
             }
 
-            let mut gzip_flag = args.gzip;
+            let mut trusted_resolvers = args.trusted_resolvers.clone();
+            if let Some(p) = &args.trusted_resolvers_file { trusted_resolvers.extend(read_resolver_file(p)?); }
+            trusted_resolvers.sort(); trusted_resolvers.dedup();
+            if args.confirm_hits && trusted_resolvers.is_empty() {
+                anyhow::bail!("--confirm-hits requires --trusted-resolvers or --trusted-resolvers-file");
+            }
+
+            let mut primary_resolvers = args.resolver_primary.clone();
+            primary_resolvers.sort(); primary_resolvers.dedup();
+
+            let probe_ports = match &args.probe_ports { Some(s) => rusub::probe::parse_ports(s)?, None => Vec::new() };
+
+            let mut gzip_flag = args.gzip || args.gzip_index;
             if !gzip_flag {
                 if let Some(ref p) = args.output {
                     if let Some(os) = p.as_os_str().to_str() { if os.ends_with(".gz") { gzip_flag = true; } }
@@ -61,28 +182,40 @@ async fn main() -> Result<()> {
             // 当输出为 json/jsonl 且未显式指定 --only-alive 时，默认只输出存活结果
             let auto_only_alive = if args.only_alive { true } else { matches!(args.output_type.to_lowercase().as_str(), "json" | "jsonl") };
             
+            // 输出去重：json/jsonl 默认开启，可用 --dedup-output / --no-dedup-output 显式指定
+            let dedup_output = if args.no_dedup_output { false } else if args.dedup_output { true } else { matches!(args.output_type.to_lowercase().as_str(), "json" | "jsonl") };
+
             // 启发式模式：当没有指定 -f 时，自动启用启发式
-            let use_heuristic = args.filename.is_none();
+            // --heuristic / --no-heuristic 优先；未指定时只在使用内置字典 (无 -f) 时启用
+            let use_heuristic = !args.reverse && if args.no_heuristic { false } else if args.heuristic { true } else { args.filename.is_empty() };
             
-            // 默认启用状态文件和泛解析过滤
-            let status_file_path = Some(".rusub-state.json".into());
-            let wild_filter = "advanced".to_string();
+            // 默认启用状态文件(按目标集合命名)和泛解析过滤
+            let status_file_path = if args.no_state { None } else { Some(rusub::state::default_state_path(&domains)) };
+            let wild_filter = args.wildcard.clone();
+            if args.wildcard_report && wild_filter == "off" {
+                anyhow::bail!("--wildcard-report needs --wildcard basic or advanced");
+            }
             
             let mut opt = Options {
                 rate,
+                burst: args.burst as i64,
                 domains: domains.clone(),
                 domain_list: args.domain_list.clone(),
-                filename: args.filename.clone(),
+                filenames: args.filename.clone(),
                 resolvers,
-                silent: false,
+                silent: args.common.log_level == "silent",
+                quiet: args.quiet,
                 timeout: args.timeout,
                 retry: args.retry,
+                retry_transient: args.retry_transient,
+                recheck_failed: args.recheck_failed,
+                host_timeout: args.host_timeout,
                 concurrency: args.common.concurrency,
                 method: OptionMethod::Enum,
                 output: args.output.clone(),
                 output_type: args.output_type.clone(),
                 gzip: gzip_flag,
-                append: false,
+                append: args.append,
                 not_print: args.not_print,
                 wild_filter_mode: wild_filter,
                 predict: false,
@@ -95,33 +228,100 @@ async fn main() -> Result<()> {
                 predict_rounds: 0,
                 predict_topn: 0,
                 status_file: status_file_path,
+                clean_state_on_success: args.clean_state_on_success,
                 status_flush_interval: 30,
-                resolver_cooldown_secs: 60,
+                resolver_cooldown_secs: args.resolver_cooldown,
+                resolver_disable_min_total: args.resolver_disable_min,
+                resolver_fail_ratio: args.resolver_fail_ratio,
                 adaptive_rate: false,
-                adaptive_min_rate: 0,
+                adaptive_min_rate: rusub::ratelimit::DEFAULT_ADAPTIVE_MIN_RATE,
                 adaptive_max_rate: 0,
                 adaptive_error_threshold: 0.0,
                 adaptive_dec_factor: 1.0,
                 adaptive_inc_factor: 1.0,
-                resolver_stats_file: None,
-                resolver_stats_interval: 0,
-                progress_json_file: None,
-                progress_json_interval: 0,
+                resolver_stats_file: args.resolver_stats.clone(),
+                resolver_stats_interval: args.resolver_stats_interval,
+                progress_json_file: args.progress_json.clone(),
+                progress_json_interval: args.progress_json_interval,
+                progress_stream: args.progress_stream,
+                heartbeat: args.heartbeat.unwrap_or(0),
                 log_level: args.common.log_level.clone(),
                 pure_output: auto_pure,
                 only_alive: auto_only_alive,
+                emit_negative: args.emit_negative,
+                include_wildcard: args.include_wildcard,
                 heuristic: use_heuristic,
                 heuristic_max: args.heuristic_max,
+                seed: args.seed,
+                trusted_resolvers,
+                primary_resolvers,
+                resolver_weights,
+                authoritative: args.authoritative,
+                recv_buffer: args.recv_buffer,
+                source_ip: args.source_ip,
+                raw: args.raw,
+                max_queries: args.max_queries.unwrap_or(0),
+                #[cfg(all(unix, feature = "control-socket"))]
+                control_socket: args.control_socket.clone(),
+                #[cfg(feature = "result-socket")]
+                result_socket: args.result_socket.clone(),
+                gzip_index: args.gzip_index,
+                confirm_hits: args.confirm_hits,
+                consensus: args.consensus.unwrap_or(0) as usize,
+                merge_resolvers: args.merge_resolvers.unwrap_or(0) as usize,
+                split_by_rtype: args.split_by_rtype.clone(),
+                dedup_output,
+                sort_output: args.sort_output,
+                partial_flush: args.partial_flush,
+                tree: args.tree,
+                output_hash: args.output_hash,
+                max_answers: args.max_answers.unwrap_or(0) as usize,
+                no_result_marker: args.no_result_marker.clone(),
+                skip_empty: args.skip_empty,
+                a_only: args.a_only,
+                recursion_desired: !args.no_rd,
+                checking_disabled: args.cd,
+                authentic_data: args.ad,
+                dnssec: args.dnssec,
+                manifest: args.manifest.clone(),
+                preserve_case: args.preserve_case,
+                allow_invalid_labels: args.allow_invalid_labels,
+                use_hosts: args.use_hosts,
+                reverse: args.reverse,
+                probe_ports,
+                probe_rate: args.probe_rate,
+                timings: args.timings,
+                takeover_check: args.takeover_check || args.takeover_fingerprints.is_some(),
+                takeover_fingerprints: args.takeover_fingerprints.clone(),
+                #[cfg(feature = "asn")]
+                asn_allow: args.asn.clone(),
+                #[cfg(feature = "asn")]
+                asn_db: args.asn_db.clone(),
+                #[cfg(not(feature = "asn"))]
+                asn_allow: Vec::new(),
+                #[cfg(not(feature = "asn"))]
+                asn_db: None,
+                ecs: args.ecs.clone(),
+                respect_ttl: args.respect_ttl,
+                domain_chunk: args.domain_chunk.unwrap_or(0) as usize,
+                shard: args.shard,
+                output_flush_ms: args.output_flush_ms,
+                cname_is_alive: args.cname_is_alive,
+                wildcard_report: args.wildcard_report,
+                wildcard_threshold: args.wildcard_threshold,
+                wildcard_probes: args.wildcard_probes.map(|n| n as usize),
             };
-            opt.check();
+            opt.check()?;
 
-            if !opt.pure_output && (opt.log_level == "debug" || opt.log_level == "info") {
-                println!("Parsed Options: {:#?}", opt);
+            debug!("Parsed Options: {:#?}", opt);
+            let summary = scanner::run(opt).await?;
+            if summary.found == 0 {
+                info!("[done] no results found (exit code {}; use --always-success to exit 0)", EXIT_EMPTY);
+                return Ok(ExitCode::from(EXIT_EMPTY));
             }
-            scanner::run(opt).await?;
         }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
@@ -0,0 +1,102 @@
+//! Scan manifest: the exact parameters a result set was produced with.
+//! Written once at scan start when `--manifest <PATH>` is given.
+
+use crate::options::Options;
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+#[derive(Serialize, Debug)]
+pub struct Wordlist {
+    /// 字典文件路径 (多个按合并顺序)，或 ["embedded"]
+    pub sources: Vec<String>,
+    pub sha256: String,
+    pub words: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Manifest {
+    pub version: &'static str,
+    /// json/jsonl 结果行的 `_schema`
+    pub result_schema: u32,
+    pub started_at: u64,
+    pub domains: Vec<String>,
+    pub resolvers: Vec<String>,
+    pub trusted_resolvers: Vec<String>,
+    pub rate_pps: i64,
+    pub timeout_secs: u64,
+    pub retry: i32,
+    pub retry_transient: u32,
+    pub concurrency: usize,
+    pub wordlist: Wordlist,
+    pub heuristic: bool,
+    pub heuristic_max: usize,
+    pub predict: bool,
+    pub predict_rounds: u32,
+    pub predict_topn: usize,
+    pub a_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// `--shard` as `I/N`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<String>,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Manifest {
+    /// `words` is the size of the base wordlist before predict/heuristic expansion.
+    pub fn build(opt: &Options, words: usize) -> Result<Self> {
+        let wordlist = if opt.filenames.is_empty() {
+            Wordlist { sources: vec!["embedded".into()], sha256: sha256_hex(crate::dicts::EMBEDDED_SUBDOMAIN_TXT.as_bytes()), words }
+        } else {
+            // 多文件时哈希按顺序拼接后的内容
+            let mut data = Vec::new();
+            for p in opt.filenames.iter() { data.extend(std::fs::read(p)?); }
+            Wordlist { sources: opt.filenames.iter().map(|p| p.display().to_string()).collect(), sha256: sha256_hex(&data), words }
+        };
+        let started_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(Manifest {
+            version: env!("CARGO_PKG_VERSION"),
+            result_schema: crate::output::SCHEMA_VERSION,
+            started_at,
+            domains: opt.domains.clone(),
+            resolvers: opt.resolvers.clone(),
+            trusted_resolvers: opt.trusted_resolvers.clone(),
+            rate_pps: opt.rate,
+            timeout_secs: opt.timeout,
+            retry: opt.retry,
+            retry_transient: opt.retry_transient,
+            concurrency: opt.concurrency,
+            wordlist,
+            heuristic: opt.heuristic,
+            heuristic_max: opt.heuristic_max,
+            predict: opt.predict,
+            predict_rounds: opt.predict_rounds,
+            predict_topn: opt.predict_topn,
+            a_only: opt.a_only,
+            seed: opt.seed,
+            shard: opt.shard.map(|(i, n)| format!("{}/{}", i, n)),
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn sha256_known_vector() {
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
@@ -1,8 +1,8 @@
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tokio::time::{interval, Duration, Instant};
 use std::io::{stderr, Write};
-use crate::resolver_pool::ResolverPool;
+use crate::resolver_pool::{ResolverPool, ResolverStat};
 use serde::Serialize;
 
 #[derive(Default)]
@@ -18,17 +18,36 @@ pub struct Metrics {
     pub servfail: AtomicU64,
     pub refused: AtomicU64,
     pub timeouts: AtomicU64,
+    pub unconfirmed: AtomicU64, // --confirm-hits: bulk hits rejected by a trusted resolver
+    pub invalid_labels: AtomicU64, // candidates dropped by label validation before spawning
+    pub servfail_recovered: AtomicU64, // hosts that got a definitive answer after a SERVFAIL
+    pub asn_filtered: AtomicU64, // --asn: resolved hosts outside the ASN allowlist
+    pub ttl_cached: AtomicU64, // --respect-ttl: revisits skipped while the previous answer was within TTL
+    pub cname_only: AtomicU64, // --cname-is-alive: dangling CNAME answers kept as results
+    pub consensus_split: AtomicU64, // --consensus: hits where the asked resolvers did not all agree
+    pub consensus_rejected: AtomicU64, // --consensus: hits dropped because no answer set had a majority
+    pub bind_errors: AtomicU64, // local UDP bind failed after retries; never charged to a resolver
+    pub host_timeouts: AtomicU64, // --host-timeout: hosts abandoned once their total time budget ran out
+    pub ipv4: AtomicU64, // IPv4 addresses across emitted results
+    pub ipv6: AtomicU64, // IPv6 addresses across emitted results
 }
 
 impl Metrics {
     pub fn new() -> Arc<Self> { Arc::new(Self::default()) }
+
+    /// Add an emitted result's addresses to the `ipv4` / `ipv6` counters.
+    pub fn count_families(&self, r: &crate::output::ScanResult) {
+        let (v4, v6) = r.split_families();
+        self.ipv4.fetch_add(v4.len() as u64, Ordering::Relaxed);
+        self.ipv6.fetch_add(v6.len() as u64, Ordering::Relaxed);
+    }
 }
 
 fn colorize(enabled: bool, code: &str, s: String) -> String {
     if enabled { format!("\x1b[{}m{}\x1b[0m", code, s) } else { s }
 }
 
-pub fn spawn_reporter(m: Arc<Metrics>, interval_secs: u64, wide: bool, color: bool, legacy: bool, pool: Option<Arc<ResolverPool>>) {
+pub fn spawn_reporter(m: Arc<Metrics>, interval_secs: u64, wide: bool, color: bool, legacy: bool, pool: Option<Arc<ResolverPool>>, paused: Arc<AtomicBool>) {
     tokio::spawn(async move {
         use std::collections::VecDeque;
         let mut last_sent = 0u64;
@@ -76,6 +95,9 @@ pub fn spawn_reporter(m: Arc<Metrics>, interval_secs: u64, wide: bool, color: bo
                 let frac = if total > 0 { (disabled as f64)/(total as f64) } else { 0.0 };
                 format!(" res={}/{} dis={:.0}%", active, total, frac*100.0)
             } else { String::new() };
+            let res_info = if paused.load(Ordering::Relaxed) {
+                format!("{} {}", res_info, colorize(color, "33", "PAUSED".to_string()))
+            } else { res_info };
 
             if legacy {
                 // Legacy condensed layout (compact columns)
@@ -133,6 +155,8 @@ pub struct ProgressSnapshot {
     pub servfail: u64,
     pub refused: u64,
     pub timeouts: u64,
+    pub invalid_labels: u64,
+    pub servfail_recovered: u64,
     pub rate: f64,
     pub rate_avg: f64,
     pub eta_secs: u64,
@@ -144,66 +168,374 @@ pub struct ProgressSnapshot {
     pub resolvers_disabled_pct: Option<f64>,
     pub error_rate_recent: f64,
     pub error_rate_total: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolvers: Option<Vec<ResolverStat>>, // 每个解析器的 ok/fail/disabled
+    /// 扫描结束时的最后一个快照
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub done: bool,
+}
+
+/// Rolling state behind `ProgressSnapshot`: recent rate window and error deltas between samples.
+pub struct ProgressSampler {
+    interval_secs: u64,
+    start: Instant,
+    last_sent: u64,
+    last_err: u64, // recent window for (timeouts+servfail+refused)
+    win: std::collections::VecDeque<u64>,
+}
+
+impl ProgressSampler {
+    pub fn new(interval_secs: u64) -> Self {
+        Self { interval_secs: interval_secs.max(1), start: Instant::now(), last_sent: 0, last_err: 0, win: Default::default() }
+    }
+
+    /// One snapshot; call once per interval. `with_resolvers` attaches the per-resolver list.
+    pub fn sample(&mut self, m: &Metrics, pool: Option<&ResolverPool>, with_resolvers: bool) -> ProgressSnapshot {
+        let interval_secs = self.interval_secs;
+        let total = m.total.load(Ordering::Relaxed);
+        let sent = m.sent.load(Ordering::Relaxed);
+        let ok = m.ok.load(Ordering::Relaxed);
+        let filtered = m.filtered.load(Ordering::Relaxed);
+        let failed = m.failed.load(Ordering::Relaxed);
+        let skipped = m.skipped.load(Ordering::Relaxed);
+        let nxdomain = m.nxdomain.load(Ordering::Relaxed);
+        let servfail = m.servfail.load(Ordering::Relaxed);
+        let refused = m.refused.load(Ordering::Relaxed);
+        let timeouts = m.timeouts.load(Ordering::Relaxed);
+        let finished = ok + filtered + failed + skipped;
+        let err_sum = timeouts + servfail + refused;
+        let d_sent = sent.saturating_sub(self.last_sent);
+        let d_err = err_sum.saturating_sub(self.last_err);
+        self.last_sent = sent;
+        self.last_err = err_sum;
+
+        self.win.push_back(d_sent);
+        if self.win.len() > 5 { self.win.pop_front(); }
+        let sum_win: u64 = self.win.iter().sum();
+        let rate = d_sent as f64 / (interval_secs as f64);
+        let rate_avg = (sum_win as f64) / (self.win.len().max(1) as f64) / (interval_secs as f64);
+        let remain = if total > finished { total - finished } else { 0 } as f64;
+        let eta_secs = if rate > 0.0 { (remain / rate) as u64 } else { 0 };
+        let percent = if total > 0 { (finished as f64 / total as f64) * 100.0 } else { 0.0 };
+        let inflight = sent.saturating_sub(finished);
+        let elapsed = self.start.elapsed().as_secs();
+
+        let (resolvers_active, resolvers_total, resolvers_disabled_pct) = resolver_counts(pool);
+        let resolvers = if with_resolvers { pool.map(|p| p.snapshot()) } else { None };
+
+        let err_total = if sent > 0 { (err_sum as f64)/(sent as f64) } else { 0.0 };
+        ProgressSnapshot {
+            total, sent, ok, filtered, failed, skipped, nxdomain, servfail, refused, timeouts,
+            invalid_labels: m.invalid_labels.load(Ordering::Relaxed),
+            servfail_recovered: m.servfail_recovered.load(Ordering::Relaxed),
+            rate, rate_avg, eta_secs, percent, inflight, elapsed,
+            resolvers_active, resolvers_total, resolvers_disabled_pct,
+            error_rate_recent: if d_sent > 0 { (d_err as f64)/(d_sent as f64) } else { 0.0 },
+            error_rate_total: err_total,
+            resolvers,
+            done: false,
+        }
+    }
+}
+
+fn resolver_counts(pool: Option<&ResolverPool>) -> (Option<u64>, Option<u64>, Option<f64>) {
+    match pool {
+        Some(p) => {
+            let (a, t) = p.counts();
+            let d = t.saturating_sub(a);
+            let frac = if t > 0 { (d as f64)/(t as f64) } else { 0.0 };
+            (Some(a as u64), Some(t as u64), Some(frac*100.0))
+        }
+        None => (None, None, None),
+    }
+}
+
+/// End-of-scan snapshot: rates come from totals over the whole run, `done` is set.
+pub fn final_snapshot(m: &Metrics, pool: &ResolverPool, elapsed: u64, with_resolvers: bool) -> ProgressSnapshot {
+    let total = m.total.load(Ordering::Relaxed);
+    let sent = m.sent.load(Ordering::Relaxed);
+    let ok = m.ok.load(Ordering::Relaxed);
+    let filtered = m.filtered.load(Ordering::Relaxed);
+    let failed = m.failed.load(Ordering::Relaxed);
+    let skipped = m.skipped.load(Ordering::Relaxed);
+    let nxdomain = m.nxdomain.load(Ordering::Relaxed);
+    let servfail = m.servfail.load(Ordering::Relaxed);
+    let refused = m.refused.load(Ordering::Relaxed);
+    let timeouts = m.timeouts.load(Ordering::Relaxed);
+    let finished = ok + filtered + failed + skipped;
+    let percent = if total > 0 { (finished as f64 / total as f64) * 100.0 } else { 0.0 };
+    let inflight = sent.saturating_sub(finished);
+    let (resolvers_active, resolvers_total, resolvers_disabled_pct) = resolver_counts(Some(pool));
+    // Compute final snapshot rates from totals
+    let rate_from_total = if elapsed > 0 { (sent as f64) / (elapsed as f64) } else { 0.0 };
+    let remain = if total > finished { (total - finished) as f64 } else { 0.0 };
+    let eta_calc = if rate_from_total > 0.0 { (remain / rate_from_total) as u64 } else { 0 };
+    let err_sum = timeouts + servfail + refused;
+    let err_total = if sent > 0 { (err_sum as f64)/(sent as f64) } else { 0.0 };
+    ProgressSnapshot {
+        total,
+        sent,
+        ok,
+        filtered,
+        failed,
+        skipped,
+        nxdomain,
+        servfail,
+        refused,
+        timeouts,
+        invalid_labels: m.invalid_labels.load(Ordering::Relaxed),
+        servfail_recovered: m.servfail_recovered.load(Ordering::Relaxed),
+        rate: rate_from_total,
+        rate_avg: rate_from_total,
+        eta_secs: eta_calc,
+        percent,
+        inflight,
+        elapsed,
+        resolvers_active,
+        resolvers_total,
+        resolvers_disabled_pct,
+        error_rate_recent: 0.0,
+        error_rate_total: err_total,
+        resolvers: if with_resolvers { Some(pool.snapshot()) } else { None },
+        done: true,
+    }
 }
 
 pub fn spawn_json_reporter(m: Arc<Metrics>, interval_secs: u64, pool: Option<Arc<ResolverPool>>, path: std::path::PathBuf) {
     tokio::spawn(async move {
-        use std::collections::VecDeque;
-    let mut last_sent = 0u64;
-    let mut last_err = 0u64; // recent window for (timeouts+servfail+refused)
-        let start = Instant::now();
-        let mut win: VecDeque<u64> = VecDeque::new();
+        let mut sampler = ProgressSampler::new(interval_secs);
         let mut tick = interval(Duration::from_secs(interval_secs.max(1)));
         loop {
             tick.tick().await;
-            let total = m.total.load(Ordering::Relaxed);
+            let snap = sampler.sample(&m, pool.as_deref(), true);
+            if let Ok(data) = serde_json::to_vec_pretty(&snap) {
+                let _ = tokio::fs::write(&path, data).await;
+            }
+        }
+    });
+}
+
+/// Where `--progress-stream` writes its JSON lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressTarget {
+    Stderr,
+    Stdout,
+    /// an inherited file descriptor (Unix, opened via /dev/fd/N)
+    Fd(u32),
+}
+
+impl std::str::FromStr for ProgressTarget {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "stderr" | "2" => Ok(Self::Stderr),
+            "stdout" | "1" => Ok(Self::Stdout),
+            v => v.parse::<u32>().map(Self::Fd).map_err(|_| format!("expected stderr, stdout or a file descriptor number: {}", s)),
+        }
+    }
+}
+
+impl ProgressTarget {
+    pub fn open(&self) -> std::io::Result<Box<dyn Write + Send>> {
+        match self {
+            Self::Stderr => Ok(Box::new(stderr())),
+            Self::Stdout => Ok(Box::new(std::io::stdout())),
+            Self::Fd(fd) => {
+                if cfg!(unix) {
+                    Ok(Box::new(std::fs::OpenOptions::new().write(true).open(format!("/dev/fd/{}", fd))?))
+                } else {
+                    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "--progress-stream <FD> needs a Unix platform"))
+                }
+            }
+        }
+    }
+}
+
+/// One compact `ProgressSnapshot` per line, for wrappers that drive their own UI.
+pub fn write_progress_event(out: &mut dyn Write, snap: &ProgressSnapshot) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(snap).map_err(std::io::Error::other)?;
+    line.push(b'\n');
+    out.write_all(&line)?;
+    out.flush()
+}
+
+/// `--progress-stream`: emit a snapshot line every interval until the task is aborted.
+pub fn spawn_progress_stream(m: Arc<Metrics>, interval_secs: u64, pool: Option<Arc<ResolverPool>>, mut out: Box<dyn Write + Send>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut sampler = ProgressSampler::new(interval_secs);
+        let mut tick = interval(Duration::from_secs(interval_secs.max(1)));
+        loop {
+            tick.tick().await;
+            let snap = sampler.sample(&m, pool.as_deref(), false);
+            if write_progress_event(&mut *out, &snap).is_err() {
+                // reader went away; stop streaming rather than spin on a broken pipe
+                return;
+            }
+        }
+    })
+}
+
+/// `--heartbeat` line: a liveness signal for supervisors, far smaller than a progress snapshot.
+#[derive(Serialize, Debug)]
+pub struct Heartbeat {
+    /// Unix seconds when the line was written
+    pub heartbeat: u64,
+    pub elapsed: u64,
+    pub sent: u64,
+    /// hosts finished (resolved, filtered, failed or skipped) out of `total`
+    pub finished: u64,
+    pub total: u64,
+    pub found: u64,
+}
+
+impl Heartbeat {
+    pub fn sample(m: &Metrics, elapsed: u64) -> Self {
+        let ok = m.ok.load(Ordering::Relaxed);
+        Heartbeat {
+            heartbeat: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            elapsed,
+            sent: m.sent.load(Ordering::Relaxed),
+            finished: ok + m.filtered.load(Ordering::Relaxed) + m.failed.load(Ordering::Relaxed) + m.skipped.load(Ordering::Relaxed),
+            total: m.total.load(Ordering::Relaxed),
+            found: ok,
+        }
+    }
+}
+
+/// `--heartbeat`: one compact JSON line on stderr every `interval_secs`, independent of
+/// `--pure-output` and the progress line, until the task is aborted.
+pub fn spawn_heartbeat(m: Arc<Metrics>, interval_secs: u64) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut tick = interval(Duration::from_secs(interval_secs.max(1)));
+        tick.tick().await;
+        loop {
+            tick.tick().await;
+            let hb = Heartbeat::sample(&m, started.elapsed().as_secs());
+            let Ok(mut line) = serde_json::to_vec(&hb) else { continue };
+            line.push(b'\n');
+            let mut err = stderr().lock();
+            if err.write_all(&line).and_then(|_| err.flush()).is_err() { return; }
+        }
+    })
+}
+
+/// Detects a scan that persistently sends well below the configured rate while
+/// resolvers are healthy, i.e. the bottleneck is the network/host, not `--band`.
+#[derive(Default)]
+pub struct RateGapDetector {
+    low_windows: u32,
+    warned: bool,
+}
+
+impl RateGapDetector {
+    /// observed 低于配置值的该比例视为 "低速窗口"
+    pub const RATIO: f64 = 0.5;
+    /// 连续多少个低速窗口才告警
+    pub const WINDOWS: u32 = 3;
+    /// 错误率高于该值时不告警 (此时慢是解析器问题，不是网络瓶颈)
+    pub const MAX_ERROR_RATE: f64 = 0.05;
+
+    /// Feed one window; returns true exactly once, when the warning should be shown.
+    /// `backlog` is false near the end of a scan, where low throughput is expected.
+    pub fn observe(&mut self, observed_pps: f64, configured_pps: i64, error_rate: f64, backlog: bool) -> bool {
+        if self.warned || configured_pps <= 0 { return false; }
+        let low = backlog
+            && error_rate <= Self::MAX_ERROR_RATE
+            && observed_pps < configured_pps as f64 * Self::RATIO;
+        self.low_windows = if low { self.low_windows + 1 } else { 0 };
+        if self.low_windows >= Self::WINDOWS {
+            self.warned = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// Periodically compare sent pps with the limiter's configured rate and warn once on a large gap.
+pub fn spawn_rate_gap_watch(m: Arc<Metrics>, rl: crate::ratelimit::RateLimiter, window_secs: u64, concurrency: usize) {
+    tokio::spawn(async move {
+        let window = window_secs.max(1);
+        let mut tick = interval(Duration::from_secs(window));
+        tick.tick().await;
+        let mut det = RateGapDetector::default();
+        let mut last_sent = m.sent.load(Ordering::Relaxed);
+        let mut last_err = 0u64;
+        loop {
+            tick.tick().await;
             let sent = m.sent.load(Ordering::Relaxed);
-            let ok = m.ok.load(Ordering::Relaxed);
-            let filtered = m.filtered.load(Ordering::Relaxed);
-            let failed = m.failed.load(Ordering::Relaxed);
-            let skipped = m.skipped.load(Ordering::Relaxed);
-            let nxdomain = m.nxdomain.load(Ordering::Relaxed);
-            let servfail = m.servfail.load(Ordering::Relaxed);
-            let refused = m.refused.load(Ordering::Relaxed);
-            let timeouts = m.timeouts.load(Ordering::Relaxed);
-            let finished = ok + filtered + failed + skipped;
-            let err_sum = m.timeouts.load(Ordering::Relaxed)
-                + m.servfail.load(Ordering::Relaxed)
-                + m.refused.load(Ordering::Relaxed);
+            let err = m.timeouts.load(Ordering::Relaxed) + m.servfail.load(Ordering::Relaxed) + m.refused.load(Ordering::Relaxed);
             let d_sent = sent.saturating_sub(last_sent);
-            let d_err = err_sum.saturating_sub(last_err);
+            let d_err = err.saturating_sub(last_err);
             last_sent = sent;
-            last_err = err_sum;
-
-            win.push_back(d_sent);
-            if win.len() > 5 { win.pop_front(); }
-            let sum_win: u64 = win.iter().sum();
-            let rate = d_sent as f64 / (interval_secs.max(1) as f64);
-            let rate_avg = (sum_win as f64) / (win.len().max(1) as f64) / (interval_secs.max(1) as f64);
-            let remain = if total > finished { total - finished } else { 0 } as f64;
-            let eta_secs = if rate > 0.0 { (remain / rate) as u64 } else { 0 };
-            let percent = if total > 0 { (finished as f64 / total as f64) * 100.0 } else { 0.0 };
-            let inflight = sent.saturating_sub(finished);
-            let elapsed = start.elapsed().as_secs();
-
-            let (resolvers_active, resolvers_total, resolvers_disabled_pct) = if let Some(ref p) = pool {
-                let (a, t) = p.counts();
-                let d = t.saturating_sub(a);
-                let frac = if t > 0 { (d as f64)/(t as f64) } else { 0.0 };
-                (Some(a as u64), Some(t as u64), Some(frac*100.0))
-            } else { (None, None, None) };
-
-            let err_total = if sent > 0 { (err_sum as f64)/(sent as f64) } else { 0.0 };
-            let snap = ProgressSnapshot {
-                total, sent, ok, filtered, failed, skipped, nxdomain, servfail, refused, timeouts,
-                rate, rate_avg, eta_secs, percent, inflight, elapsed,
-                resolvers_active, resolvers_total, resolvers_disabled_pct,
-                error_rate_recent: if d_sent > 0 { (d_err as f64)/(d_sent as f64) } else { 0.0 },
-                error_rate_total: err_total,
-            };
-            if let Ok(data) = serde_json::to_vec_pretty(&snap) {
-                let _ = tokio::fs::write(&path, data).await;
+            last_err = err;
+            let finished = m.ok.load(Ordering::Relaxed) + m.filtered.load(Ordering::Relaxed)
+                + m.failed.load(Ordering::Relaxed) + m.skipped.load(Ordering::Relaxed);
+            let configured = rl.get_rate();
+            // 剩余任务至少够再跑一个完整窗口，才算有积压
+            let backlog = m.total.load(Ordering::Relaxed).saturating_sub(finished) >= configured.max(0) as u64 * window;
+            let observed = d_sent as f64 / window as f64;
+            let err_rate = if d_sent > 0 { d_err as f64 / d_sent as f64 } else { 0.0 };
+            if det.observe(observed, configured, err_rate, backlog) {
+                tracing::warn!(
+                    "[rate] sending ~{:.0} pkt/s vs configured {} pkt/s with low error rate: throughput is network/host-bound (or limited by -c {}), not by --band",
+                    observed, configured, concurrency
+                );
+                break;
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn rate_gap_warns_once_after_consecutive_low_windows() {
+        let mut d = RateGapDetector::default();
+        assert!(!d.observe(100.0, 1000, 0.0, true));
+        assert!(!d.observe(100.0, 1000, 0.0, true));
+        assert!(!d.observe(900.0, 1000, 0.0, true), "a healthy window resets the streak");
+        assert!(!d.observe(100.0, 1000, 0.2, true), "high error rate is a resolver problem, not a gap");
+        assert!(!d.observe(100.0, 1000, 0.0, false), "no backlog at the tail of a scan");
+        for _ in 0..RateGapDetector::WINDOWS - 1 { assert!(!d.observe(100.0, 1000, 0.0, true)); }
+        assert!(d.observe(100.0, 1000, 0.0, true));
+        assert!(!d.observe(100.0, 1000, 0.0, true), "warning fires only once");
+    }
+
+    #[test]
+    fn progress_events_are_single_json_lines() {
+        let m = Metrics::new();
+        m.total.store(10, Ordering::Relaxed);
+        m.sent.store(4, Ordering::Relaxed);
+        m.ok.store(2, Ordering::Relaxed);
+        let mut s = ProgressSampler::new(1);
+        let mut out = Vec::new();
+        write_progress_event(&mut out, &s.sample(&m, None, false)).unwrap();
+        let pool = ResolverPool::new(vec!["1.1.1.1".to_string()]);
+        write_progress_event(&mut out, &final_snapshot(&m, &pool, 2, false)).unwrap();
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["sent"], 4);
+        assert_eq!(lines[0]["percent"], 20.0);
+        assert!(lines[0].get("done").is_none() && lines[0].get("resolvers").is_none());
+        assert_eq!(lines[1]["done"], true);
+        assert_eq!(lines[1]["resolvers_total"], 1);
+        assert_eq!("stderr".parse::<ProgressTarget>(), Ok(ProgressTarget::Stderr));
+        assert_eq!("3".parse::<ProgressTarget>(), Ok(ProgressTarget::Fd(3)));
+        assert!("tty".parse::<ProgressTarget>().is_err());
+    }
+
+    #[test]
+    fn heartbeat_counts_finished_hosts() {
+        let m = Metrics::new();
+        m.total.store(10, Ordering::Relaxed);
+        m.sent.store(7, Ordering::Relaxed);
+        m.ok.store(2, Ordering::Relaxed);
+        m.failed.store(3, Ordering::Relaxed);
+        let v = serde_json::to_value(Heartbeat::sample(&m, 12)).unwrap();
+        assert!(v["heartbeat"].as_u64().unwrap() > 0);
+        assert_eq!((v["elapsed"].as_u64(), v["sent"].as_u64(), v["finished"].as_u64(), v["total"].as_u64(), v["found"].as_u64()), (Some(12), Some(7), Some(5), Some(10), Some(2)));
+        assert_eq!(v.as_object().unwrap().len(), 6);
+    }
+}
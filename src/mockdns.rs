@@ -0,0 +1,114 @@
+//! Scripted [`DnsClient`] for tests and embedding: no sockets, answers come from a script.
+//!
+//! ```
+//! use rusub::dns::{DnsClient, QueryOptions, RecordType};
+//! use rusub::mockdns::{MockDnsClient, MockReply};
+//! use rusub::rng::{shared_rng, STREAM_QUERY_ID};
+//!
+//! let dns = MockDnsClient::default()
+//!     .answer("www.example.com", &["192.0.2.1"])
+//!     .script("flaky.example.com", vec![MockReply::Timeout, MockReply::a(&["192.0.2.9"])])
+//!     .wildcard("example.com", &["198.51.100.7"]);
+//! let qo = QueryOptions::new(shared_rng(Some(1), STREAM_QUERY_ID));
+//! let first = dns.query("flaky.example.com", "192.0.2.53", RecordType::A, 1000, &qo).unwrap();
+//! assert_eq!(first.rcode, "TIMEOUT");
+//! let second = dns.query("flaky.example.com", "192.0.2.53", RecordType::A, 1000, &qo).unwrap();
+//! assert_eq!(second.records[0].data, "192.0.2.9");
+//! assert_eq!(dns.queries("flaky.example.com"), 2);
+//! ```
+
+use crate::dns::{DnsAnswer, DnsClient, QueryOptions, RawRecord, RecordType};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// One scripted reply.
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    /// NOERROR with these records
+    Records(Vec<RawRecord>),
+    /// an rcode without records, spelled as the scanner sees it (`NXDomain`, `ServFail`, `Refused`, `NoError`)
+    Rcode(String),
+    /// the resolver never answers
+    Timeout,
+}
+
+impl MockReply {
+    /// NOERROR with one A (or AAAA, for addresses containing `:`) record per address.
+    pub fn a(ips: &[&str]) -> Self {
+        MockReply::Records(ips.iter().map(|ip| RawRecord {
+            rtype: if ip.contains(':') { "AAAA".into() } else { "A".into() },
+            data: ip.to_string(),
+            ttl: 300,
+        }).collect())
+    }
+
+    fn into_answer(self) -> DnsAnswer {
+        match self {
+            MockReply::Records(records) => DnsAnswer { records, rcode: "NoError".into(), ..Default::default() },
+            MockReply::Rcode(rcode) => DnsAnswer { rcode, ..Default::default() },
+            MockReply::Timeout => DnsAnswer { rcode: "TIMEOUT".into(), ..Default::default() },
+        }
+    }
+}
+
+/// Answers by lower-cased host name. A scripted host plays its replies in order and then keeps
+/// repeating the last one; names under a [`wildcard`](Self::wildcard) zone get its addresses;
+/// anything else is NXDOMAIN. Every query is counted per host.
+#[derive(Default)]
+pub struct MockDnsClient {
+    scripts: Mutex<HashMap<String, Vec<MockReply>>>,
+    wildcards: Vec<(String, Vec<String>)>,
+    seen: Mutex<HashMap<String, usize>>,
+}
+
+impl MockDnsClient {
+    pub fn answer(self, host: &str, ips: &[&str]) -> Self {
+        self.script(host, vec![MockReply::a(ips)])
+    }
+
+    pub fn script(self, host: &str, replies: Vec<MockReply>) -> Self {
+        self.scripts.lock().unwrap().insert(key(host), replies);
+        self
+    }
+
+    /// Every name below `zone` without a script of its own resolves to `ips`.
+    pub fn wildcard(mut self, zone: &str, ips: &[&str]) -> Self {
+        self.wildcards.push((key(zone), ips.iter().map(|s| s.to_string()).collect()));
+        self
+    }
+
+    /// Queries sent for `host` so far.
+    pub fn queries(&self, host: &str) -> usize {
+        self.seen.lock().unwrap().get(&key(host)).copied().unwrap_or(0)
+    }
+
+    fn reply(&self, host: &str) -> MockReply {
+        if let Some(replies) = self.scripts.lock().unwrap().get_mut(host) {
+            return if replies.len() > 1 { replies.remove(0) } else { replies[0].clone() };
+        }
+        for (zone, ips) in self.wildcards.iter() {
+            if host.strip_suffix(zone.as_str()).is_some_and(|p| p.ends_with('.')) {
+                return MockReply::a(&ips.iter().map(String::as_str).collect::<Vec<_>>());
+            }
+        }
+        MockReply::Rcode("NXDomain".into())
+    }
+}
+
+impl DnsClient for MockDnsClient {
+    fn query(&self, host: &str, _resolver: &str, rtype: RecordType, _timeout_ms: u64, _qo: &QueryOptions) -> Result<DnsAnswer> {
+        let host = key(host);
+        *self.seen.lock().unwrap().entry(host.clone()).or_insert(0) += 1;
+        let mut ans = self.reply(&host).into_answer();
+        // scripts hold address records; a typed query (PTR, ...) only sees records of its type
+        if rtype != RecordType::A {
+            ans.records.retain(|r| r.rtype == rtype.to_string());
+        }
+        Ok(ans)
+    }
+}
+
+fn key(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
@@ -1,5 +1,5 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub enum OptionMethod {
@@ -11,13 +11,22 @@ pub enum OptionMethod {
 #[derive(Debug, Clone)]
 pub struct Options {
     pub rate: i64,
+    /// 令牌桶容量：空闲后一次最多放出的查询数 (0 = 100ms 的量，最平滑)
+    pub burst: i64,
     pub domains: Vec<String>,
     pub domain_list: Option<PathBuf>,
-    pub filename: Option<PathBuf>,
+    pub filenames: Vec<PathBuf>,
     pub resolvers: Vec<String>,
     pub silent: bool,
+    /// 不显示实时进度行，日志 (警告、解析器禁用、结束汇总) 照常输出
+    pub quiet: bool,
     pub timeout: u64,
     pub retry: i32,
+    pub retry_transient: u32,
+    /// 主扫描结束后以较低速率把状态库中 Failed 的主机再查一次
+    pub recheck_failed: bool,
+    /// 单个主机 (含全部重试) 的总耗时上限，秒；0 = 不限制
+    pub host_timeout: u64,
     pub concurrency: usize,
     pub method: OptionMethod,
     pub output: Option<PathBuf>,
@@ -34,9 +43,16 @@ pub struct Options {
     pub predict_rounds: u32,
     pub predict_topn: usize,
     pub status_file: Option<PathBuf>,
+    /// 扫描正常结束后删除状态文件
+    pub clean_state_on_success: bool,
     pub status_flush_interval: u64,
     pub resolver_cooldown_secs: u64,
+    /// 健康检查：累计该数量的成功/失败后才按失败率判定禁用
+    pub resolver_disable_min_total: u64,
+    /// 健康检查：失败率超过该值即禁用解析器
+    pub resolver_fail_ratio: f64,
     pub adaptive_rate: bool,
+    /// 自适应速率下限 pps (<= 0 取默认 50)，错误率再高也不会降到其下
     pub adaptive_min_rate: i64,
     pub adaptive_max_rate: i64,
     pub adaptive_error_threshold: f64,
@@ -48,24 +64,293 @@ pub struct Options {
     pub append: bool,
     pub progress_json_file: Option<PathBuf>,
     pub progress_json_interval: u64,
+    /// 进度事件流：每 progress_interval 输出一行 JSON 快照
+    pub progress_stream: Option<crate::metrics::ProgressTarget>,
+    /// 每 N 秒向 stderr 写一行心跳 JSON，不受纯净模式影响 (0 = 关闭)
+    pub heartbeat: u64,
     pub log_level: String,
     pub pure_output: bool,
     pub only_alive: bool,
+    /// 否定应答 (NXDOMAIN 等) 也输出，带 rcode
+    pub emit_negative: bool,
+    pub include_wildcard: bool,
     pub heuristic: bool,
     pub heuristic_max: usize,
+    pub seed: Option<u64>,
+    pub trusted_resolvers: Vec<String>,
+    /// 首选解析器层 (--resolver-primary)：全部禁用前不使用 resolvers 中的其他解析器
+    pub primary_resolvers: Vec<String>,
+    /// 解析器选择权重 (来自 --resolvers-file)，未列出的为 1.0
+    pub resolver_weights: Vec<(String, f64)>,
+    /// 直接向各根域的权威 NS 发送非递归查询
+    pub authoritative: bool,
+    /// UDP 接收缓冲区字节数
+    pub recv_buffer: usize,
+    /// 查询 socket 绑定的本地源地址 (None = 0.0.0.0)
+    pub source_ip: Option<std::net::IpAddr>,
+    /// 原始套接字发包 (Linux，CAP_NET_RAW)
+    pub raw: bool,
+    /// 总查询数上限 (0 = 不限)
+    pub max_queries: u64,
+    /// 运行时控制 Unix socket (需 control-socket feature)
+    #[cfg(all(unix, feature = "control-socket"))]
+    pub control_socket: Option<PathBuf>,
+    /// 结果流 socket，逐行 JSON 推送给已连接的客户端 (需 result-socket feature)
+    #[cfg(feature = "result-socket")]
+    pub result_socket: Option<crate::resultsock::ListenAddr>,
+    /// 分块 gzip 输出并写 .idx 索引
+    pub gzip_index: bool,
+    pub confirm_hits: bool,
+    /// 每个命中向 K 个不同解析器查询，多数一致的 IP 集合才输出 (0/1 = 关闭)
+    pub consensus: usize,
+    /// 每个命中向 K 个不同解析器查询并合并记录 (0/1 = 关闭)
+    pub merge_resolvers: usize,
+    pub split_by_rtype: Option<PathBuf>,
+    pub dedup_output: bool,
+    pub sort_output: bool,
+    /// 缓冲输出 (--sort-output / table) 的中途快照间隔秒数，写入 <输出>.partial.jsonl (0 = 关闭)
+    pub partial_flush: u64,
+    /// 结果附带 parent / depth (发现树位置)
+    pub tree: bool,
+    /// 结束时把输出文件的 SHA-256 写入 <输出>.sha256
+    pub output_hash: bool,
+    /// 每个主机最多输出的 A/AAAA 地址数 (0 = 不限制)
+    pub max_answers: usize,
+    /// txt 输出中无应答结果的标记 (None = "[no-result]")
+    pub no_result_marker: Option<String>,
+    /// txt 输出省略无应答结果的行
+    pub skip_empty: bool,
+    pub a_only: bool,
+    /// 查询头部标志位：RD (默认 1)、CD、AD
+    pub recursion_desired: bool,
+    pub checking_disabled: bool,
+    pub authentic_data: bool,
+    /// EDNS0 DO 位，结果记录 AD 标志与 RRSIG
+    pub dnssec: bool,
+    pub manifest: Option<PathBuf>,
+    pub preserve_case: bool,
+    pub allow_invalid_labels: bool,
+    pub use_hosts: bool,
+    pub reverse: bool,
+    pub probe_ports: Vec<u16>,
+    pub probe_rate: i64,
+    pub timings: bool,
+    /// CNAME 指向易被接管的第三方服务时标记结果
+    pub takeover_check: bool,
+    /// 替换内置接管指纹列表
+    pub takeover_fingerprints: Option<PathBuf>,
+    /// ASN 白名单 (需 asn feature)：只保留应答 IP 属于其中之一的主机
+    pub asn_allow: Vec<u32>,
+    /// IP -> ASN 表 (iptoasn.com TSV，可 .gz)
+    pub asn_db: Option<PathBuf>,
+    /// EDNS Client Subnet：第一个附加到所有查询；多个时对存活主机逐个子网查询并记录各自应答
+    pub ecs: Vec<crate::dns::ClientSubnet>,
+    /// 本次运行内已解析主机在记录 TTL 内不再重复查询
+    pub respect_ttl: bool,
+    /// 根域分批处理，每批 N 个 (0 = 不分批)
+    pub domain_chunk: usize,
+    /// 只扫描候选词分片 (I, N)：下标 % N == I 的词
+    pub shard: Option<(usize, usize)>,
+    /// 文件输出刷盘间隔毫秒 (0 = 每行刷盘)
+    pub output_flush_ms: u64,
+    /// CNAME 目标 NXDOMAIN/SERVFAIL 的悬空 CNAME 也作为存活结果输出
+    pub cname_is_alive: bool,
+    /// 打印每个根域的泛解析集合，命中结果标记 wildcard 输出而不过滤
+    pub wildcard_report: bool,
+    /// advanced 模式：IP 出现在至少该比例的探测中才算泛解析
+    pub wildcard_threshold: f64,
+    /// 每个根域的泛解析探测次数 (None = basic 3 / advanced 6)
+    pub wildcard_probes: Option<usize>,
+}
+
+/// Library-friendly defaults: same scan parameters as the CLI (3m band, 6s timeout,
+/// 3 retries, 500 concurrency, advanced wildcard filter, heuristic expansion), but
+/// nothing printed or persisted — no stdout, progress line or state file.
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rate: band2rate("3m").unwrap_or(4687),
+            burst: 0,
+            domains: Vec::new(),
+            domain_list: None,
+            filenames: Vec::new(),
+            resolvers: Vec::new(),
+            silent: false,
+            quiet: false,
+            timeout: 6,
+            retry: 3,
+            retry_transient: 1,
+            recheck_failed: false,
+            host_timeout: 0,
+            concurrency: 500,
+            method: OptionMethod::Enum,
+            output: None,
+            output_type: "jsonl".into(),
+            not_print: true,
+            wild_filter_mode: "advanced".into(),
+            predict: false,
+            progress: false,
+            progress_interval: 1,
+            detail_records: false,
+            progress_wide: false,
+            progress_color: false,
+            progress_legacy: false,
+            predict_rounds: 0,
+            predict_topn: 0,
+            status_file: None,
+            clean_state_on_success: false,
+            status_flush_interval: 30,
+            resolver_cooldown_secs: crate::resolver_pool::DEFAULT_COOLDOWN_SECS,
+            resolver_disable_min_total: crate::resolver_pool::DisablePolicy::DEFAULT_MIN_TOTAL,
+            resolver_fail_ratio: crate::resolver_pool::DisablePolicy::DEFAULT_FAIL_RATIO,
+            adaptive_rate: false,
+            adaptive_min_rate: crate::ratelimit::DEFAULT_ADAPTIVE_MIN_RATE,
+            adaptive_max_rate: 0,
+            adaptive_error_threshold: 0.0,
+            adaptive_dec_factor: 1.0,
+            adaptive_inc_factor: 1.0,
+            resolver_stats_file: None,
+            resolver_stats_interval: 0,
+            gzip: false,
+            append: false,
+            progress_json_file: None,
+            progress_json_interval: 5,
+            progress_stream: None,
+            heartbeat: 0,
+            log_level: "info".into(),
+            pure_output: true,
+            only_alive: true,
+            emit_negative: false,
+            include_wildcard: false,
+            heuristic: true,
+            heuristic_max: 512,
+            seed: None,
+            trusted_resolvers: Vec::new(),
+            primary_resolvers: Vec::new(),
+            resolver_weights: Vec::new(),
+            authoritative: false,
+            recv_buffer: crate::dns::DEFAULT_RECV_BUFFER,
+            source_ip: None,
+            raw: false,
+            max_queries: 0,
+            #[cfg(all(unix, feature = "control-socket"))]
+            control_socket: None,
+            #[cfg(feature = "result-socket")]
+            result_socket: None,
+            gzip_index: false,
+            confirm_hits: false,
+            consensus: 0,
+            merge_resolvers: 0,
+            split_by_rtype: None,
+            dedup_output: true,
+            sort_output: false,
+            partial_flush: 0,
+            tree: false,
+            output_hash: false,
+            max_answers: 0,
+            no_result_marker: None,
+            skip_empty: false,
+            a_only: false,
+            recursion_desired: true,
+            checking_disabled: false,
+            authentic_data: false,
+            dnssec: false,
+            manifest: None,
+            preserve_case: false,
+            allow_invalid_labels: false,
+            use_hosts: false,
+            reverse: false,
+            probe_ports: Vec::new(),
+            probe_rate: 500,
+            timings: false,
+            takeover_check: false,
+            takeover_fingerprints: None,
+            asn_allow: Vec::new(),
+            asn_db: None,
+            ecs: Vec::new(),
+            respect_ttl: false,
+            domain_chunk: 0,
+            shard: None,
+            output_flush_ms: crate::output::DEFAULT_FLUSH_MS,
+            cname_is_alive: false,
+            wildcard_report: false,
+            wildcard_threshold: crate::wildcard::DEFAULT_THRESHOLD,
+            wildcard_probes: None,
+        }
+    }
 }
 
 impl Options {
-    pub fn check(&mut self) {
+    /// Results carry the answering resolver: `--timings`, or sqlite output, whose `resolver`
+    /// column is filled on every run.
+    pub fn record_resolver(&self) -> bool {
+        self.timings || self.output_type == "sqlite"
+    }
+
+    /// Reject settings that would only fail later (zero timeouts, a semaphore with no
+    /// permits, a rate of 0), then derive the implied flags.
+    pub fn check(&mut self) -> Result<()> {
+        if self.timeout == 0 {
+            anyhow::bail!("--timeout must be at least 1 second (0 would time out every query immediately)");
+        }
+        if self.concurrency == 0 {
+            anyhow::bail!("--concurrency must be at least 1 (0 would never dispatch a query)");
+        }
+        if self.rate <= 0 {
+            anyhow::bail!("rate is {} pkt/s; --band must work out to at least 1 packet per second (e.g. 3m, 2MB/s, 5000pps)", self.rate);
+        }
+        if self.retry < -1 {
+            anyhow::bail!("--retry {} is invalid: use 0 or more, or -1 for unlimited retries", self.retry);
+        }
         if self.silent {
-            // placeholder for logger level - silent implies minimal stdout
+            // silent: only result lines, no progress line either
+            self.progress = false;
         }
         if self.pure_output {
             // pure mode implies no progress and minimal stdout
             self.progress = false;
         }
+        if self.quiet {
+            // quiet: drop the live progress line but keep info logs and the final summary
+            self.progress = false;
+        }
+        if self.wildcard_report {
+            // report mode audits the filter instead of applying it
+            self.include_wildcard = true;
+        }
         // no extra checks for only_alive
+        Ok(())
+    }
+}
+
+/// clap value parser for `--band`: the expression must convert to a positive packet rate,
+/// so a typo is reported as a usage error before any resolver probing starts.
+pub fn parse_band(s: &str) -> std::result::Result<String, String> {
+    match band2rate(s) {
+        Ok(pps) if pps > 0 => Ok(s.to_string()),
+        Ok(_) => Err(format!("{:?} works out to 0 packets/s; use a larger value (e.g. 3m, 2MB/s, 5000pps)", s)),
+        Err(e) => Err(format!("{} (expected K/M/G bits, Mbps, MBps or MB/s bytes, or pps, e.g. 3m, 100M, 2MB/s, 5000pps)", e)),
+    }
+}
+
+/// clap value parser for `--shard I/N`: zero-based shard index below the shard count.
+pub fn parse_shard(s: &str) -> std::result::Result<(usize, usize), String> {
+    let (i, n) = s.split_once('/').ok_or_else(|| format!("{:?}: expected I/N, e.g. 3/8", s))?;
+    let i: usize = i.trim().parse().map_err(|_| format!("{:?}: shard index is not a number", s))?;
+    let n: usize = n.trim().parse().map_err(|_| format!("{:?}: shard count is not a number", s))?;
+    if n == 0 || i >= n {
+        return Err(format!("{:?}: need 0 <= I < N (shards are numbered from 0)", s));
+    }
+    Ok((i, n))
+}
+
+/// clap value parser for `--no-result-marker`: the marker is a tab-separated field of its own,
+/// so it must be non-empty and free of tabs and line breaks.
+pub fn parse_no_result_marker(s: &str) -> std::result::Result<String, String> {
+    if s.is_empty() || s.contains(['\t', '\n', '\r']) {
+        return Err("marker must be non-empty and contain no tab or line break".into());
     }
+    Ok(s.to_string())
 }
 
 /// Convert a bandwidth expression (e.g. 1G, 500M, 2.5M, 750K) into approximate packets per second.
@@ -78,6 +363,8 @@ pub fn band2rate(band: &str) -> Result<i64> {
     if band.is_empty() { anyhow::bail!("empty band string") }
     let s = band.trim();
     if s.is_empty() { anyhow::bail!("empty band string") }
+    // "X/s" 写法等价于 "Xps"：MB/s -> MBps, Mb/s -> Mbps
+    let s = match s.strip_suffix("/s") { Some(head) => format!("{}ps", head), None => s.to_string() };
     let lower = s.to_ascii_lowercase();
 
     // Helper: parse f64 numeric prefix
@@ -90,6 +377,19 @@ pub fn band2rate(band: &str) -> Result<i64> {
     // DNS packet size (approx) in bits
     const DNS_PACKET_BITS: f64 = 80.0 * 8.0;
 
+    // Case 0: kBps/MBps/GBps/Bps (bytes per second). Case-sensitive: a capital 'B'
+    // means bytes, so "MBps" is megabytes while "Mbps" falls through to bits below.
+    if let Some(head) = s.strip_suffix("Bps") {
+        let (num, mult) = match head.chars().last() {
+            Some('k') | Some('K') => (&head[..head.len()-1], 1_000.0),
+            Some('m') | Some('M') => (&head[..head.len()-1], 1_000_000.0),
+            Some('g') | Some('G') => (&head[..head.len()-1], 1_000_000_000.0),
+            _ => (head, 1.0),
+        };
+        let bits = parse_num(num)? * mult * 8.0;
+        return Ok((bits / DNS_PACKET_BITS).floor() as i64);
+    }
+
     // Case 1: mbps/kbps/gbps/bps (bits per second)
     if lower.ends_with("kbps") {
         let v = parse_num(&lower[..lower.len()-4])? * 1_000.0;
@@ -192,6 +492,52 @@ fn get_system_resolvers() -> Vec<String> {
     ]
 }
 
+/// Line reader for wordlists and domain lists; gzip input (by magic bytes, so a `.gz`
+/// file or a renamed one) is decompressed on the fly, multi-member files included.
+pub fn open_text_input(path: &Path) -> Result<Box<dyn std::io::BufRead>> {
+    use std::io::{BufRead, BufReader};
+    let f = std::fs::File::open(path)
+        .with_context(|| format!("cannot open {}", path.display()))?;
+    let mut reader = BufReader::new(f);
+    if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        return Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(reader))));
+    }
+    Ok(Box::new(reader))
+}
+
+/// 读取解析器列表文件：每行一个，忽略空行与 `#` 注释
+pub fn read_resolver_file(path: &Path) -> Result<Vec<String>> {
+    Ok(read_weighted_resolver_file(path)?.into_iter().map(|(addr, _)| addr).collect())
+}
+
+/// 读取解析器文件，每行 `地址[,权重] [# 注释]`，未写权重时为 1.0。
+pub fn read_weighted_resolver_file(path: &Path) -> Result<Vec<(String, f64)>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read resolver file {}", path.display()))?;
+    Ok(data.lines().filter_map(parse_resolver_line).collect())
+}
+
+/// 解析单行；空行与纯注释返回 None。非法权重 (非数字、负数、NaN) 按 1.0 处理并告警。
+pub fn parse_resolver_line(line: &str) -> Option<(String, f64)> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    if line.is_empty() { return None; }
+    let (addr, weight) = match line.split_once(',') {
+        Some((a, w)) => {
+            let w = w.trim();
+            let weight = match w.parse::<f64>() {
+                Ok(v) if v.is_finite() && v >= 0.0 => v,
+                _ => {
+                    tracing::warn!("[resolver] invalid weight {:?} for {}, using 1.0", w, a.trim());
+                    1.0
+                }
+            };
+            (a.trim(), weight)
+        }
+        None => (line, 1.0),
+    };
+    Some((addr.to_string(), weight))
+}
+
 pub fn get_resolvers(input: &Vec<String>) -> Vec<String> {
     if !input.is_empty() {
         // 用户手动指定的 DNS 服务器
@@ -202,9 +548,247 @@ pub fn get_resolvers(input: &Vec<String>) -> Vec<String> {
     get_system_resolvers()
 }
 
+/// 过滤不可用的解析器地址 (当前仅支持 IPv4，查询固定发往 53 端口)，返回 (可用, 丢弃)。
+pub fn usable_resolvers(list: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut ok = Vec::with_capacity(list.len());
+    let mut bad = Vec::new();
+    for r in list.iter() {
+        let r = r.trim();
+        if r.parse::<std::net::Ipv4Addr>().is_ok() {
+            if !ok.iter().any(|x: &String| x == r) { ok.push(r.to_string()); }
+        } else if !r.is_empty() {
+            bad.push(r.to_string());
+        }
+    }
+    (ok, bad)
+}
+
+/// 解析器条目中的主机名 (`dns.quad9.net`、`udp://dns.quad9.net:53`)；IP 地址、其他协议/端口或无法识别的条目返回 None。
+pub fn resolver_hostname(entry: &str) -> Option<String> {
+    let s = entry.trim();
+    if s.parse::<std::net::IpAddr>().is_ok() || s.parse::<std::net::SocketAddr>().is_ok() { return None; }
+    let s = match s.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("udp") || scheme.eq_ignore_ascii_case("dns") => rest,
+        Some(_) => return None,
+        None => s,
+    };
+    let s = s.split('/').next().unwrap_or("");
+    let s = match s.rsplit_once(':') {
+        Some((host, "53")) => host,
+        Some(_) => return None,
+        None => s,
+    };
+    if !s.bytes().any(|b| b.is_ascii_alphabetic()) { return None; }
+    normalize_domain(s).ok().flatten()
+}
+
+/// 主机名形式的解析器在启动时解析一次为 IPv4，返回 (原条目, IP)；无法解析的主机名直接报错，
+/// 避免其进入解析器池后所有查询静默超时。
+pub async fn resolve_resolver_hosts(list: &[String]) -> Result<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    for entry in list.iter() {
+        let Some(host) = resolver_hostname(entry) else { continue };
+        let addrs = tokio::net::lookup_host((host.as_str(), 53)).await
+            .with_context(|| format!("cannot resolve resolver hostname {:?}", entry.trim()))?;
+        let Some(ip) = addrs.map(|a| a.ip()).find(|ip| ip.is_ipv4()) else {
+            anyhow::bail!("resolver hostname {:?} has no IPv4 address", entry.trim());
+        };
+        tracing::info!("[resolver] {} -> {}", entry.trim(), ip);
+        out.push((entry.clone(), ip.to_string()));
+    }
+    Ok(out)
+}
+
+/// 用 `resolve_resolver_hosts` 的结果替换列表中的主机名条目。
+pub fn apply_resolver_hosts(list: &mut [String], names: &[(String, String)]) {
+    for r in list.iter_mut() {
+        if let Some((_, ip)) = names.iter().find(|(n, _)| n == r) { *r = ip.clone(); }
+    }
+}
+
+/// 规整一条输入域名：去掉 scheme / userinfo / 路径 / 端口 / `*.` 前缀，小写，去首尾点。
+/// `Ok(None)` 表示空行或 `#` 注释 (静默跳过)；`Err` 为无法作为域名使用的输入 (调用方告警后跳过)。
+pub fn normalize_domain(raw: &str) -> Result<Option<String>, String> {
+    let s = raw.trim();
+    if s.is_empty() || s.starts_with('#') { return Ok(None); }
+    let s = s.split_once("://").map(|(_, rest)| rest).unwrap_or(s);
+    let s = s.split(['/', '?', '#']).next().unwrap_or("");
+    let s = s.rsplit_once('@').map(|(_, host)| host).unwrap_or(s);
+    let s = match s.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => s,
+    };
+    let s = s.strip_prefix("*.").unwrap_or(s);
+    let d = s.trim_matches('.').to_ascii_lowercase();
+    if d.is_empty() { return Err(format!("no host name in {:?}", raw.trim())); }
+    if d.len() > 253 { return Err(format!("name too long ({} bytes): {:?}", d.len(), raw.trim())); }
+    for label in d.split('.') {
+        let valid = !label.is_empty() && label.len() <= 63
+            && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+            && !label.starts_with('-') && !label.ends_with('-');
+        if !valid { return Err(format!("invalid label {:?} in {:?}", label, raw.trim())); }
+    }
+    Ok(Some(d))
+}
+
+/// `--exclude-domain-suffix` / `--exclude-domains-file`：丢弃等于某个后缀或以 `.后缀` 结尾的根域。
+/// 后缀与域名使用同样的规整规则 (`*.`、首尾点、大小写)；返回保留的域名与被排除的数量。
+pub fn exclude_by_suffix(domains: Vec<String>, suffixes: &[String]) -> (Vec<String>, usize) {
+    let suffixes: Vec<String> = suffixes.iter().filter_map(|s| match normalize_domain(s) {
+        Ok(v) => v,
+        Err(reason) => { tracing::warn!("[input] skipping exclude suffix: {}", reason); None }
+    }).collect();
+    if suffixes.is_empty() { return (domains, 0); }
+    let before = domains.len();
+    let kept: Vec<String> = domains.into_iter().filter(|d| {
+        !suffixes.iter().any(|s| d == s || (d.len() > s.len() && d.ends_with(s.as_str()) && d.as_bytes()[d.len() - s.len() - 1] == b'.'))
+    }).collect();
+    let excluded = before - kept.len();
+    (kept, excluded)
+}
+
+/// 规整后的根域去重，保留首次出现的顺序；返回去重后的列表与丢弃的重复数量。
+pub fn dedup_domains(domains: Vec<String>) -> (Vec<String>, usize) {
+    let before = domains.len();
+    let mut seen = std::collections::HashSet::with_capacity(before);
+    let kept: Vec<String> = domains.into_iter().filter(|d| seen.insert(d.clone())).collect();
+    let dups = before - kept.len();
+    (kept, dups)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn dedup_domains_keeps_first_occurrence() {
+        let raw = ["https://Example.com/", "api.test.org", "example.com.", "EXAMPLE.COM", "api.test.org"];
+        let normalized: Vec<String> = raw.iter().filter_map(|d| normalize_domain(d).unwrap()).collect();
+        let (kept, dups) = dedup_domains(normalized);
+        assert_eq!(kept, ["example.com", "api.test.org"]);
+        assert_eq!(dups, 3);
+        assert_eq!(dedup_domains(Vec::new()), (Vec::new(), 0));
+    }
+
+    #[test]
+    fn exclude_by_suffix_matches_whole_labels() {
+        let domains: Vec<String> = ["example.com", "shop.example.com", "badexample.com", "parked.net", "target.org"].iter().map(|s| s.to_string()).collect();
+        let suffixes = vec!["*.Example.COM.".to_string(), ".parked.net".to_string(), "not a suffix".to_string()];
+        let (kept, excluded) = exclude_by_suffix(domains, &suffixes);
+        assert_eq!(kept, ["badexample.com", "target.org"]);
+        assert_eq!(excluded, 3);
+    }
+
+    #[test]
+    fn check_rejects_unusable_settings() {
+        let ok = || Options { rate: 1000, concurrency: 10, timeout: 6, retry: 3, ..Default::default() };
+        assert!(ok().check().is_ok());
+        assert!(Options { retry: -1, ..ok() }.check().is_ok(), "-1 = unlimited");
+        for (opt, needle) in [
+            (Options { timeout: 0, ..ok() }, "--timeout"),
+            (Options { concurrency: 0, ..ok() }, "--concurrency"),
+            (Options { rate: 0, ..ok() }, "--band"),
+            (Options { rate: -5, ..ok() }, "--band"),
+            (Options { retry: -2, ..ok() }, "--retry"),
+        ] {
+            let mut opt = opt;
+            let err = opt.check().unwrap_err().to_string();
+            assert!(err.contains(needle), "{err}");
+        }
+    }
+
+    #[test]
+    fn band_parser_reports_bad_expressions() {
+        assert_eq!(parse_band("3m"), Ok("3m".to_string()));
+        assert!(parse_band("fast").unwrap_err().contains("e.g. 3m"));
+        assert!(parse_band("0").unwrap_err().contains("0 packets/s"));
+        assert!(parse_band("100bps").unwrap_err().contains("0 packets/s"));
+        assert!(parse_band("-2m").is_err());
+    }
+
+    #[test]
+    fn shard_parser_needs_index_below_count() {
+        assert_eq!(parse_shard("3/8"), Ok((3, 8)));
+        assert_eq!(parse_shard("0/1"), Ok((0, 1)));
+        assert!(parse_shard("8/8").unwrap_err().contains("0 <= I < N"));
+        assert!(parse_shard("1/0").is_err());
+        assert!(parse_shard("3").unwrap_err().contains("I/N"));
+    }
+
+    #[test]
+    fn quiet_drops_progress_but_not_logging() {
+        let mut opt = Options { progress: true, quiet: true, pure_output: false, log_level: "info".into(), ..Default::default() };
+        opt.check().unwrap();
+        assert!(!opt.progress);
+        assert!(!opt.silent && !opt.pure_output);
+        assert_eq!(opt.log_level, "info");
+    }
+
+    #[test]
+    fn normalize_domain_cleans_common_junk() {
+        for (raw, want) in [
+            ("Example.COM", "example.com"),
+            ("  example.com.  ", "example.com"),
+            ("https://example.com/path?q=1", "example.com"),
+            ("http://user:pw@Example.com:8443/x", "example.com"),
+            ("example.com:8080", "example.com"),
+            ("*.dev.example.com", "dev.example.com"),
+            ("_dmarc.example.com", "_dmarc.example.com"),
+        ] {
+            assert_eq!(normalize_domain(raw), Ok(Some(want.to_string())), "{raw}");
+        }
+        assert_eq!(normalize_domain(""), Ok(None));
+        assert_eq!(normalize_domain("   "), Ok(None));
+        assert_eq!(normalize_domain("# staging targets"), Ok(None));
+        for bad in ["http:///path", "exa mple.com", "-bad.example.com", "a..b.com", "bücher.de", &format!("{}.com", "x".repeat(64))] {
+            assert!(normalize_domain(bad).is_err(), "{bad} should be rejected");
+        }
+    }
+    #[test]
+    fn usable_resolvers_drops_invalid() {
+        let input: Vec<String> = vec!["8.8.8.8".into(), " 1.1.1.1 ".into(), "dns.google".into(), "".into(), "8.8.8.8".into(), "2001:4860::8888".into()];
+        let (ok, bad) = usable_resolvers(&input);
+        assert_eq!(ok, vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()]);
+        assert_eq!(bad, vec!["dns.google".to_string(), "2001:4860::8888".to_string()]);
+    }
+
+    #[test]
+    fn resolver_hostnames_are_recognised() {
+        assert_eq!(resolver_hostname("dns.quad9.net").as_deref(), Some("dns.quad9.net"));
+        assert_eq!(resolver_hostname(" udp://DNS.Google:53 ").as_deref(), Some("dns.google"));
+        for not_host in ["8.8.8.8", "8.8.8.8:53", "2001:4860::8888", "https://dns.google/dns-query", "dns.google:853", "", "1.2.3"] {
+            assert_eq!(resolver_hostname(not_host), None, "{not_host}");
+        }
+        let mut list = vec!["8.8.8.8".to_string(), "dns.quad9.net".to_string()];
+        apply_resolver_hosts(&mut list, &[("dns.quad9.net".into(), "9.9.9.9".into())]);
+        assert_eq!(list, vec!["8.8.8.8".to_string(), "9.9.9.9".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn unresolvable_resolver_hostname_is_an_error() {
+        let err = resolve_resolver_hosts(&["8.8.8.8".into(), "no-such-resolver.invalid".into()]).await.unwrap_err();
+        assert!(err.to_string().contains("no-such-resolver.invalid"), "{err}");
+        assert!(resolve_resolver_hosts(&["8.8.8.8".into()]).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn resolver_line_weights_and_comments() {
+        assert_eq!(parse_resolver_line("8.8.8.8"), Some(("8.8.8.8".into(), 1.0)));
+        assert_eq!(parse_resolver_line(" 1.1.1.1, 2.5  # fast"), Some(("1.1.1.1".into(), 2.5)));
+        assert_eq!(parse_resolver_line("9.9.9.9 # quad9"), Some(("9.9.9.9".into(), 1.0)));
+        assert_eq!(parse_resolver_line("9.9.9.9,abc"), Some(("9.9.9.9".into(), 1.0)));
+        assert_eq!(parse_resolver_line("9.9.9.9,-3"), Some(("9.9.9.9".into(), 1.0)));
+        assert_eq!(parse_resolver_line("# only a comment"), None);
+        assert_eq!(parse_resolver_line("   "), None);
+    }
+
+    #[test]
+    fn sqlite_output_records_the_resolver_without_timings() {
+        assert!(!Options::default().record_resolver());
+        assert!(Options { output_type: "sqlite".into(), ..Default::default() }.record_resolver());
+        assert!(Options { timings: true, ..Default::default() }.record_resolver());
+    }
+
     #[test]
     fn test_band2rate() {
         // For updated formula: bits / (80*8)
@@ -220,5 +804,15 @@ mod tests {
     assert_eq!(band2rate("1Gbps").unwrap(), ((1_000_000_000f64) / (80.0*8.0)).floor() as i64);
         assert_eq!(band2rate("500kpps").unwrap(), 500_000);
         assert_eq!(band2rate("1200pps").unwrap(), 1200);
+        // byte rates: x8 before dividing by packet size; MBps (bytes) != Mbps (bits)
+        assert_eq!(band2rate("1MBps").unwrap(), ((8_000_000f64) / (80.0*8.0)).floor() as i64);
+        assert_eq!(band2rate("1Mbps").unwrap(), ((1_000_000f64) / (80.0*8.0)).floor() as i64);
+        assert_eq!(band2rate("500kBps").unwrap(), ((4_000_000f64) / (80.0*8.0)).floor() as i64);
+        assert_eq!(band2rate("2GBps").unwrap(), ((16_000_000_000f64) / (80.0*8.0)).floor() as i64);
+        assert_eq!(band2rate("6400Bps").unwrap(), 80);
+        assert_eq!(band2rate("1.5 MB/s").unwrap(), ((12_000_000f64) / (80.0*8.0)).floor() as i64);
+        assert_eq!(band2rate("64KB/s").unwrap(), ((512_000f64) / (80.0*8.0)).floor() as i64);
+        assert_eq!(band2rate("640B/s").unwrap(), 8);
+        assert_eq!(band2rate("10Mb/s").unwrap(), band2rate("10Mbps").unwrap());
     }
 }
@@ -1,9 +1,11 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 #[cfg(feature = "parquet-out")]
@@ -29,53 +31,191 @@ use std::sync::Arc;
 pub struct ScanRecord {
     pub rtype: String,
     pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,              // 应答中的 TTL (秒)；hosts 文件等本地来源为空
 }
 
-#[derive(Serialize, Debug, Clone)]
+/// `--ecs` 多个子网时，每个客户端子网各自看到的 A/AAAA 应答
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct EcsAnswers {
+    pub subnet: String,
+    pub answers: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
 pub struct ScanResult {
     pub subdomain: String,
     pub answers: Vec<String>,          // 兼容旧字段: 仅提取 A/AAAA IP
     #[serde(skip_serializing_if = "Option::is_none")]
     pub records: Option<Vec<ScanRecord>>, // 细分记录类型 (A/AAAA/CNAME/TXT)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub wildcard: bool,                // 命中泛解析集合 (仅 --include-wildcard 时输出)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_ports: Option<Vec<u16>>,  // --probe-ports: 至少一个 IP 可连通的端口
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<String>,      // --timings: 给出最终应答的解析器
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,       // --timings: 该次成功查询耗时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub takeover_candidate: Option<String>, // --takeover-check: 命中的 CNAME 服务后缀
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,              // --asn: 命中白名单的 AS 号
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecs: Option<Vec<EcsAnswers>>,  // --ecs (多个): 各子网的应答集合
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged_from: Option<usize>,    // --merge-resolvers: 返回了记录的解析器数量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<usize>,      // --max-answers: 被截掉的 A/AAAA 地址数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rcode: Option<String>,         // --emit-negative: NXDOMAIN / SERVFAIL / REFUSED / TIMEOUT
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dnssec_validated: Option<bool>, // --dnssec: 应答 AD 标志 (解析器完成了 DNSSEC 验证)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rrsig: Option<bool>,           // --dnssec: 应答带 RRSIG (所在区域已签名)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,        // --tree: 最近的已扫描上级域
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,            // --tree: 相对最外层根域的层数 (api.example.com = 1)
+}
+
+impl ScanResult {
+    /// Text-format marker for `--max-answers` truncation, e.g. `...+12 more`.
+    pub fn more_marker(&self) -> Option<String> {
+        self.truncated.map(|k| format!("...+{} more", k))
+    }
+
+    /// Text-format tag for a result without answers: the rcode under `--emit-negative`,
+    /// `[no-result]` otherwise.
+    pub fn status_tag(&self) -> String {
+        format!("[{}]", self.rcode.as_deref().unwrap_or("no-result"))
+    }
+
+    /// `answers` split by address family (IPv4, IPv6); entries that are not addresses
+    /// (`--reverse` PTR names) are in neither.
+    pub fn split_families(&self) -> (Vec<&str>, Vec<&str>) {
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for a in self.answers.iter() {
+            match a.parse::<std::net::IpAddr>() {
+                Ok(std::net::IpAddr::V4(_)) => v4.push(a.as_str()),
+                Ok(std::net::IpAddr::V6(_)) => v6.push(a.as_str()),
+                Err(_) => {}
+            }
+        }
+        (v4, v6)
+    }
+}
+
+/// JSON 结果结构版本 (`_schema`)：ScanResult 字段增删或含义变化时递增，字段说明见 README「输出格式」
+pub const SCHEMA_VERSION: u32 = 7;
+
+/// json/jsonl 行：`_schema` 在前，其余为 ScanResult 字段，最后是按地址族拆分的 answers
+#[derive(Serialize)]
+struct Versioned<'a> {
+    #[serde(rename = "_schema")]
+    schema: u32,
+    #[serde(flatten)]
+    result: &'a ScanResult,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    a_records: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aaaa_records: Vec<&'a str>,
+}
+
+impl<'a> Versioned<'a> {
+    fn new(result: &'a ScanResult) -> Self {
+        let (a_records, aaaa_records) = result.split_families();
+        Versioned { schema: SCHEMA_VERSION, result, a_records, aaaa_records }
+    }
+}
+
+/// One json/jsonl result line (without newline), as written by `JsonLinesWriter`.
+pub fn json_line(r: &ScanResult) -> Result<String> {
+    Ok(serde_json::to_string(&Versioned::new(r))?)
 }
 
 pub trait OutputWriter: Send + Sync {
     fn write(&self, r: &ScanResult) -> Result<()>;
+    /// Push buffered lines to the underlying file (`--output-flush-ms` ticker).
+    fn flush(&self) -> Result<()> { Ok(()) }
     fn close(&self) -> Result<()> { Ok(()) }
 }
 
+/// 文件 sink 的写缓冲大小；stdout 仍由标准库按行刷新
+pub const SINK_BUFFER: usize = 64 * 1024;
+
+/// `--output-flush-ms` 默认值：文件输出每秒刷盘一次
+pub const DEFAULT_FLUSH_MS: u64 = 1000;
+
+// 多路输出：依次写入每个 writer
+impl OutputWriter for Vec<Box<dyn OutputWriter>> {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        for w in self.iter() { w.write(r)?; }
+        Ok(())
+    }
+    fn flush(&self) -> Result<()> {
+        for w in self.iter() { w.flush()?; }
+        Ok(())
+    }
+    fn close(&self) -> Result<()> {
+        for w in self.iter() { w.close()?; }
+        Ok(())
+    }
+}
+
 pub struct PlainWriter {
     file: Option<Mutex<Box<dyn Write + Send>>>,
     to_stdout: bool,
     detail: bool,
     domain_only: bool,
+    /// `--no-result-marker`: replaces `[no-result]`; rcode tags from `--emit-negative` are kept
+    no_result: Option<String>,
+    /// `--skip-empty`: hosts without any answer or record produce no line at all
+    skip_empty: bool,
 }
 
 impl PlainWriter {
-    pub fn new(path: Option<PathBuf>, to_stdout: bool, detail: bool, gzip: bool, domain_only: bool, append: bool) -> Result<Self> {
+    pub fn new(path: Option<PathBuf>, to_stdout: bool, detail: bool, gzip: bool, gzip_index: bool, domain_only: bool, append: bool) -> Result<Self> {
         let file = match path {
             Some(p) => {
-                let mut oo = OpenOptions::new();
-                oo.create(true).write(true);
-                if append { oo.append(true); } else { oo.truncate(true); }
-                let f = oo.open(p)?;
-                let w: Box<dyn Write + Send> = if gzip { Box::new(GzEncoder::new(f, Compression::default())) } else { Box::new(f) };
-                Some(Mutex::new(w))
+                Some(Mutex::new(open_sink(&p, gzip, gzip_index, append)?))
             }
             None => None,
         };
-        Ok(PlainWriter { file, to_stdout, detail, domain_only })
+        Ok(PlainWriter { file, to_stdout, detail, domain_only, no_result: None, skip_empty: false })
+    }
+
+    /// Spell the tag for answerless results as `marker` (default `[no-result]`), or drop
+    /// those lines entirely with `skip_empty`.
+    pub fn with_no_result(mut self, marker: Option<String>, skip_empty: bool) -> Self {
+        self.no_result = marker;
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Feed every byte written to the file into `digest` (`--output-hash`).
+    pub fn with_digest(mut self, digest: Option<&OutputDigest>) -> Self {
+        self.file = self.file.map(|f| digest_sink(f, digest));
+        self
     }
 }
 
 impl OutputWriter for PlainWriter {
     fn write(&self, r: &ScanResult) -> Result<()> {
+        if self.skip_empty && r.answers.is_empty() && r.records.as_ref().is_none_or(|x| x.is_empty()) {
+            return Ok(());
+        }
         let mut line = if self.domain_only {
             r.subdomain.clone()
         } else if r.answers.is_empty() {
-            format!("{}\t[no-result]", r.subdomain)
+            match (&self.no_result, &r.rcode) {
+                (Some(m), None) => format!("{}\t{}", r.subdomain, m),
+                _ => format!("{}\t{}", r.subdomain, r.status_tag()),
+            }
         } else {
-            format!("{}\t{}", r.subdomain, r.answers.join(","))
+            let mut answers = r.answers.join(",");
+            if let Some(m) = r.more_marker() { answers.push(','); answers.push_str(&m); }
+            format!("{}\t{}", r.subdomain, answers)
         };
         if self.detail {
             if let Some(recs) = &r.records {
@@ -84,73 +224,198 @@ impl OutputWriter for PlainWriter {
                 line.push_str(&det.join("|"));
             }
         }
+        if r.wildcard { line.push_str("\t[wildcard]"); }
+        if let Some(ports) = &r.open_ports {
+            let ps: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+            line.push_str(&format!("\tports={}", ps.join(",")));
+        }
+        if let Some(svc) = &r.takeover_candidate { line.push_str(&format!("\ttakeover={}", svc)); }
+        if let Some(ecs) = &r.ecs {
+            let per: Vec<String> = ecs.iter().map(|e| format!("{}={}", e.subnet, e.answers.join(","))).collect();
+            line.push_str(&format!("\tecs={}", per.join(";")));
+        }
+        if let Some(n) = r.merged_from { line.push_str(&format!("\tmerged_from={}", n)); }
         if self.to_stdout { println!("{}", line); }
         if let Some(f) = &self.file {
-            let mut guard = f.lock().unwrap();
-            writeln!(guard, "{}", line)?;
-            guard.flush()?;
+            writeln!(f.lock().unwrap(), "{}", line)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> { flush_sink(&self.file) }
+
+    fn close(&self) -> Result<()> { flush_sink(&self.file) }
+}
+
+fn flush_sink(file: &Option<Mutex<Box<dyn Write + Send>>>) -> Result<()> {
+    if let Some(f) = file { f.lock().unwrap().flush()?; }
+    Ok(())
+}
+
+/// `--output-hash` 摘要：输出文件 sink 写入的未压缩字节实时喂入，[`HashWriter`] 关闭时取值
+pub type OutputDigest = std::sync::Arc<Mutex<sha2::Sha256>>;
+
+// 位于缓冲 / gzip 之上：看到的是写入前的原始内容，无需结束时再读一遍文件
+struct DigestSink {
+    inner: Box<dyn Write + Send>,
+    digest: OutputDigest,
+}
+
+impl Write for DigestSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        sha2::Digest::update(&mut *self.digest.lock().unwrap(), &buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> { self.inner.flush() }
+}
+
+fn digest_sink(sink: Mutex<Box<dyn Write + Send>>, digest: Option<&OutputDigest>) -> Mutex<Box<dyn Write + Send>> {
+    match digest {
+        Some(d) => Mutex::new(Box::new(DigestSink { inner: sink.into_inner().unwrap(), digest: d.clone() })),
+        None => sink,
+    }
+}
+
+// 仅输出标签：子域相对其根域的部分 (api.example.com -> api，dev.api.example.com -> dev.api)，
+// 每个标签只输出一次，供排列组合 / 字典对比等工具使用；无应答的结果不输出
+pub struct LabelWriter {
+    file: Option<Mutex<Box<dyn Write + Send>>>,
+    to_stdout: bool,
+    /// 根域，长的在前以便最长匹配
+    roots: Vec<String>,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl LabelWriter {
+    pub fn new(path: Option<PathBuf>, to_stdout: bool, gzip: bool, gzip_index: bool, append: bool, roots: &[String]) -> Result<Self> {
+        let file = match path {
+            Some(p) => Some(Mutex::new(open_sink(&p, gzip, gzip_index, append)?)),
+            None => None,
+        };
+        let mut roots: Vec<String> = roots.iter().map(|r| r.trim_end_matches('.').to_ascii_lowercase()).collect();
+        roots.sort_by_key(|r| std::cmp::Reverse(r.len()));
+        Ok(Self { file, to_stdout, roots, seen: Mutex::new(HashSet::new()) })
+    }
+
+    /// Feed every byte written to the file into `digest` (`--output-hash`).
+    pub fn with_digest(mut self, digest: Option<&OutputDigest>) -> Self {
+        self.file = self.file.map(|f| digest_sink(f, digest));
+        self
+    }
+
+    /// Part of `host` left of its longest matching root; the leftmost label when no root matches.
+    fn label(&self, host: &str) -> Option<String> {
+        let host = host.trim_end_matches('.');
+        let lower = host.to_ascii_lowercase();
+        for root in self.roots.iter() {
+            if lower == *root { return None; } // the root itself has no label
+            if let Some(prefix) = lower.strip_suffix(root.as_str()).and_then(|p| p.strip_suffix('.')) {
+                return (!prefix.is_empty()).then(|| host[..prefix.len()].to_string());
+            }
+        }
+        host.split('.').next().filter(|l| !l.is_empty()).map(str::to_string)
+    }
+}
+
+impl OutputWriter for LabelWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        if r.answers.is_empty() && r.records.as_ref().is_none_or(|v| v.is_empty()) { return Ok(()); }
+        let Some(label) = self.label(&r.subdomain) else { return Ok(()) };
+        if !self.seen.lock().unwrap().insert(label.to_ascii_lowercase()) { return Ok(()); }
+        if self.to_stdout { println!("{}", label); }
+        if let Some(f) = &self.file {
+            writeln!(f.lock().unwrap(), "{}", label)?;
         }
         Ok(())
     }
+
+    fn flush(&self) -> Result<()> { flush_sink(&self.file) }
+
+    fn close(&self) -> Result<()> { flush_sink(&self.file) }
 }
 
+// json/jsonl：文件与 stdout 可同时开启 (tee)，每行依次写入两个 sink
 pub struct JsonLinesWriter {
     file: Option<Mutex<Box<dyn Write + Send>>>,
-    to_stdout: bool,
+    to_stdout: AtomicBool,
 }
 
 impl JsonLinesWriter {
-    pub fn new(path: Option<PathBuf>, to_stdout: bool, gzip: bool, append: bool) -> Result<Self> {
+    pub fn new(path: Option<PathBuf>, to_stdout: bool, gzip: bool, gzip_index: bool, append: bool) -> Result<Self> {
         let file = match path {
             Some(p) => {
-                let mut oo = OpenOptions::new();
-                oo.create(true).write(true);
-                if append { oo.append(true); } else { oo.truncate(true); }
-                let f = oo.open(p)?;
-                let w: Box<dyn Write + Send> = if gzip { Box::new(GzEncoder::new(f, Compression::default())) } else { Box::new(f) };
-                Some(Mutex::new(w))
+                Some(Mutex::new(open_sink(&p, gzip, gzip_index, append)?))
             }
             None => None,
         };
-        Ok(JsonLinesWriter { file, to_stdout })
+        Ok(JsonLinesWriter { file, to_stdout: AtomicBool::new(to_stdout) })
+    }
+
+    /// Feed every byte written to the file into `digest` (`--output-hash`).
+    pub fn with_digest(mut self, digest: Option<&OutputDigest>) -> Self {
+        self.file = self.file.map(|f| digest_sink(f, digest));
+        self
     }
 }
 
 impl OutputWriter for JsonLinesWriter {
     fn write(&self, r: &ScanResult) -> Result<()> {
-        let line = serde_json::to_string(r)?;
-        if self.to_stdout { println!("{}", line); }
+        let line = json_line(r)?;
+        // 文件优先落盘，stdout 侧失败不影响持久化
         if let Some(f) = &self.file {
-            let mut guard = f.lock().unwrap();
-            writeln!(guard, "{}", line)?;
-            guard.flush()?;
+            writeln!(f.lock().unwrap(), "{}", line)?;
+        }
+        if self.to_stdout.load(Ordering::Relaxed) {
+            let mut out = std::io::stdout().lock();
+            if let Err(e) = writeln!(out, "{}", line) {
+                // 下游管道关闭 (如 `| head`)：停止写 stdout，文件继续写
+                if e.kind() != std::io::ErrorKind::BrokenPipe || self.file.is_none() { return Err(e.into()); }
+                self.to_stdout.store(false, Ordering::Relaxed);
+            }
         }
         Ok(())
     }
+
+    fn flush(&self) -> Result<()> { flush_sink(&self.file) }
+
+    fn close(&self) -> Result<()> { flush_sink(&self.file) }
 }
 
 pub struct CsvWriter {
     file: Mutex<Box<dyn Write + Send>>,
     to_stdout: bool,
     detail: bool,
+    /// `--timings`: every row gets `resolver;latency_ms`, empty where a result has none
+    timings: bool,
 }
 
 impl CsvWriter {
-    pub fn new(path: PathBuf, to_stdout: bool, detail: bool, gzip: bool, append: bool) -> Result<Self> {
-        let mut oo = OpenOptions::new();
-        oo.create(true).write(true);
-        if append { oo.append(true); } else { oo.truncate(true); }
-        let f = oo.open(path)?;
-        let w: Box<dyn Write + Send> = if gzip { Box::new(GzEncoder::new(f, Compression::default())) } else { Box::new(f) };
-        Ok(CsvWriter { file: Mutex::new(w), to_stdout, detail })
+    pub fn new(path: PathBuf, to_stdout: bool, detail: bool, gzip: bool, gzip_index: bool, append: bool) -> Result<Self> {
+        let w = open_sink(&path, gzip, gzip_index, append)?;
+        Ok(CsvWriter { file: Mutex::new(w), to_stdout, detail, timings: false })
         // Parquet placeholder removed; will implement real writer in future.
     }
+
+    /// Add the two timing columns to every row, so rows without a timing keep the column count.
+    pub fn with_timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    /// Feed every byte written to the file into `digest` (`--output-hash`).
+    pub fn with_digest(mut self, digest: Option<&OutputDigest>) -> Self {
+        self.file = digest_sink(self.file, digest);
+        self
+    }
 }
 
 impl OutputWriter for CsvWriter {
     fn write(&self, r: &ScanResult) -> Result<()> {
         let mut guard = self.file.lock().unwrap();
-        let mut parts: Vec<String> = vec![r.subdomain.clone(), r.answers.join("|")];
+        let mut answers = r.answers.join("|");
+        if let Some(m) = r.more_marker() { answers.push('|'); answers.push_str(&m); }
+        let mut parts: Vec<String> = vec![r.subdomain.clone(), answers];
         if self.detail {
             if let Some(recs) = &r.records {
                 let det: Vec<String> = recs.iter().map(|x| format!("{}:{}", x.rtype, x.data)).collect();
@@ -159,39 +424,84 @@ impl OutputWriter for CsvWriter {
                 parts.push(String::new());
             }
         }
+        if self.timings {
+            parts.push(r.resolver.clone().unwrap_or_default());
+            parts.push(r.latency_ms.map(|v| v.to_string()).unwrap_or_default());
+        }
         let line = parts.join(";");
         if self.to_stdout { println!("{}", line); }
         writeln!(guard, "{}", line)?;
-        guard.flush()?;
         Ok(())
     }
+
+    fn flush(&self) -> Result<()> {
+        self.file.lock().unwrap().flush()?;
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> { OutputWriter::flush(self) }
 }
 
-pub fn build_writers(path: Option<PathBuf>, output_type: &str, to_stdout: bool, detail: bool, gzip: bool, append: bool) -> Result<Vec<Box<dyn OutputWriter>>> {
+/// Format-specific settings for [`build_writers`]; formats ignore the fields that don't apply.
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// txt / csv: append each result's typed records (`--detail-records`)
+    pub detail: bool,
+    /// txt: tag for answerless results instead of `[no-result]` (`--no-result-marker`)
+    pub no_result_marker: Option<String>,
+    /// txt: omit answerless results entirely (`--skip-empty`)
+    pub skip_empty: bool,
+    /// label: the scan's root domains; labels are taken relative to them
+    pub roots: Vec<String>,
+    /// csv: fixed `resolver;latency_ms` columns on every row (`--timings`)
+    pub timings: bool,
+    /// all file formats: hash the bytes written to `path` (`--output-hash`, see [`HashWriter`])
+    pub digest: Option<OutputDigest>,
+}
+
+/// `gzip_index`: block-gzip output plus a `<path>.idx` sidecar (see `gzindex`).
+pub fn build_writers(path: Option<PathBuf>, output_type: &str, to_stdout: bool, gzip: bool, gzip_index: bool, append: bool, fmt: &FormatOptions) -> Result<Vec<Box<dyn OutputWriter>>> {
     let mut v: Vec<Box<dyn OutputWriter>> = Vec::new();
     match output_type {
         "txt" => {
-            v.push(Box::new(PlainWriter::new(path, to_stdout, detail, gzip, false, append)?));
+            v.push(Box::new(PlainWriter::new(path, to_stdout, fmt.detail, gzip, gzip_index, false, append)?
+                .with_no_result(fmt.no_result_marker.clone(), fmt.skip_empty).with_digest(fmt.digest.as_ref())));
         }
         "txt-domain" => {
-            v.push(Box::new(PlainWriter::new(path, to_stdout, false, gzip, true, append)?));
+            v.push(Box::new(PlainWriter::new(path, to_stdout, false, gzip, gzip_index, true, append)?.with_digest(fmt.digest.as_ref())));
         }
         "txt-ks" => {
-            v.push(Box::new(KsWriter::new(path, to_stdout, gzip, append)?));
+            v.push(Box::new(KsWriter::new(path, to_stdout, gzip, gzip_index, append)?.with_digest(fmt.digest.as_ref())));
+        }
+        "label" => {
+            v.push(Box::new(LabelWriter::new(path, to_stdout, gzip, gzip_index, append, &fmt.roots)?.with_digest(fmt.digest.as_ref())));
+        }
+        "table" => {
+            v.push(Box::new(TableWriter::new(path, to_stdout, gzip, gzip_index, append)?.with_digest(fmt.digest.as_ref())));
         }
         "json" | "jsonl" => {
             if path.is_none() && !to_stdout {
-                return Err(anyhow::anyhow!("jsonl output requires either --output path or enable stdout (omit --not-print)"));
+                return Err(anyhow::anyhow!("json/jsonl output needs a sink: give --output <PATH> and/or drop --not-print (both can be combined)"));
             }
-            v.push(Box::new(JsonLinesWriter::new(path, to_stdout, gzip, append)?));
+            v.push(Box::new(JsonLinesWriter::new(path, to_stdout, gzip, gzip_index, append)?.with_digest(fmt.digest.as_ref())));
         }
         "csv" => {
             let p = path.ok_or_else(|| anyhow::anyhow!("csv output requires --output path"))?;
-            v.push(Box::new(CsvWriter::new(p, to_stdout, detail, gzip, append)?));
+            v.push(Box::new(CsvWriter::new(p, to_stdout, fmt.detail, gzip, gzip_index, append)?.with_timings(fmt.timings).with_digest(fmt.digest.as_ref())));
         }
         "parquet" => {
             return Err(anyhow::anyhow!("parquet output not implemented yet"));
         }
+        #[cfg(feature = "sqlite-out")]
+        "sqlite" => {
+            let p = path.ok_or_else(|| anyhow::anyhow!("sqlite output requires --output path"))?;
+            if fmt.digest.is_some() { anyhow::bail!("--output-hash does not support sqlite output: the database is not written as a byte stream"); }
+            v.push(Box::new(SqliteWriter::new(p, to_stdout)?));
+        }
+        #[cfg(not(feature = "sqlite-out"))]
+        "sqlite" => {
+            return Err(anyhow::anyhow!("sqlite output requires building with --features sqlite-out"));
+        }
         other => {
             return Err(anyhow::anyhow!("unsupported output type: {}", other));
         }
@@ -199,6 +509,624 @@ pub fn build_writers(path: Option<PathBuf>, output_type: &str, to_stdout: bool,
     Ok(v)
 }
 
+/// Create the missing parent directory of an output file (`-o results/scan.jsonl`),
+/// naming the directory when that fails instead of surfacing a bare OS error.
+pub fn ensure_parent_dir(path: &Path) -> Result<()> {
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else { return Ok(()) };
+    if parent.is_dir() { return Ok(()); }
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("cannot create output directory {}", parent.display()))
+}
+
+fn open_sink(path: &Path, gzip: bool, gzip_index: bool, append: bool) -> Result<Box<dyn Write + Send>> {
+    ensure_parent_dir(path)?;
+    if gzip_index {
+        if append { anyhow::bail!("--gzip-index cannot append to an existing file"); }
+        return Ok(Box::new(crate::gzindex::BlockGzWriter::create(path)?));
+    }
+    let mut oo = OpenOptions::new();
+    oo.create(true).write(true);
+    if append { oo.append(true); } else { oo.truncate(true); }
+    let f = oo.open(path).with_context(|| format!("cannot open output file {}", path.display()))?;
+    // 缓冲在压缩层之上：gzip 不再逐行做 sync flush
+    Ok(if gzip {
+        Box::new(std::io::BufWriter::with_capacity(SINK_BUFFER, GzEncoder::new(f, Compression::default())))
+    } else {
+        Box::new(std::io::BufWriter::with_capacity(SINK_BUFFER, f))
+    })
+}
+
+/// Subdomains that already have a result in an output file about to be appended to.
+/// Lines without answers (`[no-result]` or the `--no-result-marker` given as `no_result`, `[NXDOMAIN]`-style tags,
+/// empty answer lists) are left out so a later hit still gets written. A missing file yields an empty set; gzip
+/// files may hold several members.
+pub fn existing_subdomains(path: &Path, output_type: &str, gzip: bool, no_result: Option<&str>) -> Result<HashSet<String>> {
+    use std::io::{BufRead, BufReader, Read};
+    // sqlite 不是按行文本；数据库本身总是追加写入，不做文件级去重
+    if output_type == "sqlite" { return Ok(HashSet::new()); }
+    let f = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let reader: Box<dyn Read> = if gzip { Box::new(flate2::read::MultiGzDecoder::new(f)) } else { Box::new(f) };
+    let mut known = HashSet::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() { continue; }
+        let host = match output_type {
+            "json" | "jsonl" => {
+                let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else { continue };
+                let has_answers = v["answers"].as_array().is_some_and(|a| !a.is_empty()) || v["records"].as_array().is_some_and(|a| !a.is_empty());
+                if !has_answers { continue; }
+                v["subdomain"].as_str().map(str::to_string)
+            }
+            "csv" => match line.split(';').collect::<Vec<_>>().as_slice() {
+                [host, answers, ..] if !answers.is_empty() => Some(host.to_string()),
+                _ => None,
+            },
+            "txt-ks" => line.split_once(" => ").map(|(host, _)| host.to_string()),
+            "txt-domain" => Some(line.to_string()),
+            _ => match line.split_once('\t') {
+                Some((host, rest)) if !rest.starts_with('[') && Some(rest) != no_result => Some(host.to_string()),
+                _ => None,
+            },
+        };
+        if let Some(h) = host { known.insert(h); }
+    }
+    Ok(known)
+}
+
+// 按记录类型拆分：<dir>/a.txt, aaaa.txt, cname.txt, txt.txt ...，每行 "subdomain\tdata"
+pub struct SplitWriter {
+    dir: PathBuf,
+    gzip: bool,
+    append: bool,
+    files: Mutex<HashMap<String, Box<dyn Write + Send>>>,
+}
+
+impl SplitWriter {
+    pub fn new(dir: PathBuf, gzip: bool, append: bool) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir, gzip, append, files: Mutex::new(HashMap::new()) })
+    }
+
+    fn file_name(&self, rtype: &str) -> PathBuf {
+        let mut name = format!("{}.txt", rtype.to_ascii_lowercase());
+        if self.gzip { name.push_str(".gz"); }
+        self.dir.join(name)
+    }
+}
+
+impl OutputWriter for SplitWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        let Some(recs) = &r.records else { return Ok(()) };
+        let mut files = self.files.lock().unwrap();
+        for rec in recs.iter() {
+            if !files.contains_key(&rec.rtype) {
+                let sink = open_sink(&self.file_name(&rec.rtype), self.gzip, false, self.append)?;
+                files.insert(rec.rtype.clone(), sink);
+            }
+            let f = files.get_mut(&rec.rtype).unwrap();
+            writeln!(f, "{}\t{}", r.subdomain, rec.data)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        for f in self.files.lock().unwrap().values_mut() { f.flush()?; }
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        // dropping the sinks finishes gzip trailers
+        let files = std::mem::take(&mut *self.files.lock().unwrap());
+        for (_, mut f) in files.into_iter() { f.flush()?; }
+        Ok(())
+    }
+}
+
+// 全程去重：同一主机仅在应答集合变化时再次输出 (应答相同则跳过)
+pub struct DedupWriter {
+    inner: Vec<Box<dyn OutputWriter>>,
+    seen: Mutex<HashMap<String, Vec<String>>>,
+    /// --append: 输出文件中已有结果的子域，本次不再写出
+    known: HashSet<String>,
+}
+
+impl DedupWriter {
+    pub fn new(inner: Vec<Box<dyn OutputWriter>>) -> Self {
+        Self::with_known(inner, HashSet::new())
+    }
+
+    pub fn with_known(inner: Vec<Box<dyn OutputWriter>>, known: HashSet<String>) -> Self {
+        Self { inner, seen: Mutex::new(HashMap::new()), known }
+    }
+
+    fn signature(r: &ScanResult) -> Vec<String> {
+        let mut sig = r.answers.clone();
+        if let Some(recs) = &r.records {
+            sig.extend(recs.iter().map(|x| format!("{}:{}", x.rtype, x.data)));
+        }
+        if r.wildcard { sig.push("[wildcard]".into()); }
+        sig.sort(); sig.dedup();
+        sig
+    }
+}
+
+impl OutputWriter for DedupWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        if self.known.contains(&r.subdomain) { return Ok(()); }
+        let sig = Self::signature(r);
+        {
+            let mut seen = self.seen.lock().unwrap();
+            if seen.get(&r.subdomain) == Some(&sig) { return Ok(()); }
+            seen.insert(r.subdomain.clone(), sig);
+        }
+        for w in self.inner.iter() { w.write(r)?; }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> { self.inner.flush() }
+
+    fn close(&self) -> Result<()> {
+        for w in self.inner.iter() { w.close()?; }
+        Ok(())
+    }
+}
+
+// 通道输出：把结果转发给库调用方 (scanner::enumerate)，接收端关闭后静默丢弃
+pub struct ChannelWriter {
+    tx: futures::channel::mpsc::UnboundedSender<ScanResult>,
+}
+
+impl ChannelWriter {
+    pub fn new(tx: futures::channel::mpsc::UnboundedSender<ScanResult>) -> Self {
+        Self { tx }
+    }
+}
+
+impl OutputWriter for ChannelWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        let _ = self.tx.unbounded_send(r.clone());
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        self.tx.close_channel();
+        Ok(())
+    }
+}
+
+// 内存收集：结果追加到共享 Vec，供嵌入调用方 / 测试在扫描结束后直接检查 (scanner::run_with)
+#[derive(Default)]
+pub struct VecWriter {
+    results: std::sync::Arc<Mutex<Vec<ScanResult>>>,
+}
+
+impl VecWriter {
+    pub fn new(results: std::sync::Arc<Mutex<Vec<ScanResult>>>) -> Self {
+        Self { results }
+    }
+
+    /// Shared handle to the collected results; keep it before boxing the writer.
+    pub fn results(&self) -> std::sync::Arc<Mutex<Vec<ScanResult>>> {
+        self.results.clone()
+    }
+}
+
+impl OutputWriter for VecWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        self.results.lock().unwrap().push(r.clone());
+        Ok(())
+    }
+}
+
+// 排序输出：缓存全部结果，close() 时按反转标签排序后写入 inner
+// (com.example.api 形式，同级子域聚在一起)；内存占用随结果数线性增长
+pub struct SortingWriter {
+    inner: Box<dyn OutputWriter>,
+    buf: Mutex<Vec<ScanResult>>,
+}
+
+impl SortingWriter {
+    pub fn new(inner: Box<dyn OutputWriter>) -> Self {
+        Self { inner, buf: Mutex::new(Vec::new()) }
+    }
+
+    fn sort_key(sub: &str) -> Vec<String> {
+        sub.trim_end_matches('.').rsplit('.').map(|l| l.to_ascii_lowercase()).collect()
+    }
+}
+
+impl OutputWriter for SortingWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        self.buf.lock().unwrap().push(r.clone());
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        let mut all = std::mem::take(&mut *self.buf.lock().unwrap());
+        // 稳定排序：同一主机多次输出保持原有先后
+        all.sort_by_cached_key(|r| Self::sort_key(&r.subdomain));
+        for r in all.iter() { self.inner.write(r)?; }
+        self.inner.close()
+    }
+}
+
+/// `table` 输出缓存超过该行数时告警一次 (全部结果留在内存直到结束)
+pub const TABLE_WARN_ROWS: usize = 50_000;
+
+// 表格输出 (给人看)：缓存全部结果，close() 时按终端宽度对齐渲染
+// 子域 | IP | 记录类型；IP 列放不下时折行。仅 stdout 为终端且未设置 NO_COLOR 时着色
+pub struct TableWriter {
+    file: Option<Mutex<Box<dyn Write + Send>>>,
+    to_stdout: bool,
+    rows: Mutex<Vec<ScanResult>>,
+    warned: AtomicBool,
+}
+
+impl TableWriter {
+    pub fn new(path: Option<PathBuf>, to_stdout: bool, gzip: bool, gzip_index: bool, append: bool) -> Result<Self> {
+        let file = match path {
+            Some(p) => Some(Mutex::new(open_sink(&p, gzip, gzip_index, append)?)),
+            None => None,
+        };
+        Ok(Self { file, to_stdout, rows: Mutex::new(Vec::new()), warned: AtomicBool::new(false) })
+    }
+
+    /// Feed every byte written to the file into `digest` (`--output-hash`).
+    pub fn with_digest(mut self, digest: Option<&OutputDigest>) -> Self {
+        self.file = self.file.map(|f| digest_sink(f, digest));
+        self
+    }
+
+    /// `COLUMNS` when set (shells export it for interactive sessions), otherwise 120.
+    fn terminal_width() -> usize {
+        std::env::var("COLUMNS").ok().and_then(|v| v.parse().ok()).filter(|w| *w >= 40).unwrap_or(120)
+    }
+
+    fn use_color() -> bool {
+        use std::io::IsTerminal;
+        std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+    }
+}
+
+fn paint(on: bool, code: &str, s: &str) -> String {
+    if on { format!("\x1b[{}m{}\x1b[0m", code, s) } else { s.to_string() }
+}
+
+/// Render `rows` as an aligned table no wider than `width` where possible: long IP lists wrap
+/// onto continuation lines, an over-long subdomain pushes its own row out instead of being cut.
+pub fn render_table(rows: &[ScanResult], width: usize, color: bool) -> String {
+    let types: Vec<String> = rows.iter().map(|r| {
+        let mut t: Vec<&str> = Vec::new();
+        for rec in r.records.iter().flatten() {
+            if !t.contains(&rec.rtype.as_str()) { t.push(&rec.rtype); }
+        }
+        let mut s = if t.is_empty() && !r.answers.is_empty() { "A".to_string() } else { t.join(",") };
+        if r.wildcard { s.push_str(if s.is_empty() { "[wildcard]" } else { " [wildcard]" }); }
+        if s.is_empty() { r.rcode.clone().unwrap_or_else(|| "-".into()) } else { s }
+    }).collect();
+    let sub_w = rows.iter().map(|r| r.subdomain.chars().count()).max().unwrap_or(0).max("SUBDOMAIN".len()).min(width / 2);
+    let types_w = types.iter().map(|t| t.chars().count()).max().unwrap_or(0).max("TYPES".len());
+    let ips_w = width.saturating_sub(sub_w + types_w + 4).max(15);
+    let mut out = String::new();
+    let header = format!("{:<sub_w$}  {:<ips_w$}  {}", "SUBDOMAIN", "IPS", "TYPES");
+    out.push_str(&paint(color, "1", header.trim_end()));
+    out.push('\n');
+    out.push_str(&"-".repeat(sub_w + ips_w + types_w + 4));
+    out.push('\n');
+    for (r, t) in rows.iter().zip(types.iter()) {
+        // greedy wrap of "ip, ip, ..." into ips_w-wide lines
+        let mut lines: Vec<String> = Vec::new();
+        let mut cur = String::new();
+        for ip in r.answers.iter().cloned().chain(r.more_marker()) {
+            let add = if cur.is_empty() { ip.clone() } else { format!(", {}", ip) };
+            if !cur.is_empty() && cur.chars().count() + add.chars().count() > ips_w {
+                lines.push(std::mem::take(&mut cur) + ",");
+                cur = ip.clone();
+            } else {
+                cur.push_str(&add);
+            }
+        }
+        if !cur.is_empty() || lines.is_empty() { lines.push(if cur.is_empty() { "-".into() } else { cur }); }
+        for (i, l) in lines.iter().enumerate() {
+            let sub = if i == 0 { r.subdomain.as_str() } else { "" };
+            let sub_pad = " ".repeat(sub_w.saturating_sub(sub.chars().count()));
+            let ip_pad = " ".repeat(ips_w.saturating_sub(l.chars().count()));
+            let line = if i == 0 {
+                format!("{}{}  {}{}  {}", paint(color, "36", sub), sub_pad, paint(color, "32", l), ip_pad, paint(color, "33", t))
+            } else {
+                format!("{}  {}", " ".repeat(sub_w), paint(color, "32", l))
+            };
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+impl OutputWriter for TableWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        let mut rows = self.rows.lock().unwrap();
+        rows.push(r.clone());
+        if rows.len() > TABLE_WARN_ROWS && !self.warned.swap(true, Ordering::Relaxed) {
+            tracing::warn!("[output] table output keeps every result in memory until the scan ends ({}+ rows); use txt/jsonl for large scans", TABLE_WARN_ROWS);
+        }
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        let rows = std::mem::take(&mut *self.rows.lock().unwrap());
+        if rows.is_empty() { return flush_sink(&self.file); }
+        let width = Self::terminal_width();
+        if self.to_stdout {
+            print!("{}", render_table(&rows, width, Self::use_color()));
+        }
+        if let Some(f) = &self.file {
+            f.lock().unwrap().write_all(render_table(&rows, width, false).as_bytes())?;
+        }
+        flush_sink(&self.file)
+    }
+}
+
+/// 输出队列容量：写线程落后时工作任务在此阻塞 (背压)，内存不随积压无限增长
+pub const OUTPUT_QUEUE: usize = 4096;
+
+enum QueueMsg {
+    Result(Box<ScanResult>),
+    Flush,
+    Close,
+}
+
+// 队列输出：工作任务只把结果送入有界通道，由独立线程依次写入 inner。
+// 热路径上不再争抢各 writer 的锁，写入顺序即入队顺序
+pub struct QueuedWriter {
+    tx: std::sync::mpsc::SyncSender<QueueMsg>,
+    drain: Mutex<Option<std::thread::JoinHandle<Result<()>>>>,
+}
+
+impl QueuedWriter {
+    pub fn spawn(inner: Box<dyn OutputWriter>) -> Result<Self> {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<QueueMsg>(OUTPUT_QUEUE);
+        let drain = std::thread::Builder::new().name("rusub-output".into()).spawn(move || {
+            // 单条写入失败不中断队列；第一个错误在 close() 时返回
+            let mut first_err = None;
+            for msg in rx {
+                let (res, done) = match msg {
+                    QueueMsg::Result(r) => (inner.write(&r), false),
+                    QueueMsg::Flush => (inner.flush(), false),
+                    QueueMsg::Close => (inner.close(), true),
+                };
+                if let Err(e) = res { first_err.get_or_insert(e); }
+                if done { break; }
+            }
+            first_err.map_or(Ok(()), Err)
+        })?;
+        Ok(Self { tx, drain: Mutex::new(Some(drain)) })
+    }
+}
+
+impl OutputWriter for QueuedWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        self.tx.send(QueueMsg::Result(Box::new(r.clone()))).map_err(|_| anyhow::anyhow!("output writer already closed"))
+    }
+
+    fn flush(&self) -> Result<()> {
+        let _ = self.tx.send(QueueMsg::Flush);
+        Ok(())
+    }
+
+    /// Drains everything queued so far, closes the inner writers and waits for the thread.
+    fn close(&self) -> Result<()> {
+        let Some(drain) = self.drain.lock().unwrap().take() else { return Ok(()) };
+        let _ = self.tx.send(QueueMsg::Close);
+        drain.join().map_err(|_| anyhow::anyhow!("output thread panicked"))?
+    }
+}
+
+// --max-answers：每个结果最多保留 N 个 A/AAAA 地址 (去重后的前 N 个)，records 中只去掉被截掉地址的记录
+// (CNAME 等保留)，truncated 记录截掉的数量。放在格式 writer 外层，去重 / 状态仍基于完整应答
+pub struct AnswerCapWriter {
+    inner: Box<dyn OutputWriter>,
+    max: usize,
+}
+
+impl AnswerCapWriter {
+    pub fn new(inner: Box<dyn OutputWriter>, max: usize) -> Self {
+        Self { inner, max }
+    }
+
+    pub fn cap(r: &ScanResult, max: usize) -> Option<ScanResult> {
+        if r.answers.len() <= max { return None; }
+        let mut out = r.clone();
+        let dropped: HashSet<String> = out.answers.split_off(max).into_iter().collect();
+        if let Some(recs) = out.records.as_mut() {
+            recs.retain(|x| !((x.rtype == "A" || x.rtype == "AAAA") && dropped.contains(&x.data)));
+        }
+        out.truncated = Some(dropped.len());
+        Some(out)
+    }
+}
+
+impl OutputWriter for AnswerCapWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        match Self::cap(r, self.max) {
+            Some(capped) => self.inner.write(&capped),
+            None => self.inner.write(r),
+        }
+    }
+
+    fn flush(&self) -> Result<()> { self.inner.flush() }
+
+    fn close(&self) -> Result<()> { self.inner.close() }
+}
+
+/// 发现树：扫描的根域集合。主机的 parent 是最近的上级根域 (嵌套根域如 example.com 与
+/// dev.example.com 同时扫描时为 dev.example.com)，depth 为相对最外层根域的标签层数
+pub struct DiscoveryTree {
+    roots: HashSet<String>,
+}
+
+impl DiscoveryTree {
+    pub fn new(roots: &[String]) -> Self {
+        Self { roots: roots.iter().map(|r| r.trim().trim_end_matches('.').to_ascii_lowercase()).filter(|r| !r.is_empty()).collect() }
+    }
+
+    /// `(parent, depth)` of `host`; `None` when no scanned root encloses it.
+    pub fn position(&self, host: &str) -> Option<(String, u32)> {
+        let lower = host.trim_end_matches('.').to_ascii_lowercase();
+        let labels: Vec<&str> = lower.split('.').collect();
+        let mut parent: Option<usize> = None;
+        let mut outermost = None;
+        for i in 1..labels.len() {
+            if self.roots.contains(&labels[i..].join(".")) {
+                parent.get_or_insert(i);
+                outermost = Some(i);
+            }
+        }
+        Some((labels[parent?..].join("."), outermost? as u32))
+    }
+
+    /// Depth for the status DB's `domain_level`; 0 outside the tree (e.g. reverse mode).
+    pub fn level(&self, host: &str) -> i32 {
+        self.position(host).map_or(0, |(_, d)| d as i32)
+    }
+}
+
+/// `--tree`: 结果附带 parent / depth (JSON 类输出可见)，供下游重建枚举树
+pub struct TreeWriter {
+    tree: std::sync::Arc<DiscoveryTree>,
+    inner: Box<dyn OutputWriter>,
+}
+
+impl TreeWriter {
+    pub fn new(tree: std::sync::Arc<DiscoveryTree>, inner: Box<dyn OutputWriter>) -> Self {
+        Self { tree, inner }
+    }
+}
+
+impl OutputWriter for TreeWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        let Some((parent, depth)) = self.tree.position(&r.subdomain) else { return self.inner.write(r) };
+        self.inner.write(&ScanResult { parent: Some(parent), depth: Some(depth), ..r.clone() })
+    }
+
+    fn flush(&self) -> Result<()> { self.inner.flush() }
+
+    fn close(&self) -> Result<()> { self.inner.close() }
+}
+
+/// `--output-hash` 旁路文件：`<输出路径>.sha256`
+pub fn hash_path(output: &Path) -> PathBuf {
+    let mut p = output.as_os_str().to_owned();
+    p.push(".sha256");
+    PathBuf::from(p)
+}
+
+// --output-hash：包在整条输出链 (含输出线程) 外层。摘要由文件 sink 写入时实时累计 (见 FormatOptions::digest)，
+// close() 在内层全部关闭后取值，写入 <输出>.sha256 (sha256sum 格式)。gzip 输出对压缩前的原始内容计算，
+// 与压缩级别 / 分块无关。--append 时只覆盖本次追加的字节：旁路文件对应本次写入的内容，不再能用
+// sha256sum -c 校验整个文件
+pub struct HashWriter {
+    inner: Box<dyn OutputWriter>,
+    path: PathBuf,
+    digest: OutputDigest,
+}
+
+impl HashWriter {
+    pub fn new(inner: Box<dyn OutputWriter>, path: PathBuf, digest: OutputDigest) -> Self {
+        Self { inner, path, digest }
+    }
+}
+
+impl OutputWriter for HashWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> { self.inner.write(r) }
+
+    fn flush(&self) -> Result<()> { self.inner.flush() }
+
+    fn close(&self) -> Result<()> {
+        use sha2::Digest;
+        self.inner.close()?;
+        let digest: String = self.digest.lock().unwrap().clone().finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        let name = self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let side = hash_path(&self.path);
+        std::fs::write(&side, format!("{}  {}\n", digest, name)).with_context(|| format!("cannot write {}", side.display()))?;
+        Ok(())
+    }
+}
+
+/// `--partial-flush` 旁路文件：`<输出路径>.partial.jsonl`
+pub fn partial_path(output: &Path) -> PathBuf {
+    let mut p = output.as_os_str().to_owned();
+    p.push(".partial.jsonl");
+    PathBuf::from(p)
+}
+
+// 缓冲输出 (--sort-output / table) 的中途快照：结果到达即以 JSONL 写入旁路文件 (同样去重)，
+// 每隔 every 落盘一次；扫描正常结束时删除，崩溃 / 被杀时保留已得到的结果。最终输出文件才是完整结果
+pub struct PartialWriter {
+    path: PathBuf,
+    inner: DedupWriter,
+    every: std::time::Duration,
+    last: Mutex<std::time::Instant>,
+}
+
+impl PartialWriter {
+    pub fn new(path: PathBuf, every: std::time::Duration) -> Result<Self> {
+        let sink = JsonLinesWriter::new(Some(path.clone()), false, false, false, false)?;
+        Ok(Self { path, inner: DedupWriter::new(vec![Box::new(sink)]), every, last: Mutex::new(std::time::Instant::now()) })
+    }
+
+    fn flush_due(&self) -> Result<()> {
+        let mut last = self.last.lock().unwrap();
+        if last.elapsed() < self.every { return Ok(()); }
+        *last = std::time::Instant::now();
+        self.inner.flush()
+    }
+}
+
+impl OutputWriter for PartialWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        self.inner.write(r)?;
+        self.flush_due()
+    }
+
+    fn flush(&self) -> Result<()> { self.flush_due() }
+
+    fn close(&self) -> Result<()> {
+        self.inner.close()?;
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("cannot remove {}", self.path.display())),
+            _ => Ok(()),
+        }
+    }
+}
+
+// 逐行刷盘 (--output-flush-ms 0)：每条结果写完立即 flush，便于 tail -f 实时查看
+pub struct LineFlushWriter {
+    inner: Box<dyn OutputWriter>,
+}
+
+impl LineFlushWriter {
+    pub fn new(inner: Box<dyn OutputWriter>) -> Self {
+        Self { inner }
+    }
+}
+
+impl OutputWriter for LineFlushWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        self.inner.write(r)?;
+        self.inner.flush()
+    }
+
+    fn flush(&self) -> Result<()> { self.inner.flush() }
+
+    fn close(&self) -> Result<()> { self.inner.close() }
+}
+
 // 链式输出：sub => CNAME xxx => CNAME yyy => ip => ip
 pub struct KsWriter {
     file: Option<Mutex<Box<dyn Write + Send>>>,
@@ -206,20 +1134,21 @@ pub struct KsWriter {
 }
 
 impl KsWriter {
-    pub fn new(path: Option<PathBuf>, to_stdout: bool, gzip: bool, append: bool) -> Result<Self> {
+    pub fn new(path: Option<PathBuf>, to_stdout: bool, gzip: bool, gzip_index: bool, append: bool) -> Result<Self> {
         let file = match path {
             Some(p) => {
-                let mut oo = OpenOptions::new();
-                oo.create(true).write(true);
-                if append { oo.append(true); } else { oo.truncate(true); }
-                let f = oo.open(p)?;
-                let w: Box<dyn Write + Send> = if gzip { Box::new(GzEncoder::new(f, Compression::default())) } else { Box::new(f) };
-                Some(Mutex::new(w))
+                Some(Mutex::new(open_sink(&p, gzip, gzip_index, append)?))
             }
             None => None,
         };
         Ok(Self { file, to_stdout })
     }
+
+    /// Feed every byte written to the file into `digest` (`--output-hash`).
+    pub fn with_digest(mut self, digest: Option<&OutputDigest>) -> Self {
+        self.file = self.file.map(|f| digest_sink(f, digest));
+        self
+    }
 }
 
 impl OutputWriter for KsWriter {
@@ -247,16 +1176,20 @@ impl OutputWriter for KsWriter {
         parts.push(r.subdomain.clone());
         for c in cnames { parts.push(format!("CNAME {}", c)); }
         for ip in ips { parts.push(ip); }
-        let line = parts.join(" => ");
+        if let Some(m) = r.more_marker() { parts.push(m); }
+        let mut line = parts.join(" => ");
+        if r.wildcard { line.push_str(" [wildcard]"); }
 
         if self.to_stdout { println!("{}", line); }
         if let Some(f) = &self.file {
-            let mut g = f.lock().unwrap();
-            writeln!(g, "{}", line)?;
-            g.flush()?;
+            writeln!(f.lock().unwrap(), "{}", line)?;
         }
         Ok(())
     }
+
+    fn flush(&self) -> Result<()> { flush_sink(&self.file) }
+
+    fn close(&self) -> Result<()> { flush_sink(&self.file) }
 }
 
 #[cfg(feature = "parquet-out")]
@@ -290,7 +1223,7 @@ impl OutputWriter for ParquetWriter {
         if self.to_stdout {
             // for parity with other writers, emit a concise line to stdout
             let mut line = if r.answers.is_empty() {
-                format!("{}\t[no-result]", r.subdomain)
+                format!("{}\t{}", r.subdomain, r.status_tag())
             } else {
                 format!("{}\t{}", r.subdomain, r.answers.join(","))
             };
@@ -387,3 +1320,477 @@ impl OutputWriter for ParquetWriter {
         Ok(())
     }
 }
+
+/// `--output-type sqlite`: one row per record in table `results`, inserted in
+/// batched transactions (every `SQLITE_BATCH` rows and on close).
+#[cfg(feature = "sqlite-out")]
+pub struct SqliteWriter {
+    conn: Mutex<rusqlite::Connection>,
+    to_stdout: bool,
+    pending: Mutex<Vec<SqliteRow>>,
+}
+
+#[cfg(feature = "sqlite-out")]
+pub const SQLITE_BATCH: usize = 1000;
+
+#[cfg(feature = "sqlite-out")]
+struct SqliteRow {
+    subdomain: String,
+    rtype: String,
+    data: String,
+    ttl: Option<u32>,
+    resolver: Option<String>,
+    seen_at: i64,
+}
+
+#[cfg(feature = "sqlite-out")]
+impl SqliteWriter {
+    /// Opens (or creates) the database; existing rows are kept so runs accumulate.
+    pub fn new(path: PathBuf, to_stdout: bool) -> Result<Self> {
+        ensure_parent_dir(&path)?;
+        let conn = rusqlite::Connection::open(&path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode=WAL;
+             CREATE TABLE IF NOT EXISTS results (
+                 subdomain TEXT NOT NULL,
+                 rtype     TEXT NOT NULL,
+                 data      TEXT NOT NULL,
+                 ttl       INTEGER,
+                 resolver  TEXT,
+                 seen_at   INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS results_subdomain ON results(subdomain);",
+        )?;
+        Ok(Self { conn: Mutex::new(conn), to_stdout, pending: Mutex::new(Vec::with_capacity(SQLITE_BATCH)) })
+    }
+
+    fn flush_rows(&self, rows: Vec<SqliteRow>) -> Result<()> {
+        if rows.is_empty() { return Ok(()); }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("INSERT INTO results (subdomain, rtype, data, ttl, resolver, seen_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+            for r in rows.iter() {
+                stmt.execute(rusqlite::params![r.subdomain, r.rtype, r.data, r.ttl, r.resolver, r.seen_at])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite-out")]
+impl OutputWriter for SqliteWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        if self.to_stdout {
+            if r.answers.is_empty() { println!("{}\t{}", r.subdomain, r.status_tag()); } else { println!("{}\t{}", r.subdomain, r.answers.join(",")); }
+        }
+        let seen_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        let row = |rtype: String, data: String, ttl: Option<u32>| SqliteRow {
+            subdomain: r.subdomain.clone(), rtype, data, ttl, resolver: r.resolver.clone(), seen_at,
+        };
+        // 无细分记录时 (系统解析器回退) 按 answers 推断 A/AAAA
+        let rows: Vec<SqliteRow> = match &r.records {
+            Some(recs) => recs.iter().map(|x| row(x.rtype.clone(), x.data.clone(), x.ttl)).collect(),
+            None => r.answers.iter().map(|ip| row(if ip.contains(':') { "AAAA".into() } else { "A".into() }, ip.clone(), None)).collect(),
+        };
+        if rows.is_empty() { return Ok(()); }
+        let batch = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.extend(rows);
+            if pending.len() < SQLITE_BATCH { return Ok(()); }
+            std::mem::take(&mut *pending)
+        };
+        self.flush_rows(batch)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let rest = std::mem::take(&mut *self.pending.lock().unwrap());
+        self.flush_rows(rest)
+    }
+
+    fn close(&self) -> Result<()> { OutputWriter::flush(self) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn rec(rtype: &str, data: &str) -> ScanRecord { ScanRecord { rtype: rtype.into(), data: data.into(), ttl: None } }
+
+    struct Collect(Mutex<Vec<ScanResult>>);
+    impl OutputWriter for Arc<Collect> {
+        fn write(&self, r: &ScanResult) -> Result<()> { self.0.lock().unwrap().push(r.clone()); Ok(()) }
+    }
+
+    #[test]
+    fn dedup_writer_skips_identical_repeats() {
+        let sink = Arc::new(Collect(Mutex::new(Vec::new())));
+        let w = DedupWriter::new(vec![Box::new(sink.clone())]);
+        let hit = |ips: &[&str]| ScanResult {
+            subdomain: "api.example.com".into(),
+            answers: ips.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        w.write(&hit(&["1.1.1.1", "2.2.2.2"])).unwrap();
+        w.write(&hit(&["2.2.2.2", "1.1.1.1"])).unwrap();
+        assert_eq!(sink.0.lock().unwrap().len(), 1, "same answer set (any order) is written once");
+        w.write(&hit(&["3.3.3.3"])).unwrap();
+        assert_eq!(sink.0.lock().unwrap().len(), 2, "a changed answer set is surfaced again");
+    }
+
+    #[test]
+    fn sorting_writer_groups_siblings() {
+        let sink = Arc::new(Collect(Mutex::new(Vec::new())));
+        let w = SortingWriter::new(Box::new(sink.clone()));
+        for sub in ["www.b.com", "z.a.com", "api.b.com", "a.com", "dev.api.b.com"] {
+            w.write(&ScanResult { subdomain: sub.into(), ..Default::default() }).unwrap();
+        }
+        assert!(sink.0.lock().unwrap().is_empty(), "nothing is written before close");
+        w.close().unwrap();
+        let got: Vec<String> = sink.0.lock().unwrap().iter().map(|r| r.subdomain.clone()).collect();
+        assert_eq!(got, ["a.com", "z.a.com", "api.b.com", "dev.api.b.com", "www.b.com"]);
+    }
+
+    #[test]
+    fn queued_writer_drains_in_order_on_close() {
+        let sink = Arc::new(Collect(Mutex::new(Vec::new())));
+        let w = QueuedWriter::spawn(Box::new(sink.clone())).unwrap();
+        for i in 0..(OUTPUT_QUEUE * 2) {
+            w.write(&ScanResult { subdomain: format!("h{}.example.com", i), ..Default::default() }).unwrap();
+        }
+        w.close().unwrap();
+        let got = sink.0.lock().unwrap();
+        assert_eq!(got.len(), OUTPUT_QUEUE * 2, "close() returns only after the queue is drained");
+        assert!(got.iter().enumerate().all(|(i, r)| r.subdomain == format!("h{}.example.com", i)));
+        drop(got);
+        assert!(w.write(&ScanResult::default()).is_err(), "writes after close are rejected");
+        w.close().unwrap();
+    }
+
+    #[test]
+    fn jsonl_writer_requires_a_sink() {
+        assert!(build_writers(None, "jsonl", false, false, false, false, &FormatOptions::default()).is_err());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.jsonl");
+        let ws = build_writers(Some(path.clone()), "jsonl", true, false, false, false, &FormatOptions::default()).unwrap();
+        ws.write(&ScanResult { subdomain: "a.example.com".into(), answers: vec!["1.1.1.1".into()], ..Default::default() }).unwrap();
+        ws.close().unwrap();
+        let data = std::fs::read_to_string(path).unwrap();
+        assert_eq!(data, format!("{{\"_schema\":{},\"subdomain\":\"a.example.com\",\"answers\":[\"1.1.1.1\"],\"a_records\":[\"1.1.1.1\"]}}\n", SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn answers_split_by_address_family() {
+        let r = ScanResult { subdomain: "dual.example.com".into(), answers: vec!["192.0.2.1".into(), "2001:db8::1".into(), "192.0.2.2".into()], ..Default::default() };
+        assert_eq!(r.split_families(), (vec!["192.0.2.1", "192.0.2.2"], vec!["2001:db8::1"]));
+        let v: serde_json::Value = serde_json::from_str(&serde_json::to_string(&Versioned::new(&r)).unwrap()).unwrap();
+        assert_eq!(v["a_records"], serde_json::json!(["192.0.2.1", "192.0.2.2"]));
+        assert_eq!(v["aaaa_records"], serde_json::json!(["2001:db8::1"]));
+        assert_eq!(v["answers"].as_array().unwrap().len(), 3);
+        let ptr = ScanResult { subdomain: "192.0.2.1".into(), answers: vec!["host.example.com.".into()], ..Default::default() };
+        assert_eq!(ptr.split_families(), (vec![], vec![]));
+        let m = crate::metrics::Metrics::new();
+        m.count_families(&r);
+        assert_eq!((m.ipv4.load(Ordering::Relaxed), m.ipv6.load(Ordering::Relaxed)), (2, 1));
+    }
+
+    #[test]
+    fn file_output_is_buffered_until_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let hit = ScanResult { subdomain: "a.example.com".into(), answers: vec!["1.1.1.1".into()], ..Default::default() };
+        let ws = build_writers(Some(path.clone()), "txt", false, false, false, false, &FormatOptions::default()).unwrap();
+        ws.write(&hit).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "", "a line sits in the buffer until the next flush");
+        ws.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a.example.com\t1.1.1.1\n");
+        // --output-flush-ms 0
+        let w = LineFlushWriter::new(Box::new(build_writers(Some(path.clone()), "txt", false, false, false, false, &FormatOptions::default()).unwrap()));
+        w.write(&hit).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a.example.com\t1.1.1.1\n");
+    }
+
+    #[test]
+    fn append_dedup_skips_hosts_already_in_gzip_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.jsonl.gz");
+        let hit = |sub: &str, ips: &[&str]| ScanResult { subdomain: sub.into(), answers: ips.iter().map(|s| s.to_string()).collect(), ..Default::default() };
+        let first = build_writers(Some(path.clone()), "jsonl", false, true, false, false, &FormatOptions::default()).unwrap();
+        first.write(&hit("a.example.com", &["192.0.2.1"])).unwrap();
+        first.write(&hit("gone.example.com", &[])).unwrap();
+        first.close().unwrap();
+        drop(first);
+        let known = existing_subdomains(&path, "jsonl", true, None).unwrap();
+        assert_eq!(known, HashSet::from(["a.example.com".to_string()]), "hosts without answers are not known");
+        let second = DedupWriter::with_known(build_writers(Some(path.clone()), "jsonl", false, true, false, true, &FormatOptions::default()).unwrap(), known);
+        second.write(&hit("a.example.com", &["192.0.2.1"])).unwrap();
+        second.write(&hit("gone.example.com", &["192.0.2.9"])).unwrap();
+        second.close().unwrap();
+        drop(second);
+        let mut subs: Vec<String> = Vec::new();
+        let mut s = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::MultiGzDecoder::new(std::fs::File::open(&path).unwrap()), &mut s).unwrap();
+        for l in s.lines() { subs.push(serde_json::from_str::<serde_json::Value>(l).unwrap()["subdomain"].as_str().unwrap().to_string()); }
+        assert_eq!(subs, ["a.example.com", "gone.example.com", "gone.example.com"]);
+        assert_eq!(existing_subdomains(&dir.path().join("missing.txt"), "txt", false, None).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn missing_output_directory_is_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results/2026/scan.txt");
+        let ws = build_writers(Some(path.clone()), "txt", false, false, false, false, &FormatOptions::default()).unwrap();
+        ws.write(&ScanResult { subdomain: "a.example.com".into(), answers: vec!["192.0.2.1".into()], ..Default::default() }).unwrap();
+        ws.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a.example.com\t192.0.2.1\n");
+        // a regular file where the directory should be: the error names it
+        let blocked = dir.path().join("file");
+        std::fs::write(&blocked, "").unwrap();
+        let err = build_writers(Some(blocked.join("scan.txt")), "txt", false, false, false, false, &FormatOptions::default()).err().unwrap();
+        assert!(err.to_string().starts_with(&format!("cannot create output directory {}", blocked.display())), "{err}");
+    }
+
+    #[test]
+    fn label_writer_strips_root_and_dedups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labels.txt");
+        let fmt = FormatOptions { roots: vec!["example.com".into(), "dev.example.org".into()], ..Default::default() };
+        let w = build_writers(Some(path.clone()), "label", false, false, false, false, &fmt).unwrap();
+        let hit = |sub: &str| ScanResult { subdomain: sub.into(), answers: vec!["192.0.2.1".into()], ..Default::default() };
+        for sub in ["api.example.com", "dev.api.Example.com.", "www.dev.example.org", "www.example.com", "API.example.com", "example.com", "host.other.net"] {
+            w.write(&hit(sub)).unwrap();
+        }
+        w.write(&ScanResult { subdomain: "miss.example.com".into(), ..Default::default() }).unwrap();
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "api\ndev.api\nwww\nhost\n");
+    }
+
+    #[test]
+    fn table_aligns_columns_and_wraps_ips() {
+        let rows = vec![
+            ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], records: Some(vec![rec("CNAME", "edge.example.net."), rec("A", "192.0.2.1")]), ..Default::default() },
+            ScanResult { subdomain: "api.example.com".into(), answers: (1..=4).map(|i| format!("198.51.100.{}", i)).collect(), records: Some((1..=4).map(|i| rec("A", &format!("198.51.100.{}", i))).collect()), ..Default::default() },
+            ScanResult { subdomain: "gone.example.com".into(), ..Default::default() },
+        ];
+        let out = render_table(&rows, 60, false);
+        let want = "\
+SUBDOMAIN         IPS                                TYPES
+------------------------------------------------------------
+www.example.com   192.0.2.1                          CNAME,A
+api.example.com   198.51.100.1, 198.51.100.2,        A
+                  198.51.100.3, 198.51.100.4
+gone.example.com  -                                  -
+";
+        assert_eq!(out, want);
+        assert!(!out.contains('\x1b'));
+        assert!(render_table(&rows, 60, true).contains("\x1b[36mwww.example.com\x1b[0m"));
+    }
+
+    #[test]
+    fn split_writer_routes_by_rtype() {
+        let dir = tempfile::tempdir().unwrap();
+        let w = SplitWriter::new(dir.path().join("split"), false, false).unwrap();
+        w.write(&ScanResult {
+            subdomain: "www.example.com".into(),
+            answers: vec!["1.2.3.4".into()],
+            records: Some(vec![rec("CNAME", "edge.example.net."), rec("A", "1.2.3.4")]),
+            ..Default::default()
+        }).unwrap();
+        w.write(&ScanResult {
+            subdomain: "v6.example.com".into(),
+            answers: vec!["::1".into()],
+            records: Some(vec![rec("AAAA", "::1")]),
+            ..Default::default()
+        }).unwrap();
+        w.close().unwrap();
+        let read = |n: &str| std::fs::read_to_string(dir.path().join("split").join(n)).unwrap();
+        assert_eq!(read("a.txt"), "www.example.com\t1.2.3.4\n");
+        assert_eq!(read("cname.txt"), "www.example.com\tedge.example.net.\n");
+        assert_eq!(read("aaaa.txt"), "v6.example.com\t::1\n");
+        assert!(!dir.path().join("split").join("txt.txt").exists());
+    }
+
+    #[cfg(feature = "sqlite-out")]
+    #[test]
+    fn sqlite_writer_batches_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.db");
+        let w = build_writers(Some(path.clone()), "sqlite", false, false, false, false, &FormatOptions { detail: true, ..Default::default() }).unwrap();
+        for i in 0..(SQLITE_BATCH + 5) {
+            w.write(&ScanResult {
+                subdomain: format!("h{}.example.com", i),
+                answers: vec!["192.0.2.1".into()],
+                records: Some(vec![rec("CNAME", "edge.example.net."), ScanRecord { rtype: "A".into(), data: "192.0.2.1".into(), ttl: Some(300) }]),
+                ..Default::default()
+            }).unwrap();
+        }
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let count = |c: &rusqlite::Connection| c.query_row("SELECT COUNT(*) FROM results", [], |r| r.get::<_, i64>(0)).unwrap();
+        // first full batch is committed before close, the remainder on close
+        assert_eq!(count(&conn), 2 * SQLITE_BATCH as i64);
+        w.close().unwrap();
+        assert_eq!(count(&conn), 2 * (SQLITE_BATCH as i64 + 5));
+        let ttl: Option<u32> = conn.query_row("SELECT ttl FROM results WHERE subdomain = 'h3.example.com' AND rtype = 'A'", [], |r| r.get(0)).unwrap();
+        assert_eq!(ttl, Some(300));
+    }
+
+    #[test]
+    fn partial_sidecar_keeps_results_until_clean_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("scan.txt");
+        let side = partial_path(&out);
+        assert_eq!(side, dir.path().join("scan.txt.partial.jsonl"));
+        let hit = |s: &str| ScanResult { subdomain: s.into(), answers: vec!["192.0.2.1".into()], ..Default::default() };
+        let sorted = SortingWriter::new(Box::new(build_writers(Some(out.clone()), "txt", false, false, false, false, &FormatOptions::default()).unwrap()));
+        let w: Vec<Box<dyn OutputWriter>> = vec![Box::new(sorted), Box::new(PartialWriter::new(side.clone(), std::time::Duration::ZERO).unwrap())];
+        for s in ["www.example.com", "api.example.com", "www.example.com"] { w.write(&hit(s)).unwrap(); }
+        // the sorted file is still empty, the sidecar already has both hosts once
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "");
+        assert_eq!(std::fs::read_to_string(&side).unwrap().lines().count(), 2);
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap().lines().collect::<Vec<_>>(), ["api.example.com\t192.0.2.1", "www.example.com\t192.0.2.1", "www.example.com\t192.0.2.1"]);
+        assert!(!side.exists(), "removed once the final output is complete");
+    }
+
+    #[test]
+    fn answer_cap_keeps_first_n_and_marks_rest() {
+        let ips: Vec<String> = (1..=5).map(|i| format!("198.51.100.{}", i)).collect();
+        let mut records = vec![rec("CNAME", "edge.example.net.")];
+        records.extend(ips.iter().map(|ip| rec("A", ip)));
+        let r = ScanResult { subdomain: "cdn.example.com".into(), answers: ips, records: Some(records), ..Default::default() };
+        let c = AnswerCapWriter::cap(&r, 2).unwrap();
+        assert_eq!(c.answers, vec!["198.51.100.1", "198.51.100.2"]);
+        assert_eq!(c.truncated, Some(3));
+        let kept: Vec<&str> = c.records.as_ref().unwrap().iter().map(|x| x.data.as_str()).collect();
+        assert_eq!(kept, vec!["edge.example.net.", "198.51.100.1", "198.51.100.2"]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let w = AnswerCapWriter::new(Box::new(PlainWriter::new(Some(path.clone()), false, false, false, false, false, false).unwrap()), 2);
+        w.write(&r).unwrap();
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "cdn.example.com\t198.51.100.1,198.51.100.2,...+3 more\n");
+        assert!(AnswerCapWriter::cap(&r, 5).is_none(), "at or under the cap is passed through");
+    }
+
+    #[test]
+    fn negative_results_carry_rcode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let w = PlainWriter::new(Some(path.clone()), false, false, false, false, false, false).unwrap();
+        w.write(&ScanResult { subdomain: "gone.example.com".into(), rcode: Some("NXDOMAIN".into()), ..Default::default() }).unwrap();
+        w.write(&ScanResult { subdomain: "miss.example.com".into(), ..Default::default() }).unwrap();
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "gone.example.com\t[NXDOMAIN]\nmiss.example.com\t[no-result]\n");
+        assert!(existing_subdomains(&path, "txt", false, None).unwrap().is_empty());
+        let v = serde_json::to_value(ScanResult { subdomain: "gone.example.com".into(), rcode: Some("SERVFAIL".into()), ..Default::default() }).unwrap();
+        assert_eq!(v["rcode"], "SERVFAIL");
+    }
+
+    #[test]
+    fn csv_timing_columns_are_on_every_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        let w = build_writers(Some(path.clone()), "csv", false, false, false, false, &FormatOptions { timings: true, ..Default::default() }).unwrap();
+        w.write(&ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], resolver: Some("1.1.1.1".into()), latency_ms: Some(12), ..Default::default() }).unwrap();
+        // local source (hosts file) and failed hosts have no timing
+        w.write(&ScanResult { subdomain: "intranet.example.com".into(), answers: vec!["10.0.0.1".into()], ..Default::default() }).unwrap();
+        w.write(&ScanResult { subdomain: "miss.example.com".into(), ..Default::default() }).unwrap();
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "www.example.com;192.0.2.1;1.1.1.1;12\nintranet.example.com;10.0.0.1;;\nmiss.example.com;;;\n");
+        // without --timings the columns are left out, even if a result happens to carry them
+        let w = build_writers(Some(path.clone()), "csv", false, false, false, false, &FormatOptions::default()).unwrap();
+        w.write(&ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], resolver: Some("1.1.1.1".into()), ..Default::default() }).unwrap();
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "www.example.com;192.0.2.1\n");
+    }
+
+    #[test]
+    fn no_result_marker_and_skip_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let hit = ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], ..Default::default() };
+        let miss = ScanResult { subdomain: "miss.example.com".into(), ..Default::default() };
+        let gone = ScanResult { subdomain: "gone.example.com".into(), rcode: Some("NXDOMAIN".into()), ..Default::default() };
+        let fmt = FormatOptions { no_result_marker: Some("-".into()), ..Default::default() };
+        let w = build_writers(Some(path.clone()), "txt", false, false, false, false, &fmt).unwrap();
+        for r in [&hit, &miss, &gone] { w.write(r).unwrap(); }
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "www.example.com\t192.0.2.1\nmiss.example.com\t-\ngone.example.com\t[NXDOMAIN]\n");
+        let known = existing_subdomains(&path, "txt", false, Some("-")).unwrap();
+        assert_eq!(known.into_iter().collect::<Vec<_>>(), ["www.example.com"]);
+        let w = build_writers(Some(path.clone()), "txt", false, false, false, false, &FormatOptions { skip_empty: true, ..Default::default() }).unwrap();
+        for r in [&hit, &miss, &gone] { w.write(r).unwrap(); }
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "www.example.com\t192.0.2.1\n");
+    }
+
+    #[test]
+    fn output_hash_covers_uncompressed_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let hit = ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], ..Default::default() };
+        let mut digests = Vec::new();
+        for (name, gzip) in [("out.txt", false), ("out.txt.gz", true)] {
+            let path = dir.path().join(name);
+            let digest = OutputDigest::default();
+            let fmt = FormatOptions { digest: Some(digest.clone()), ..Default::default() };
+            let inner = build_writers(Some(path.clone()), "txt", false, gzip, false, false, &fmt).unwrap();
+            let w = HashWriter::new(Box::new(QueuedWriter::spawn(Box::new(inner)).unwrap()), path.clone(), digest);
+            w.write(&hit).unwrap();
+            w.close().unwrap();
+            let side = std::fs::read_to_string(hash_path(&path)).unwrap();
+            assert_eq!(side.split_whitespace().nth(1), Some(name));
+            digests.push(side.split_whitespace().next().unwrap().to_string());
+        }
+        assert_eq!(digests[0], crate::manifest::sha256_hex(b"www.example.com\t192.0.2.1\n"));
+        assert_eq!(digests[0], digests[1], "gzip does not change the hash");
+    }
+
+    #[test]
+    fn output_hash_under_append_covers_this_run_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        std::fs::write(&path, "old.example.com\t192.0.2.9\n").unwrap();
+        let digest = OutputDigest::default();
+        let fmt = FormatOptions { digest: Some(digest.clone()), ..Default::default() };
+        let inner = build_writers(Some(path.clone()), "txt", false, false, false, true, &fmt).unwrap();
+        let w = HashWriter::new(Box::new(inner), path.clone(), digest);
+        w.write(&ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], ..Default::default() }).unwrap();
+        w.close().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old.example.com\t192.0.2.9\nwww.example.com\t192.0.2.1\n");
+        let side = std::fs::read_to_string(hash_path(&path)).unwrap();
+        assert_eq!(side, format!("{}  out.txt\n", crate::manifest::sha256_hex(b"www.example.com\t192.0.2.1\n")));
+    }
+
+    #[test]
+    fn vec_writer_collects_behind_the_output_chain() {
+        let sink = VecWriter::default();
+        let results = sink.results();
+        let w = QueuedWriter::spawn(Box::new(DedupWriter::new(vec![Box::new(sink)]))).unwrap();
+        for ip in ["192.0.2.1", "192.0.2.1", "192.0.2.2"] {
+            w.write(&ScanResult { subdomain: "www.example.com".into(), answers: vec![ip.into()], ..Default::default() }).unwrap();
+        }
+        w.close().unwrap();
+        let got: Vec<Vec<String>> = results.lock().unwrap().iter().map(|r| r.answers.clone()).collect();
+        assert_eq!(got, [["192.0.2.1"], ["192.0.2.2"]]);
+    }
+
+    #[test]
+    fn tree_writer_annotates_parent_and_depth() {
+        let tree = Arc::new(DiscoveryTree::new(&["example.com".into(), "Dev.Example.com.".into()]));
+        assert_eq!(tree.position("www.example.com"), Some(("example.com".into(), 1)));
+        assert_eq!(tree.position("API.dev.example.com"), Some(("dev.example.com".into(), 2)));
+        assert_eq!(tree.position("x.api.dev.example.com"), Some(("dev.example.com".into(), 3)));
+        assert_eq!(tree.position("example.com"), None, "a root is not below itself");
+        assert_eq!(tree.position("www.example.org"), None);
+        assert_eq!(tree.level("www.example.org"), 0);
+        let sink = Arc::new(Collect(Mutex::new(Vec::new())));
+        let w = TreeWriter::new(tree, Box::new(sink.clone()));
+        w.write(&ScanResult { subdomain: "api.dev.example.com".into(), ..Default::default() }).unwrap();
+        w.write(&ScanResult { subdomain: "192.0.2.1".into(), ..Default::default() }).unwrap();
+        let got = sink.0.lock().unwrap();
+        assert_eq!((got[0].parent.as_deref(), got[0].depth), (Some("dev.example.com"), Some(2)));
+        assert_eq!((got[1].parent.as_deref(), got[1].depth), (None, None));
+    }
+}
@@ -0,0 +1,76 @@
+//! Optional TCP liveness probe for discovered hosts (`--probe-ports`).
+//! Connects are paced by their own token bucket so they never eat into the DNS rate budget.
+
+use anyhow::{bail, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::time::{timeout, Duration};
+
+/// 单次 TCP connect 超时
+pub const CONNECT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Parse `80,443,8000-8010` into a sorted, deduped port list.
+pub fn parse_ports(spec: &str) -> Result<Vec<u16>> {
+    let mut out = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((a, b)) => {
+                let (a, b): (u16, u16) = (a.trim().parse()?, b.trim().parse()?);
+                if a == 0 || a > b { bail!("invalid port range '{}'", part); }
+                out.extend(a..=b);
+            }
+            None => {
+                let p: u16 = part.parse()?;
+                if p == 0 { bail!("invalid port '0'"); }
+                out.push(p);
+            }
+        }
+    }
+    out.sort(); out.dedup();
+    Ok(out)
+}
+
+/// Ports (from `ports`) accepting a TCP connection on at least one of `ips`.
+/// Each connect attempt takes one token from `rate`.
+pub async fn open_ports(ips: &[String], ports: &[u16], rate: &Arc<Semaphore>) -> Vec<u16> {
+    let addrs: Vec<IpAddr> = ips.iter().filter_map(|s| s.parse().ok()).collect();
+    let checks = ports.iter().map(|&port| {
+        let addrs = addrs.clone();
+        let rate = rate.clone();
+        async move {
+            for ip in addrs {
+                // 令牌消费后不归还，由 refill 按速率补充
+                rate.acquire().await.unwrap().forget();
+                if let Ok(Ok(_)) = timeout(CONNECT_TIMEOUT, TcpStream::connect(SocketAddr::new(ip, port))).await {
+                    return Some(port);
+                }
+            }
+            None
+        }
+    });
+    futures::future::join_all(checks).await.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_port_lists_and_ranges() {
+        assert_eq!(parse_ports("443, 80,8000-8002,80").unwrap(), [80, 443, 8000, 8001, 8002]);
+        assert!(parse_ports("0").is_err());
+        assert!(parse_ports("90-80").is_err());
+        assert!(parse_ports("http").is_err());
+    }
+
+    #[tokio::test]
+    async fn open_ports_finds_listener() {
+        let l = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = l.local_addr().unwrap().port();
+        let rl = crate::ratelimit::RateLimiter::new(100);
+        rl.spawn_refill();
+        let got = open_ports(&["127.0.0.1".into()], &[open], &rl.handle()).await;
+        assert_eq!(got, [open]);
+    }
+}
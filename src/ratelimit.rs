@@ -1,19 +1,25 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicI64, Ordering};
 use tokio::sync::Semaphore;
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, Duration, MissedTickBehavior};
 
-/// 简单令牌桶: 每秒补充 `rate` 令牌，容量为 `rate`，消费时 acquire 一个 permit。
+/// 每秒补充次数：令牌按 100ms 分批补充，发包均匀分布而不是每秒开头一次性涌出
+const TICKS_PER_SEC: i64 = 10;
+
+/// 令牌桶: 每 100ms 补充 `rate / 10` 令牌 (小数部分累积到下一拍)，消费时 acquire 一个 permit
+/// 并 `forget()`：令牌用掉即消失，归还会让桶退化成只限制在途数量。
+/// 容量为 burst；burst 为 0 时取一拍的量，即最平滑的发包节奏。
 #[derive(Clone)]
 pub struct RateLimiter {
     sem: Arc<Semaphore>,
     rate: Arc<AtomicI64>,
+    burst: Arc<AtomicI64>,
 }
 
 impl RateLimiter {
     pub fn new(rate: i64) -> Self {
         let sem = Arc::new(Semaphore::new(0)); // 初始为空，避免冷启动瞬间洪水
-        Self { sem, rate: Arc::new(AtomicI64::new(rate.max(0))) }
+        Self { sem, rate: Arc::new(AtomicI64::new(rate.max(0))), burst: Arc::new(AtomicI64::new(0)) }
     }
 
     pub fn handle(&self) -> Arc<Semaphore> { self.sem.clone() }
@@ -21,14 +27,23 @@ impl RateLimiter {
     pub fn spawn_refill(&self) {
         let sem = self.sem.clone();
         let rate = self.rate.clone();
+        let burst = self.burst.clone();
         tokio::spawn(async move {
-            let mut tick = interval(Duration::from_secs(1));
+            let mut tick = interval(Duration::from_millis(1000 / TICKS_PER_SEC as u64));
+            // 调度延迟后不补拍，否则会一次补出多拍令牌，等于突发
+            tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // rate 不足 10 的部分跨拍累积，低速率下也按配置速率发放
+            let mut carry = 0i64;
             loop {
                 tick.tick().await;
-                // 补充，不超过容量 (rate)
                 let rate = rate.load(Ordering::Relaxed);
+                carry += rate;
+                let due = carry / TICKS_PER_SEC;
+                carry %= TICKS_PER_SEC;
+                // 补充，不超过容量 (burst)
+                let cap = capacity(rate, burst.load(Ordering::Relaxed));
                 let available = sem.available_permits() as i64;
-                let to_add = (rate - available).max(0);
+                let to_add = due.min(cap - available).max(0);
                 if to_add > 0 {
                     sem.add_permits(to_add as usize);
                 }
@@ -42,4 +57,143 @@ impl RateLimiter {
     }
 
     pub fn get_rate(&self) -> i64 { self.rate.load(Ordering::Relaxed) }
+
+    /// 桶容量上限：空闲后最多一次放出多少查询 (0 = 一拍的量)
+    pub fn set_burst(&self, burst: i64) {
+        self.burst.store(burst.max(0), Ordering::Relaxed);
+    }
+}
+
+/// 自适应速率的默认下限 (pps)：错误率持续偏高时最多降到这里，不会把扫描拖到停滞
+pub const DEFAULT_ADAPTIVE_MIN_RATE: i64 = 50;
+/// 一个调整窗口内至少这么多查询才调整；下限速率附近也要能采到样本，否则无法回升
+pub const ADAPTIVE_MIN_SAMPLE: u64 = 20;
+
+/// What [`AdaptiveRate::step`] reports besides the new rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloorEvent {
+    /// a cut was capped at the floor; errors are persistently above the threshold
+    Hit,
+    /// errors subsided and the rate moved up off the floor
+    Left,
+}
+
+/// Error-driven rate controller: cut by `dec` while the window's error rate exceeds the
+/// threshold, grow by `inc` otherwise, always within `[min, max]`.
+#[derive(Debug, Clone)]
+pub struct AdaptiveRate {
+    min: i64,
+    max: i64,
+    err_threshold: f64,
+    dec: f64,
+    inc: f64,
+    at_floor: bool,
+}
+
+impl AdaptiveRate {
+    /// `min <= 0` means [`DEFAULT_ADAPTIVE_MIN_RATE`]; the floor never exceeds `max`. `inc` is at
+    /// least 1.1 so the rate always climbs back once errors subside.
+    pub fn new(min: i64, max: i64, err_threshold: f64, dec: f64, inc: f64) -> Self {
+        let max = max.max(1);
+        let min = if min > 0 { min } else { DEFAULT_ADAPTIVE_MIN_RATE }.min(max);
+        Self { min, max, err_threshold: err_threshold.max(0.01), dec: dec.clamp(0.1, 0.99), inc: inc.clamp(1.1, 1.5), at_floor: false }
+    }
+
+    pub fn min(&self) -> i64 { self.min }
+
+    /// Next rate for one window's error rate; the event fires on entering / leaving the floor.
+    pub fn step(&mut self, current: i64, err_rate: f64) -> (i64, Option<FloorEvent>) {
+        let next = if err_rate > self.err_threshold {
+            (current as f64 * self.dec).floor() as i64
+        } else {
+            (current as f64 * self.inc).ceil() as i64
+        }.clamp(self.min, self.max);
+        let at_floor = next == self.min && err_rate > self.err_threshold;
+        let event = match (self.at_floor, at_floor) {
+            (false, true) => Some(FloorEvent::Hit),
+            (true, false) if next > self.min => Some(FloorEvent::Left),
+            _ => None,
+        };
+        if event.is_some() { self.at_floor = at_floor; }
+        (next, event)
+    }
+}
+
+/// Bucket size: the configured burst, or one tick's worth of tokens when unset.
+fn capacity(rate: i64, burst: i64) -> i64 {
+    if burst > 0 { burst } else { (rate / TICKS_PER_SEC).max(1) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokens taken within `window`, as fast as they are handed out.
+    async fn drain_for(rl: &RateLimiter, window: Duration) -> usize {
+        let sem = rl.handle();
+        let end = tokio::time::Instant::now() + window;
+        let mut n = 0;
+        while let Ok(Ok(p)) = tokio::time::timeout_at(end, sem.acquire()).await {
+            p.forget();
+            n += 1;
+        }
+        n
+    }
+
+    // paused clock: refill ticks fire at exact 100ms steps, independent of machine load
+    #[tokio::test(start_paused = true)]
+    async fn refill_is_spread_across_the_second() {
+        let rl = RateLimiter::new(100);
+        rl.spawn_refill();
+        // a once-per-second refill would hand out all 100 tokens up front;
+        // ticks at 0, 100, 200, 300ms give 10 each
+        let early = drain_for(&rl, Duration::from_millis(350)).await;
+        assert_eq!(early, 40, "got {} tokens in 350ms", early);
+        let rest = drain_for(&rl, Duration::from_millis(700)).await;
+        assert_eq!(early + rest, 110, "got {} tokens in 1050ms", early + rest);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_caps_tokens_saved_while_idle() {
+        let smooth = RateLimiter::new(1000);
+        let bursty = RateLimiter::new(1000);
+        bursty.set_burst(500);
+        smooth.spawn_refill();
+        bursty.spawn_refill();
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert_eq!(smooth.handle().available_permits(), 100, "default: one tick's worth");
+        assert_eq!(bursty.handle().available_permits(), 500);
+    }
+
+    #[test]
+    fn adaptive_rate_stops_at_floor_and_recovers() {
+        // the old default: min 0, inc 1.0 could collapse to nothing and never come back
+        let mut a = AdaptiveRate::new(0, 1000, 0.1, 0.5, 1.0);
+        assert_eq!(a.min(), DEFAULT_ADAPTIVE_MIN_RATE);
+        let mut rate = 1000;
+        let mut events = Vec::new();
+        for _ in 0..10 {
+            let (next, ev) = a.step(rate, 0.9);
+            rate = next;
+            events.extend(ev);
+        }
+        assert_eq!(rate, DEFAULT_ADAPTIVE_MIN_RATE);
+        assert_eq!(events, [FloorEvent::Hit], "logged once, not every window");
+        let (next, ev) = a.step(rate, 0.0);
+        assert!(next > rate);
+        assert_eq!(ev, Some(FloorEvent::Left));
+        rate = next;
+        for _ in 0..40 { rate = a.step(rate, 0.0).0; }
+        assert_eq!(rate, 1000, "capped at max");
+        // a floor above a tiny max is lowered to it
+        assert_eq!(AdaptiveRate::new(0, 20, 0.1, 0.5, 1.2).min(), 20);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rates_below_ticks_per_second_carry_over() {
+        let rl = RateLimiter::new(5);
+        rl.spawn_refill();
+        let n = drain_for(&rl, Duration::from_millis(1050)).await;
+        assert_eq!(n, 5, "got {} tokens in 1050ms at 5/s", n);
+    }
 }
@@ -0,0 +1,192 @@
+//! `--raw` (Linux, CAP_NET_RAW)：查询帧 (IPv4 + UDP + DNS) 由一个原始套接字直接发出，
+//! 不再为每个查询创建 / 绑定 / 关闭 UDP socket。
+//!
+//! 所有查询共用同一个源端口：一个普通 UDP socket 占住该端口，内核把应答直接投递给它，
+//! 接收线程按 (DNS id, 解析器地址) 把应答分发给等待中的查询，本身不保存任何状态。
+
+use anyhow::{Context, Result};
+use pnet_packet::ip::IpNextHeaderProtocols;
+use pnet_packet::ipv4::{self, Ipv4Flags, MutableIpv4Packet};
+use pnet_packet::udp::{self, MutableUdpPacket};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+/// 接收 socket 的内核缓冲区：高 pps 下默认值 (~200KB) 会丢应答
+const RECV_SOCKET_BUFFER: libc::c_int = 8 << 20;
+
+type Pending = Mutex<HashMap<(u16, SocketAddrV4), mpsc::SyncSender<Vec<u8>>>>;
+
+pub struct RawTransport {
+    fd: OwnedFd,
+    port: u16,
+    source: Option<Ipv4Addr>,
+    /// resolver -> 路由选出的本地源地址 (未指定 --source-ip 时)
+    routes: Mutex<HashMap<Ipv4Addr, Ipv4Addr>>,
+    pending: Arc<Pending>,
+    ip_id: AtomicU16,
+}
+
+impl RawTransport {
+    /// Open the raw sender and the shared receive port. Fails without CAP_NET_RAW.
+    pub fn open(source: Option<Ipv4Addr>) -> Result<Arc<Self>> {
+        // IPPROTO_RAW implies IP_HDRINCL: we write the IPv4 header ourselves
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_RAW) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("cannot open raw socket (needs CAP_NET_RAW)");
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let recv = UdpSocket::bind(SocketAddrV4::new(source.unwrap_or(Ipv4Addr::UNSPECIFIED), 0))
+            .context("cannot bind raw-mode receive port")?;
+        set_recv_buffer(&recv, RECV_SOCKET_BUFFER);
+        let port = recv.local_addr()?.port();
+        let t = Arc::new(Self { fd, port, source, routes: Mutex::new(HashMap::new()), pending: Arc::new(Mutex::new(HashMap::new())), ip_id: AtomicU16::new(std::process::id() as u16) });
+        let pending = Arc::downgrade(&t.pending);
+        std::thread::Builder::new().name("rusub-raw-recv".into()).spawn(move || receive_loop(recv, pending))?;
+        Ok(t)
+    }
+
+    pub fn port(&self) -> u16 { self.port }
+
+    /// Send every packet to `server` and wait until `timeout` for the replies, in order.
+    /// DNS ids already waiting on this resolver are bumped so replies cannot be mixed up.
+    pub fn exchange(&self, packets: Vec<Vec<u8>>, server: SocketAddrV4, timeout: Duration) -> Result<Vec<Option<Vec<u8>>>> {
+        let deadline = Instant::now() + timeout;
+        let mut waits = Vec::with_capacity(packets.len());
+        for mut pkt in packets {
+            if pkt.len() < 2 { continue; }
+            let (tx, rx) = mpsc::sync_channel(1);
+            let mut id = u16::from_be_bytes([pkt[0], pkt[1]]);
+            {
+                let mut pending = self.pending.lock().unwrap();
+                while pending.contains_key(&(id, server)) { id = id.wrapping_add(1); }
+                pending.insert((id, server), tx);
+            }
+            pkt[..2].copy_from_slice(&id.to_be_bytes());
+            waits.push((id, rx));
+            if let Err(e) = self.send(server, &pkt) {
+                self.forget(&waits, server);
+                return Err(e.into());
+            }
+        }
+        let replies = waits.iter().map(|(_, rx)| rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()).collect();
+        self.forget(&waits, server);
+        Ok(replies)
+    }
+
+    fn forget(&self, waits: &[(u16, mpsc::Receiver<Vec<u8>>)], server: SocketAddrV4) {
+        let mut pending = self.pending.lock().unwrap();
+        for (id, _) in waits { pending.remove(&(*id, server)); }
+    }
+
+    fn send(&self, dst: SocketAddrV4, payload: &[u8]) -> io::Result<()> {
+        let src = self.source_for(*dst.ip())?;
+        let frame = build_frame(SocketAddrV4::new(src, self.port), dst, payload, self.ip_id.fetch_add(1, Ordering::Relaxed));
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: dst.port().to_be(),
+            sin_addr: libc::in_addr { s_addr: u32::from(*dst.ip()).to_be() },
+            sin_zero: [0; 8],
+        };
+        let n = unsafe {
+            libc::sendto(self.fd.as_raw_fd(), frame.as_ptr() as *const libc::c_void, frame.len(), 0,
+                &addr as *const libc::sockaddr_in as *const libc::sockaddr, std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t)
+        };
+        if n < 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+    }
+
+    /// `--source-ip`, else the address the kernel would pick for `dst` (cached per resolver).
+    fn source_for(&self, dst: Ipv4Addr) -> io::Result<Ipv4Addr> {
+        if let Some(s) = self.source { return Ok(s); }
+        if let Some(s) = self.routes.lock().unwrap().get(&dst) { return Ok(*s); }
+        let probe = UdpSocket::bind("0.0.0.0:0")?;
+        probe.connect(SocketAddrV4::new(dst, 53))?;
+        let SocketAddr::V4(local) = probe.local_addr()? else {
+            return Err(io::Error::new(io::ErrorKind::AddrNotAvailable, "no IPv4 route"));
+        };
+        self.routes.lock().unwrap().insert(dst, *local.ip());
+        Ok(*local.ip())
+    }
+}
+
+/// 应答分发：按 (id, 来源) 找到等待的查询；没有对应查询的包 (迟到 / 伪造) 直接丢弃。
+/// 传输对象释放后线程退出，接收端口随之关闭。
+fn receive_loop(sock: UdpSocket, pending: Weak<Pending>) {
+    let _ = sock.set_read_timeout(Some(Duration::from_millis(200)));
+    let mut buf = vec![0u8; 65535];
+    loop {
+        match sock.recv_from(&mut buf) {
+            Ok((n, SocketAddr::V4(from))) if n >= 2 => {
+                let Some(pending) = pending.upgrade() else { return };
+                let id = u16::from_be_bytes([buf[0], buf[1]]);
+                let waiter = pending.lock().unwrap().remove(&(id, from));
+                if let Some(tx) = waiter { let _ = tx.try_send(buf[..n].to_vec()); }
+            }
+            Ok(_) => {}
+            Err(_) => if pending.strong_count() == 0 { return },
+        }
+    }
+}
+
+fn set_recv_buffer(sock: &UdpSocket, bytes: libc::c_int) {
+    // best effort: capped by net.core.rmem_max
+    unsafe {
+        libc::setsockopt(sock.as_raw_fd(), libc::SOL_SOCKET, libc::SO_RCVBUF,
+            &bytes as *const libc::c_int as *const libc::c_void, std::mem::size_of::<libc::c_int>() as libc::socklen_t);
+    }
+}
+
+/// IPv4 + UDP + payload with both checksums filled in.
+pub fn build_frame(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8], ip_id: u16) -> Vec<u8> {
+    let udp_len = UDP_HEADER_LEN + payload.len();
+    let mut frame = vec![0u8; IPV4_HEADER_LEN + udp_len];
+    {
+        let mut udp = MutableUdpPacket::new(&mut frame[IPV4_HEADER_LEN..]).expect("udp buffer");
+        udp.set_source(src.port());
+        udp.set_destination(dst.port());
+        udp.set_length(udp_len as u16);
+        udp.set_payload(payload);
+        let sum = udp::ipv4_checksum(&udp.to_immutable(), src.ip(), dst.ip());
+        udp.set_checksum(sum);
+    }
+    let mut ip = MutableIpv4Packet::new(&mut frame).expect("ipv4 buffer");
+    ip.set_version(4);
+    ip.set_header_length((IPV4_HEADER_LEN / 4) as u8);
+    ip.set_total_length((IPV4_HEADER_LEN + udp_len) as u16);
+    ip.set_identification(ip_id);
+    ip.set_flags(Ipv4Flags::DontFragment);
+    ip.set_ttl(64);
+    ip.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+    ip.set_source(*src.ip());
+    ip.set_destination(*dst.ip());
+    let sum = ipv4::checksum(&ip.to_immutable());
+    ip.set_checksum(sum);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet_packet::ipv4::Ipv4Packet;
+    use pnet_packet::udp::UdpPacket;
+    use pnet_packet::Packet;
+
+    #[test]
+    fn frame_headers_and_checksums() {
+        let src = SocketAddrV4::new(Ipv4Addr::new(10, 0, 8, 2), 40000);
+        let dst = SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 53), 53);
+        let frame = build_frame(src, dst, b"\x12\x34dns", 7);
+        let ip = Ipv4Packet::new(&frame).unwrap();
+        assert_eq!((ip.get_source(), ip.get_destination(), ip.get_total_length()), (*src.ip(), *dst.ip(), 33));
+        assert_eq!(ip.get_checksum(), ipv4::checksum(&ip));
+        let u = UdpPacket::new(ip.payload()).unwrap();
+        assert_eq!((u.get_source(), u.get_destination(), u.payload()), (40000, 53, &b"\x12\x34dns"[..]));
+        assert_eq!(u.get_checksum(), udp::ipv4_checksum(&u, src.ip(), dst.ip()));
+    }
+}
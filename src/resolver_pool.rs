@@ -4,30 +4,68 @@ use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use serde::Serialize;
+use crate::rng::{shared_rng, SharedRng, STREAM_RESOLVER};
+use tracing::warn;
+
+pub const DEFAULT_COOLDOWN_SECS: u64 = 60;
+
+/// Thresholds for the health-check disable heuristic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisablePolicy {
+    /// outcomes needed before the fail ratio is trusted
+    pub min_total: u64,
+    /// disable once fail / total exceeds this
+    pub fail_ratio: f64,
+}
+
+impl DisablePolicy {
+    pub const DEFAULT_MIN_TOTAL: u64 = 20;
+    pub const DEFAULT_FAIL_RATIO: f64 = 0.8;
+}
+
+impl Default for DisablePolicy {
+    fn default() -> Self {
+        Self { min_total: Self::DEFAULT_MIN_TOTAL, fail_ratio: Self::DEFAULT_FAIL_RATIO }
+    }
+}
+
+/// clap value parser for `--resolver-fail-ratio`: a ratio in (0, 1].
+pub fn parse_fail_ratio(s: &str) -> Result<f64, String> {
+    let v: f64 = s.parse().map_err(|_| format!("invalid ratio: {}", s))?;
+    if v > 0.0 && v <= 1.0 { Ok(v) } else { Err(format!("ratio must be in (0, 1]: {}", s)) }
+}
 
 struct ResolverInner {
     addr: String,
+    /// selection bias for `choose_weighted` (1.0 = neutral, 0 = only when nothing else is left)
+    weight: f64,
     ok: AtomicU64,
     fail: AtomicU64,
     disabled: AtomicBool,
     disabled_at: Mutex<Option<Instant>>,
+    /// how many times health checks have tripped for this resolver
+    disable_count: AtomicU64,
 }
 
 impl ResolverInner {
     fn new(addr: String) -> Self {
-        Self { addr, ok: AtomicU64::new(0), fail: AtomicU64::new(0), disabled: AtomicBool::new(false), disabled_at: Mutex::new(None) }
+        Self::with_weight(addr, 1.0)
     }
 
-    fn should_disable(&self) -> bool {
+    fn with_weight(addr: String, weight: f64) -> Self {
+        Self { addr, weight, ok: AtomicU64::new(0), fail: AtomicU64::new(0), disabled: AtomicBool::new(false), disabled_at: Mutex::new(None), disable_count: AtomicU64::new(0) }
+    }
+
+    fn should_disable(&self, policy: &DisablePolicy) -> bool {
         let ok = self.ok.load(Ordering::Relaxed);
         let fail = self.fail.load(Ordering::Relaxed);
         // Simple heuristic:
-        // - if total >= 20 and fail ratio > 0.8
+        // - if total >= min_total and fail ratio > fail_ratio (default 20 / 0.8)
         // - or fail >= 10 and ok == 0
         let total = ok + fail;
-        if total >= 20 {
+        if total >= policy.min_total {
             let ratio = if total > 0 { (fail as f64) / (total as f64) } else { 0.0 };
-            ratio > 0.8
+            ratio > policy.fail_ratio
         } else {
             fail >= 10 && ok == 0
         }
@@ -50,24 +88,49 @@ impl ResolverInner {
 
 pub struct ResolverPool {
     order: Mutex<Vec<Arc<ResolverInner>>>,
+    trusted: Mutex<Vec<Arc<ResolverInner>>>,
+    /// preferred tier (`--resolver-primary`); a subset of `order`, the rest is fallback
+    primary: Mutex<Vec<Arc<ResolverInner>>>,
+    /// bulk picks served by the primary / fallback tier while a primary tier is set
+    primary_picks: AtomicU64,
+    fallback_picks: AtomicU64,
     map: Mutex<HashMap<String, Arc<ResolverInner>>>,
     on_disable: Mutex<Option<Arc<dyn Fn(String) + Send + Sync>>>,
     cooldown_secs: AtomicU64,
+    policy: Mutex<DisablePolicy>,
+    rng: Mutex<SharedRng>,
+    /// set once every bulk resolver is disabled; cleared when one comes back
+    exhausted: AtomicBool,
 }
 
 impl ResolverPool {
     pub fn new(list: Vec<String>) -> Arc<Self> {
+        Self::new_weighted(list.into_iter().map(|a| (a, 1.0)).collect())
+    }
+
+    /// Pool whose bulk selection is biased by per-resolver weights.
+    pub fn new_weighted(list: Vec<(String, f64)>) -> Arc<Self> {
         let mut order = Vec::with_capacity(list.len());
         let mut map = HashMap::with_capacity(list.len());
-        for a in list.into_iter() {
-            let arc = Arc::new(ResolverInner::new(a.clone()));
+        for (a, w) in list.into_iter() {
+            let arc = Arc::new(ResolverInner::with_weight(a.clone(), w));
             order.push(arc.clone());
             map.insert(a, arc);
         }
-        Arc::new(Self { order: Mutex::new(order), map: Mutex::new(map), on_disable: Mutex::new(None), cooldown_secs: AtomicU64::new(60) })
+        Arc::new(Self { order: Mutex::new(order), trusted: Mutex::new(Vec::new()), primary: Mutex::new(Vec::new()), primary_picks: AtomicU64::new(0), fallback_picks: AtomicU64::new(0), map: Mutex::new(map), on_disable: Mutex::new(None), cooldown_secs: AtomicU64::new(DEFAULT_COOLDOWN_SECS), policy: Mutex::new(DisablePolicy::default()), rng: Mutex::new(shared_rng(None, STREAM_RESOLVER)), exhausted: AtomicBool::new(false) })
     }
 
     pub fn choose_random(&self) -> Option<String> {
+        self.choose_active(None)
+    }
+
+    /// Like `choose_random` but avoids `exclude` (the resolver that just failed for this host);
+    /// falls back to it only when no other resolver is active.
+    pub fn choose_random_excluding(&self, exclude: &str) -> Option<String> {
+        self.choose_active(Some(exclude))
+    }
+
+    fn choose_active(&self, exclude: Option<&str>) -> Option<String> {
         let order = self.order.lock().unwrap();
         // try re-enable disabled resolvers if cooldown elapsed
         let cooldown = self.cooldown_secs.load(Ordering::Relaxed);
@@ -75,8 +138,87 @@ impl ResolverPool {
             r.maybe_reenable(cooldown);
         }
         let active: Vec<&Arc<ResolverInner>> = order.iter().filter(|r| !r.disabled.load(Ordering::Relaxed)).collect();
-        let mut rng = rand::thread_rng();
-        active.choose(&mut rng).map(|r| r.addr.clone())
+        if !active.is_empty() && self.exhausted.swap(false, Ordering::Relaxed) {
+            warn!("[resolver] {} resolver(s) re-enabled after cooldown, resuming", active.len());
+        }
+        // with a primary tier, fallbacks are only used while every primary is disabled
+        let primary = self.primary.lock().unwrap();
+        let tier: Vec<&Arc<ResolverInner>> = if primary.is_empty() { active } else {
+            let preferred: Vec<&Arc<ResolverInner>> = active.iter().copied().filter(|r| primary.iter().any(|p| Arc::ptr_eq(p, r))).collect();
+            if !preferred.is_empty() {
+                self.primary_picks.fetch_add(1, Ordering::Relaxed);
+                preferred
+            } else {
+                if !active.is_empty() { self.fallback_picks.fetch_add(1, Ordering::Relaxed); }
+                active
+            }
+        };
+        drop(primary);
+        let others: Vec<&Arc<ResolverInner>> = match exclude {
+            Some(ex) => tier.iter().copied().filter(|r| r.addr != ex).collect(),
+            None => Vec::new(),
+        };
+        let pick_from = if others.is_empty() { &tier } else { &others };
+        let rng = self.rng.lock().unwrap();
+        let mut g = rng.lock().unwrap();
+        choose_weighted(pick_from, &mut *g)
+    }
+
+    /// Up to `n` distinct active bulk resolvers other than `exclude` (`--consensus` peers).
+    pub fn choose_distinct(&self, n: usize, exclude: &str) -> Vec<String> {
+        let order = self.order.lock().unwrap();
+        let cooldown = self.cooldown_secs.load(Ordering::Relaxed);
+        for r in order.iter() { r.maybe_reenable(cooldown); }
+        let others: Vec<&Arc<ResolverInner>> = order.iter().filter(|r| r.addr != exclude && !r.disabled.load(Ordering::Relaxed)).collect();
+        let rng = self.rng.lock().unwrap();
+        let mut g = rng.lock().unwrap();
+        others.choose_multiple(&mut *g, n).map(|r| r.addr.clone()).collect()
+    }
+
+    /// Register the trusted tier used to confirm hits (kept out of bulk selection).
+    pub fn set_trusted(&self, list: Vec<String>) {
+        let mut trusted = self.trusted.lock().unwrap();
+        let mut map = self.map.lock().unwrap();
+        trusted.clear();
+        for a in list.into_iter() {
+            let arc = map.entry(a.clone()).or_insert_with(|| Arc::new(ResolverInner::new(a))).clone();
+            trusted.push(arc);
+        }
+    }
+
+    /// Register the preferred tier (`--resolver-primary`): bulk selection only falls back to the
+    /// other resolvers while all of these are disabled. Addresses not yet in the pool are added.
+    pub fn set_primary(&self, list: Vec<String>) {
+        let mut map = self.map.lock().unwrap();
+        let mut order = self.order.lock().unwrap();
+        let mut primary = self.primary.lock().unwrap();
+        primary.clear();
+        for a in list.into_iter() {
+            let arc = map.entry(a.clone()).or_insert_with(|| Arc::new(ResolverInner::new(a))).clone();
+            if !order.iter().any(|r| Arc::ptr_eq(r, &arc)) { order.push(arc.clone()); }
+            primary.push(arc);
+        }
+    }
+
+    /// Bulk picks served by the primary and the fallback tier; `None` without a primary tier.
+    pub fn tier_picks(&self) -> Option<(u64, u64)> {
+        if self.primary.lock().unwrap().is_empty() { return None; }
+        Some((self.primary_picks.load(Ordering::Relaxed), self.fallback_picks.load(Ordering::Relaxed)))
+    }
+
+    pub fn is_trusted(&self, addr: &str) -> bool {
+        self.trusted.lock().unwrap().iter().any(|r| r.addr == addr)
+    }
+
+    /// Random active trusted resolver, if any.
+    pub fn choose_trusted(&self) -> Option<String> {
+        let trusted = self.trusted.lock().unwrap();
+        let cooldown = self.cooldown_secs.load(Ordering::Relaxed);
+        for r in trusted.iter() { r.maybe_reenable(cooldown); }
+        let active: Vec<&Arc<ResolverInner>> = trusted.iter().filter(|r| !r.disabled.load(Ordering::Relaxed)).collect();
+        let rng = self.rng.lock().unwrap();
+        let mut g = rng.lock().unwrap();
+        active.choose(&mut *g).map(|r| r.addr.clone())
     }
 
     pub fn report_ok(&self, addr: &str) {
@@ -88,12 +230,20 @@ impl ResolverPool {
     pub fn report_fail(&self, addr: &str) {
         if let Some(item) = self.map.lock().unwrap().get(addr) {
             item.fail.fetch_add(1, Ordering::Relaxed);
-            if item.should_disable() {
-                item.disabled.store(true, Ordering::Relaxed);
+            // only the active -> disabled transition counts; late replies from
+            // in-flight queries must not bump the count or restart the cooldown
+            let policy = *self.policy.lock().unwrap();
+            if item.should_disable(&policy) && !item.disabled.swap(true, Ordering::Relaxed) {
+                item.disable_count.fetch_add(1, Ordering::Relaxed);
                 *item.disabled_at.lock().unwrap() = Some(Instant::now());
                 if let Some(cb) = self.on_disable.lock().unwrap().as_ref() {
                     cb(item.addr.clone());
                 }
+                let order = self.order.lock().unwrap();
+                let all_down = !order.is_empty() && order.iter().all(|r| r.disabled.load(Ordering::Relaxed));
+                if all_down && !self.exhausted.swap(true, Ordering::Relaxed) {
+                    warn!("[resolver] ALL {} resolvers are disabled by health checks; queries will fail until they re-enable after {}s cooldown (check network / resolver list)", order.len(), self.cooldown_secs.load(Ordering::Relaxed));
+                }
             }
         }
     }
@@ -117,28 +267,59 @@ impl ResolverPool {
         self.cooldown_secs.store(secs, Ordering::Relaxed);
     }
 
+    pub fn set_disable_policy(&self, policy: DisablePolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Replace the selection RNG (e.g. a seeded one for reproducible scans).
+    pub fn set_rng(&self, rng: SharedRng) {
+        *self.rng.lock().unwrap() = rng;
+    }
+
     pub fn snapshot(&self) -> Vec<ResolverStat> {
         let order = self.order.lock().unwrap();
-        order.iter().map(|r| ResolverStat {
+        let trusted = self.trusted.lock().unwrap();
+        let bulk = order.iter().filter(|r| !trusted.iter().any(|t| Arc::ptr_eq(t, r)));
+        let primary = self.primary.lock().unwrap();
+        bulk.map(|r| (r, false)).chain(trusted.iter().map(|r| (r, true))).map(|(r, is_trusted)| ResolverStat {
             addr: r.addr.clone(),
             ok: r.ok.load(Ordering::Relaxed),
             fail: r.fail.load(Ordering::Relaxed),
             disabled: r.disabled.load(Ordering::Relaxed),
+            disabled_since_secs: r.disabled_at.lock().unwrap().map(|t| t.elapsed().as_secs()),
+            disable_count: r.disable_count.load(Ordering::Relaxed),
+            trusted: is_trusted,
+            primary: primary.iter().any(|p| Arc::ptr_eq(p, r)),
         }).collect()
     }
 }
 
+/// Weighted pick; all-zero weights fall back to a uniform pick.
+fn choose_weighted<R: rand::Rng + ?Sized>(from: &[&Arc<ResolverInner>], rng: &mut R) -> Option<String> {
+    match from.choose_weighted(rng, |r| r.weight) {
+        Ok(r) => Some(r.addr.clone()),
+        Err(_) => from.choose(rng).map(|r| r.addr.clone()),
+    }
+}
+
 #[derive(Serialize)]
 pub struct ResolverStat {
     pub addr: String,
     pub ok: u64,
     pub fail: u64,
     pub disabled: bool,
+    /// seconds since the current disable tripped; absent while active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_since_secs: Option<u64>,
+    pub disable_count: u64,
+    pub trusted: bool,
+    /// `--resolver-primary` tier
+    pub primary: bool,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ResolverPool;
+    use super::{parse_fail_ratio, DisablePolicy, ResolverPool};
     use std::sync::Arc;
     use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -153,6 +334,60 @@ mod tests {
         assert_eq!(active, 0, "resolver should be disabled after 10 fails and 0 ok");
     }
 
+    #[test]
+    fn choose_random_excluding_avoids_last_resolver() {
+        let pool = ResolverPool::new(vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()]);
+        for _ in 0..32 {
+            assert_eq!(pool.choose_random_excluding("1.1.1.1").as_deref(), Some("8.8.8.8"));
+        }
+        // the only other resolver is disabled: fall back to the excluded one
+        for _ in 0..10 { pool.report_fail("8.8.8.8"); }
+        assert_eq!(pool.choose_random_excluding("1.1.1.1").as_deref(), Some("1.1.1.1"));
+    }
+
+    #[test]
+    fn exhausted_flag_tracks_all_disabled() {
+        let pool = ResolverPool::new(vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()]);
+        for _ in 0..10 { pool.report_fail("1.1.1.1"); }
+        assert!(!pool.exhausted.load(Ordering::Relaxed));
+        for _ in 0..10 { pool.report_fail("8.8.8.8"); }
+        assert!(pool.exhausted.load(Ordering::Relaxed));
+        assert_eq!(pool.choose_random(), None);
+        // cooldown elapsed: resolvers come back and the flag clears
+        pool.set_cooldown_secs(0);
+        assert!(pool.choose_random().is_some());
+        assert!(!pool.exhausted.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn weights_bias_selection() {
+        let pool = ResolverPool::new_weighted(vec![("1.1.1.1".to_string(), 9.0), ("8.8.8.8".to_string(), 1.0), ("9.9.9.9".to_string(), 0.0)]);
+        pool.set_rng(crate::rng::shared_rng(Some(7), crate::rng::STREAM_RESOLVER));
+        let mut heavy = 0;
+        for _ in 0..1000 {
+            let r = pool.choose_random().unwrap();
+            assert_ne!(r, "9.9.9.9", "zero-weight resolver must not be picked while others are active");
+            if r == "1.1.1.1" { heavy += 1; }
+        }
+        assert!(heavy > 800, "heavy resolver picked {} / 1000", heavy);
+    }
+
+    #[test]
+    fn disable_history_in_snapshot() {
+        let pool = ResolverPool::new(vec!["1.1.1.1".to_string()]);
+        for _ in 0..15 { pool.report_fail("1.1.1.1"); }
+        let s = &pool.snapshot()[0];
+        assert!(s.disabled);
+        assert_eq!(s.disable_count, 1, "further fails while disabled must not re-count");
+        assert!(s.disabled_since_secs.is_some());
+        // cooldown: re-enabled, then trips again
+        pool.set_cooldown_secs(0);
+        assert!(pool.choose_random().is_some());
+        assert_eq!(pool.snapshot()[0].disabled_since_secs, None);
+        for _ in 0..10 { pool.report_fail("1.1.1.1"); }
+        assert_eq!(pool.snapshot()[0].disable_count, 2);
+    }
+
     #[test]
     fn disable_on_high_fail_ratio() {
         let pool = ResolverPool::new(vec!["8.8.8.8".to_string()]);
@@ -171,6 +406,64 @@ mod tests {
         assert!(fired.load(Ordering::Relaxed));
     }
 
+    #[test]
+    fn disable_policy_is_tunable() {
+        let pool = ResolverPool::new(vec!["8.8.8.8".to_string()]);
+        pool.set_disable_policy(DisablePolicy { min_total: 50, fail_ratio: 0.9 });
+        // 17 fail / 3 ok: 0.85 over 20 outcomes would trip the default policy
+        for _ in 0..3 { pool.report_ok("8.8.8.8"); }
+        for _ in 0..17 { pool.report_fail("8.8.8.8"); }
+        assert_eq!(pool.counts().0, 1);
+        for _ in 0..30 { pool.report_fail("8.8.8.8"); }
+        assert_eq!(pool.counts().0, 0, "47/50 failed exceeds 0.9");
+        assert_eq!(parse_fail_ratio("0.5"), Ok(0.5));
+        assert!(parse_fail_ratio("0").is_err());
+        assert!(parse_fail_ratio("1.2").is_err());
+    }
+
+    #[test]
+    fn choose_distinct_skips_excluded_and_disabled() {
+        let pool = ResolverPool::new(vec!["1.1.1.1".to_string(), "8.8.8.8".to_string(), "9.9.9.9".to_string()]);
+        let mut peers = pool.choose_distinct(5, "1.1.1.1");
+        peers.sort();
+        assert_eq!(peers, vec!["8.8.8.8".to_string(), "9.9.9.9".to_string()], "fewer than n available: all of them");
+        for _ in 0..10 { pool.report_fail("9.9.9.9"); }
+        assert_eq!(pool.choose_distinct(2, "1.1.1.1"), vec!["8.8.8.8".to_string()]);
+    }
+
+    #[test]
+    fn trusted_tier_separate_from_bulk() {
+        let pool = ResolverPool::new(vec!["8.8.8.8".to_string()]);
+        pool.set_trusted(vec!["9.9.9.9".to_string()]);
+        assert!(pool.is_trusted("9.9.9.9"));
+        assert!(!pool.is_trusted("8.8.8.8"));
+        for _ in 0..16 { assert_eq!(pool.choose_random().as_deref(), Some("8.8.8.8")); }
+        assert_eq!(pool.choose_trusted().as_deref(), Some("9.9.9.9"));
+        let snap = pool.snapshot();
+        assert_eq!(snap.len(), 2);
+        assert!(snap.iter().any(|s| s.addr == "9.9.9.9" && s.trusted));
+    }
+
+    #[test]
+    fn primary_tier_preferred_until_disabled() {
+        let pool = ResolverPool::new(vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()]);
+        assert_eq!(pool.tier_picks(), None);
+        pool.set_primary(vec!["10.0.0.53".to_string()]);
+        assert_eq!(pool.counts(), (3, 3));
+        for _ in 0..16 { assert_eq!(pool.choose_random().as_deref(), Some("10.0.0.53")); }
+        // a single primary stays preferred on retries too
+        assert_eq!(pool.choose_random_excluding("10.0.0.53").as_deref(), Some("10.0.0.53"));
+        for _ in 0..10 { pool.report_fail("10.0.0.53"); }
+        for _ in 0..16 { assert_ne!(pool.choose_random().as_deref(), Some("10.0.0.53")); }
+        assert_eq!(pool.tier_picks(), Some((17, 16)));
+        let snap = pool.snapshot();
+        assert!(snap.iter().any(|s| s.addr == "10.0.0.53" && s.primary && s.disabled));
+        assert!(snap.iter().filter(|s| s.addr != "10.0.0.53").all(|s| !s.primary));
+        // back after cooldown: primaries win again
+        pool.set_cooldown_secs(0);
+        assert_eq!(pool.choose_random().as_deref(), Some("10.0.0.53"));
+    }
+
     #[test]
     fn reenable_after_cooldown() {
         let pool = ResolverPool::new(vec!["1.0.0.1".to_string()]);
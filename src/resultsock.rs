@@ -0,0 +1,210 @@
+//! `--result-socket <ADDR>`: stream results to live consumers over TCP or a Unix socket
+//! (feature `result-socket`, off by default).
+//!
+//! Every connected client receives each result as one json line (same format as `-o x.jsonl`),
+//! starting from the moment it connects. A client that falls more than [`CLIENT_BUFFER`] lines
+//! behind is disconnected instead of stalling the scan; clients are closed when the scan ends.
+//!
+//! ```text
+//! rusub enum example.com --result-socket tcp:127.0.0.1:7000 &
+//! nc 127.0.0.1 7000 | jq -r .subdomain
+//! ```
+
+use crate::output::{json_line, OutputWriter, ScanResult};
+use anyhow::{Context, Result};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+/// Lines buffered per client before a slow client is dropped.
+pub const CLIENT_BUFFER: usize = 4096;
+
+/// Pause after a failed accept (e.g. EMFILE) so a persistent error doesn't spin the loop.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Where `--result-socket` listens: `tcp:HOST:PORT`, `unix:PATH`, or a bare `HOST:PORT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl std::str::FromStr for ListenAddr {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return if path.is_empty() { Err("unix: needs a socket path".into()) } else { Ok(Self::Unix(path.into())) };
+            #[cfg(not(unix))]
+            return Err(format!("{:?}: Unix sockets need a Unix platform", path));
+        }
+        let addr = s.strip_prefix("tcp:").unwrap_or(s);
+        match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(Self::Tcp(addr.to_string())),
+            _ => Err(format!("{:?}: expected tcp:HOST:PORT, unix:PATH or HOST:PORT", s)),
+        }
+    }
+}
+
+/// Broadcast-backed writer: each result goes to every connected client. Closing it ends
+/// every client stream once its buffered lines are sent and stops accepting.
+pub struct SocketWriter {
+    tx: Mutex<Option<broadcast::Sender<Arc<str>>>>,
+    accept: JoinHandle<()>,
+    /// the bound address (with the real port for `:0`, or the socket path)
+    pub local: String,
+}
+
+impl SocketWriter {
+    /// Bind `addr` and start accepting clients; a stale Unix socket file is replaced, any other
+    /// file at the path is left alone and reported as an error.
+    pub async fn bind(addr: &ListenAddr) -> Result<Self> {
+        let (tx, _) = broadcast::channel::<Arc<str>>(CLIENT_BUFFER);
+        let sub = tx.clone();
+        let (local, accept) = match addr {
+            ListenAddr::Tcp(a) => {
+                let listener = TcpListener::bind(a).await.with_context(|| format!("cannot bind result socket {}", a))?;
+                let local = listener.local_addr()?.to_string();
+                (local, tokio::spawn(async move {
+                    loop {
+                        let (stream, peer) = match listener.accept().await {
+                            Ok(conn) => conn,
+                            Err(e) => { accept_failed(e).await; continue; }
+                        };
+                        let _ = stream.set_nodelay(true);
+                        tokio::spawn(feed(stream, sub.subscribe(), peer.to_string()));
+                    }
+                }))
+            }
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = tokio::net::UnixListener::bind(path).with_context(|| format!("cannot bind result socket {}", path.display()))?;
+                let local = path.display().to_string();
+                (local, tokio::spawn(async move {
+                    loop {
+                        let stream = match listener.accept().await {
+                            Ok((stream, _)) => stream,
+                            Err(e) => { accept_failed(e).await; continue; }
+                        };
+                        tokio::spawn(feed(stream, sub.subscribe(), "unix client".to_string()));
+                    }
+                }))
+            }
+        };
+        info!("[result-socket] streaming results on {}", local);
+        Ok(SocketWriter { tx: Mutex::new(Some(tx)), accept, local })
+    }
+}
+
+async fn accept_failed(e: std::io::Error) {
+    warn!("[result-socket] accept error: {}", e);
+    tokio::time::sleep(ACCEPT_BACKOFF).await;
+}
+
+/// Remove a socket file left behind by an earlier run; anything else at `path` is an error,
+/// so a mistyped path (`unix:./results.json`) never deletes user data.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(m) if m.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("cannot remove stale result socket {}", path.display())),
+        Ok(_) => anyhow::bail!("result socket path {} exists and is not a socket; refusing to replace it", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("cannot inspect result socket path {}", path.display())),
+    }
+}
+
+/// Copy broadcast lines to one client until the scan ends, the client goes away or lags.
+async fn feed<W: AsyncWrite + Unpin>(mut w: W, mut rx: broadcast::Receiver<Arc<str>>, peer: String) {
+    loop {
+        match rx.recv().await {
+            Ok(line) => {
+                if w.write_all(line.as_bytes()).await.is_err() { return; }
+            }
+            Err(RecvError::Lagged(n)) => {
+                warn!("[result-socket] dropping slow client {} ({} result(s) behind)", peer, n);
+                return;
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+    let _ = w.shutdown().await;
+}
+
+impl OutputWriter for SocketWriter {
+    fn write(&self, r: &ScanResult) -> Result<()> {
+        if let Some(tx) = self.tx.lock().unwrap().as_ref() {
+            let mut line = json_line(r)?;
+            line.push('\n');
+            // no clients connected is not an error
+            let _ = tx.send(line.into());
+        }
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        self.tx.lock().unwrap().take();
+        self.accept.abort();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    #[test]
+    fn parses_listen_addresses() {
+        assert_eq!("tcp:127.0.0.1:7000".parse::<ListenAddr>(), Ok(ListenAddr::Tcp("127.0.0.1:7000".into())));
+        assert_eq!("[::1]:7000".parse::<ListenAddr>(), Ok(ListenAddr::Tcp("[::1]:7000".into())));
+        #[cfg(unix)]
+        assert_eq!("unix:/tmp/r.sock".parse::<ListenAddr>(), Ok(ListenAddr::Unix("/tmp/r.sock".into())));
+        assert!("127.0.0.1".parse::<ListenAddr>().is_err());
+        assert!("tcp::99999".parse::<ListenAddr>().is_err());
+    }
+
+    #[tokio::test]
+    async fn clients_receive_json_lines_until_close() {
+        let w = SocketWriter::bind(&ListenAddr::Tcp("127.0.0.1:0".into())).await.unwrap();
+        let a = tokio::net::TcpStream::connect(&w.local).await.unwrap();
+        let b = tokio::net::TcpStream::connect(&w.local).await.unwrap();
+        // let the accept loop subscribe both clients before the first result
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        w.write(&ScanResult { subdomain: "www.example.com".into(), answers: vec!["192.0.2.1".into()], ..Default::default() }).unwrap();
+        w.write(&ScanResult { subdomain: "api.example.com".into(), answers: vec!["192.0.2.2".into()], ..Default::default() }).unwrap();
+        w.close().unwrap();
+        for client in [a, b] {
+            let mut lines = BufReader::new(client).lines();
+            let mut got = Vec::new();
+            while let Some(l) = lines.next_line().await.unwrap() {
+                let v: serde_json::Value = serde_json::from_str(&l).unwrap();
+                got.push(v["subdomain"].as_str().unwrap().to_string());
+            }
+            assert_eq!(got, ["www.example.com", "api.example.com"], "stream ends after close");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_bind_refuses_to_replace_a_regular_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        std::fs::write(&path, "keep me").unwrap();
+        let err = SocketWriter::bind(&ListenAddr::Unix(path.clone())).await.err().unwrap();
+        assert!(err.to_string().contains("is not a socket"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        // a stale socket from an earlier run is replaced
+        let sock = dir.path().join("r.sock");
+        drop(std::os::unix::net::UnixListener::bind(&sock).unwrap());
+        SocketWriter::bind(&ListenAddr::Unix(sock)).await.unwrap().close().unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Reverse (PTR) mode helpers: expand IP / CIDR inputs and build the
+//! `in-addr.arpa` / `ip6.arpa` query names.
+
+use anyhow::{bail, Result};
+use std::net::IpAddr;
+
+/// 单个 CIDR 最多展开的地址数 (IPv4 /16，IPv6 /112)
+pub const MAX_CIDR_HOSTS: u128 = 65_536;
+
+/// Expand a list of IPs and CIDR blocks (e.g. `192.0.2.0/24`, `2001:db8::/120`) into addresses.
+pub fn expand_targets(inputs: &[String]) -> Result<Vec<IpAddr>> {
+    let mut out = Vec::new();
+    for raw in inputs {
+        let s = raw.trim();
+        if s.is_empty() { continue; }
+        let Some((addr, prefix)) = s.split_once('/') else {
+            out.push(s.parse::<IpAddr>().map_err(|e| anyhow::anyhow!("invalid IP '{}': {}", s, e))?);
+            continue;
+        };
+        let addr: IpAddr = addr.parse().map_err(|e| anyhow::anyhow!("invalid CIDR '{}': {}", s, e))?;
+        let prefix: u32 = prefix.parse().map_err(|e| anyhow::anyhow!("invalid CIDR '{}': {}", s, e))?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > bits { bail!("invalid CIDR '{}': prefix > {}", s, bits); }
+        let count = 1u128 << (bits - prefix).min(127);
+        if bits - prefix > 16 || count > MAX_CIDR_HOSTS {
+            bail!("CIDR '{}' is too large for reverse mode (max {} addresses per block)", s, MAX_CIDR_HOSTS);
+        }
+        match addr {
+            IpAddr::V4(v4) => {
+                let base = u32::from(v4) & (u32::MAX.checked_shl(32 - prefix).unwrap_or(0));
+                for i in 0..count as u32 { out.push(IpAddr::V4((base + i).into())); }
+            }
+            IpAddr::V6(v6) => {
+                let base = u128::from(v6) & (u128::MAX.checked_shl(128 - prefix).unwrap_or(0));
+                for i in 0..count { out.push(IpAddr::V6((base + i).into())); }
+            }
+        }
+    }
+    out.sort(); out.dedup();
+    Ok(out)
+}
+
+/// `192.0.2.1` -> `1.2.0.192.in-addr.arpa`; IPv6 uses the reversed nibble form under `ip6.arpa`.
+pub fn ptr_name(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let mut s = String::with_capacity(72);
+            for b in v6.octets().iter().rev() {
+                s.push_str(&format!("{:x}.{:x}.", b & 0x0f, b >> 4));
+            }
+            s.push_str("ip6.arpa");
+            s
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn ptr_names_and_cidr_expansion() {
+        assert_eq!(ptr_name(&"192.0.2.1".parse().unwrap()), "1.2.0.192.in-addr.arpa");
+        assert_eq!(
+            ptr_name(&"2001:db8::567:89ab".parse().unwrap()),
+            "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+        let v = expand_targets(&["192.0.2.5/30".into(), "198.51.100.7".into(), "192.0.2.4".into()]).unwrap();
+        let v: Vec<String> = v.iter().map(|ip| ip.to_string()).collect();
+        assert_eq!(v, ["192.0.2.4", "192.0.2.5", "192.0.2.6", "192.0.2.7", "198.51.100.7"]);
+        assert_eq!(expand_targets(&["2001:db8::/126".into()]).unwrap().len(), 4);
+        assert!(expand_targets(&["10.0.0.0/8".into()]).is_err());
+        assert!(expand_targets(&["example.com".into()]).is_err());
+    }
+}
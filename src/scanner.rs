@@ -1,7 +1,6 @@
 use crate::options::Options;
-use anyhow::Result;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use anyhow::{Context, Result};
+use std::io::BufRead;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
@@ -9,26 +8,34 @@ use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::net::lookup_host;
 // rand was previously used for direct resolver randomization; now handled inside ResolverPool
 // (remove unused imports)
-use crate::dns::udp_query_full;
-use crate::output::{ScanResult, ScanRecord, build_writers};
-use crate::wildcard::{detect_wildcard, is_wildcard};
+use crate::dns::{BindError, DnsAnswer, DnsClient, QueryOptions, RawRecord, RecordType, UdpClient, BIND_ERROR};
+use crate::output::{AnswerCapWriter, ChannelWriter, DedupWriter, DiscoveryTree, EcsAnswers, FormatOptions, HashWriter, LineFlushWriter, OutputDigest, OutputWriter, PartialWriter, QueuedWriter, ScanResult, ScanRecord, SortingWriter, SplitWriter, TreeWriter, build_writers, existing_subdomains};
+use crate::wildcard::{detect_all, is_wildcard, DetectParams, DETECT_PARALLEL};
 use std::sync::Mutex;
 use tokio::time::{timeout, Duration};
 use crate::ratelimit::RateLimiter;
 use crate::discovery;
-use crate::metrics::{Metrics, spawn_reporter, spawn_json_reporter};
-use crate::resolver_pool::ResolverPool;
+use crate::metrics::{final_snapshot, write_progress_event, Metrics, ProgressTarget, spawn_heartbeat, spawn_progress_stream, spawn_rate_gap_watch, spawn_reporter, spawn_json_reporter};
+use crate::resolver_pool::{DisablePolicy, ResolverPool};
 use crate::state::{StatusDb, Item, EntryState};
+use crate::hosts::HostsMap;
+use crate::rng::{shared_rng, SharedRng, STREAM_AUTH, STREAM_QUERY_ID, STREAM_RESOLVER, STREAM_WILDCARD};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tracing::{debug, error, info, warn};
 
-async fn read_wordlist(path: &Option<PathBuf>) -> Result<Vec<String>> {
-    if let Some(p) = path {
+async fn read_wordlist(paths: &[PathBuf]) -> Result<Vec<String>> {
+    if !paths.is_empty() {
+        // 多个字典按给定顺序合并，跨文件去重 (保留首次出现的顺序)
         let mut words = Vec::new();
-        let f = File::open(p)?;
-        for line in BufReader::new(f).lines() {
-            if let Ok(l) = line {
-                let s = l.trim();
-                if s.is_empty() || s.starts_with('#') { continue; }
-                words.push(s.to_string());
+        let mut seen = HashSet::new();
+        for p in paths {
+            for line in crate::options::open_text_input(p)?.lines() {
+                if let Ok(l) = line {
+                    let s = l.trim();
+                    if s.is_empty() || s.starts_with('#') { continue; }
+                    if seen.insert(s.to_string()) { words.push(s.to_string()); }
+                }
             }
         }
         Ok(words)
@@ -38,70 +45,274 @@ async fn read_wordlist(path: &Option<PathBuf>) -> Result<Vec<String>> {
     }
 }
 
-pub async fn run(opt: Options) -> Result<()> {
-    let mut words = read_wordlist(&opt.filename).await?;
-    
+/// 规范化候选标签：去掉空白与首尾的点，按小写去重 (DNS 大小写不敏感)。
+/// `preserve_case` 时保留首次出现的原始大小写，否则统一小写；顺序保持不变。
+fn normalize_labels(words: Vec<String>, preserve_case: bool) -> Vec<String> {
+    let mut seen = HashSet::with_capacity(words.len());
+    let mut out = Vec::with_capacity(words.len());
+    for w in words {
+        let label = w.trim().trim_matches('.');
+        if label.is_empty() { continue; }
+        let key = label.to_ascii_lowercase();
+        if seen.insert(key.clone()) {
+            out.push(if preserve_case { label.to_string() } else { key });
+        }
+    }
+    out
+}
+
+/// `--shard I/N`: every N-th candidate starting at index I. The list is final (merged, expanded,
+/// filtered) by then, so N runs over the same inputs cover it exactly once between them.
+fn shard_words(words: Vec<String>, index: usize, count: usize) -> Vec<String> {
+    words.into_iter().enumerate().filter(|(i, _)| i % count == index).map(|(_, w)| w).collect()
+}
+
+/// 候选标签校验：每段 1..=63 字符，仅 LDH 加下划线 (SRV/_dmarc 等)，不以连字符开头或结尾。
+fn valid_candidate(word: &str) -> bool {
+    word.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    })
+}
+
+/// Run a scan and yield results as a stream instead of writing files or stdout.
+///
+/// Persistence and console knobs on `opt` (output file, stdout, state file,
+/// progress/stats/manifest files, split dir) are cleared; filtering knobs
+/// (`only_alive`, `include_wildcard`, `dedup_output`, `sort_output`) still apply.
+/// The stream ends when the scan finishes; a scan error is logged and ends it early.
+pub fn enumerate(mut opt: Options) -> impl futures::Stream<Item = ScanResult> {
+    opt.output = None;
+    opt.not_print = true;
+    opt.progress = false;
+    opt.status_file = None;
+    opt.progress_json_file = None;
+    opt.progress_stream = None;
+    opt.heartbeat = 0;
+    opt.resolver_stats_file = None;
+    opt.manifest = None;
+    opt.split_by_rtype = None;
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let sink: Vec<Box<dyn OutputWriter>> = vec![Box::new(ChannelWriter::new(tx))];
+        if let Err(e) = run_with_writers(opt, Some(sink), Arc::new(UdpClient)).await {
+            error!("enumerate: {}", e);
+        }
+    });
+    rx
+}
+
+/// End-of-scan counters returned by [`run`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanSummary {
+    /// hosts resolved and emitted in this run (entries skipped via the state file are not counted)
+    pub found: u64,
+    pub failed: u64,
+    /// SERVFAIL answers seen / hosts that still got a definitive answer on retry
+    pub servfail: u64,
+    pub servfail_recovered: u64,
+    /// `--recheck-failed`: failed hosts that resolved on the final pass (also counted in `found`)
+    pub recovered: u64,
+    /// IPv4 / IPv6 addresses across the emitted results
+    pub ipv4: u64,
+    pub ipv6: u64,
+}
+
+pub async fn run(opt: Options) -> Result<ScanSummary> {
+    run_with_writers(opt, None, Arc::new(UdpClient)).await
+}
+
+/// Run a scan into caller-built writers (e.g. [`crate::output::VecWriter`]) instead of the
+/// file/stdout writers from `opt`. Unlike [`enumerate`] nothing on `opt` is cleared: state
+/// file, progress and manifest knobs apply, as do the dedup / sort / tree wrappers.
+pub async fn run_with(opt: Options, writers: Vec<Box<dyn OutputWriter>>) -> Result<ScanSummary> {
+    run_with_writers(opt, Some(writers), Arc::new(UdpClient)).await
+}
+
+/// [`run_with`] over another DNS transport, e.g. a [`crate::mockdns::MockDnsClient`]. Host
+/// lookups, `--reverse` PTR queries and the wildcard probes all go through `client`; NS
+/// discovery for `--auth` and resolver host names still use the network.
+pub async fn run_with_client(opt: Options, writers: Vec<Box<dyn OutputWriter>>, client: Arc<dyn DnsClient>) -> Result<ScanSummary> {
+    run_with_writers(opt, Some(writers), client).await
+}
+
+/// `writers: None` builds the file/stdout writers from `opt`.
+async fn run_with_writers(mut opt: Options, writers: Option<Vec<Box<dyn OutputWriter>>>, client: Arc<dyn DnsClient>) -> Result<ScanSummary> {
+    // 主机名形式的解析器 (dns.quad9.net) 先解析为 IP，池中只保存地址
+    let names = crate::options::resolve_resolver_hosts(&opt.resolvers.iter().chain(opt.trusted_resolvers.iter()).chain(opt.primary_resolvers.iter()).cloned().collect::<Vec<_>>()).await?;
+    if !names.is_empty() {
+        crate::options::apply_resolver_hosts(&mut opt.resolvers, &names);
+        crate::options::apply_resolver_hosts(&mut opt.trusted_resolvers, &names);
+        crate::options::apply_resolver_hosts(&mut opt.primary_resolvers, &names);
+        for (addr, _) in opt.resolver_weights.iter_mut() {
+            if let Some((_, ip)) = names.iter().find(|(n, _)| n == addr) { *addr = ip.clone(); }
+        }
+    }
+    // 解析器池为空时所有查询都会失败：启动即报错，而不是跑完一个全失败的扫描
+    let (usable, dropped) = crate::options::usable_resolvers(&opt.resolvers);
+    for r in dropped.iter() { warn!("[resolver] ignoring unusable resolver {:?} (IPv4 address expected)", r); }
+    if usable.is_empty() {
+        anyhow::bail!("no usable resolvers (given: {}); pass IPv4 addresses with -r or check the system DNS configuration", if opt.resolvers.is_empty() { "none".to_string() } else { opt.resolvers.join(", ") });
+    }
+    opt.resolvers = usable;
+    let (usable, dropped) = crate::options::usable_resolvers(&opt.primary_resolvers);
+    for r in dropped.iter() { warn!("[resolver] ignoring unusable primary resolver {:?} (IPv4 address expected)", r); }
+    opt.primary_resolvers = usable;
+    // --reverse: 输入是 IP / CIDR，不需要字典
+    let reverse_targets = if opt.reverse { crate::reverse::expand_targets(&opt.domains)? } else { Vec::new() };
+    let mut words = if opt.reverse { Vec::new() } else { normalize_labels(read_wordlist(&opt.filenames).await?, opt.preserve_case) };
+    let src = if opt.filenames.is_empty() { "embedded".to_string() } else { opt.filenames.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ") };
+    if opt.reverse { info!("[reverse] {} address(es) to query", reverse_targets.len()); } else { info!("[wordlist] loaded {} words ({})", words.len(), src); }
+    // 清单 / 统计 / 进度等旁路文件与结果文件一样：目录不存在时先创建
+    for p in [&opt.manifest, &opt.resolver_stats_file, &opt.progress_json_file].into_iter().flatten() {
+        crate::output::ensure_parent_dir(p)?;
+    }
+    if let Some(path) = &opt.manifest {
+        crate::manifest::Manifest::build(&opt, words.len())?.write(path)?;
+    }
+
     if opt.predict {
         let mut seeds = discovery::basic_seeds();
         let dyn_ext: Vec<String> = discovery::dynamic_extend(&[], &seeds, 32);
         seeds.extend(dyn_ext);
         words.append(&mut seeds);
-        words.sort(); words.dedup();
+        words = normalize_labels(words, opt.preserve_case);
+        words.sort();
     }
     //启发式扩展（基于现有词表和常见 token），可配置最大条数
     if opt.heuristic {
         let max = opt.heuristic_max.max(1);
-        let mut h = discovery::generate_heuristics(&words, max);
+        let mut h = discovery::generate_heuristics_for(&words, &opt.domains, max);
         words.append(&mut h);
-        words.sort(); words.dedup();
+        words = normalize_labels(words, opt.preserve_case);
+        words.sort();
+    }
+    // metrics & status db
+    let metrics = Metrics::new();
+    // 丢弃必然 NXDOMAIN 的非法候选，避免浪费查询与污染统计
+    if !opt.allow_invalid_labels {
+        let before = words.len();
+        words.retain(|w| valid_candidate(w));
+        let dropped = (before - words.len()) as u64;
+        metrics.invalid_labels.fetch_add(dropped, Ordering::Relaxed);
+        if dropped > 0 {
+            info!("[wordlist] dropped {} invalid label(s) (use --allow-invalid-labels to keep them)", dropped);
+        }
+    }
+    if let Some((index, count)) = opt.shard {
+        let before = words.len();
+        words = shard_words(words, index, count);
+        info!("[shard] {}/{}: {} of {} candidate(s)", index, count, words.len(), before);
     }
     let discovered = Arc::new(Mutex::new(Vec::<String>::new()));
-    let word_set = Arc::new(Mutex::new(words.iter().cloned().collect::<std::collections::HashSet<String>>()));
-    let sem = Arc::new(Semaphore::new(opt.concurrency));
+    let word_set = Arc::new(Mutex::new(words.iter().map(|w| w.to_ascii_lowercase()).collect::<std::collections::HashSet<String>>()));
+    // -c 限制在途查询 (active)，等速率令牌的任务不占并发槽；派发另设上限 (admit) 以约束任务数
+    let active = Arc::new(Semaphore::new(opt.concurrency));
+    let admit = Arc::new(Semaphore::new(opt.concurrency.saturating_mul(ADMIT_FACTOR)));
     // rate limiter based on packets-per-second (derived from band)
     let rl = RateLimiter::new(opt.rate.max(0));
+    rl.set_burst(opt.burst);
     rl.spawn_refill();
     let rl_sem = rl.handle();
-    // metrics & status db
-    let metrics = Metrics::new();
+    // 暂停标志：置位时派发循环不再发放新任务，在途查询照常完成
+    let paused = Arc::new(AtomicBool::new(false));
+    let signal_task = spawn_pause_signals(paused.clone());
+    #[cfg(all(unix, feature = "control-socket"))]
+    let control_task = match &opt.control_socket {
+        Some(path) => Some(crate::control::serve(path.clone(), crate::control::ControlState {
+            rate: rl.clone(),
+            paused: paused.clone(),
+            metrics: metrics.clone(),
+        })?),
+        None => None,
+    };
     let scan_start = tokio::time::Instant::now();
-    let status_db = StatusDb::create_memory_db();
+    // 单次扫描的状态只活到本次运行结束，不需要过期清理
+    let status_db = StatusDb::create_memory_db_no_expiry();
     // load persisted status if configured
     if let Some(path) = &opt.status_file {
         match crate::state::load_from_file(&status_db, path).await {
-            Ok(n) => { if !opt.silent && !opt.pure_output { eprintln!("[statusdb] loaded {} entries from {}", n, path.display()); } },
-            Err(e) => { if !opt.pure_output { eprintln!("[statusdb] load error: {}", e); } }
+            Ok(n) => info!("[statusdb] loaded {} entries from {}", n, path.display()),
+            Err(e) => warn!("[statusdb] load error: {}", e),
         }
     }
-    // total = words * domains (initial pass)
-    let total_tasks = (words.len() as u64) * (opt.domains.len() as u64);
+    // total = words * domains (initial pass); with --domain-chunk only the first chunk, later chunks add theirs as they start
+    let first_chunk = if opt.domain_chunk > 0 { opt.domains.len().min(opt.domain_chunk) } else { opt.domains.len() };
+    let total_tasks = if opt.reverse { reverse_targets.len() as u64 } else { (words.len() as u64) * (first_chunk as u64) };
     metrics.total.store(total_tasks, std::sync::atomic::Ordering::Relaxed);
     // init resolver pool and base resolver list for wildcard detection
-    let resolver_pool = ResolverPool::new(opt.resolvers.clone());
+    let resolver_pool = ResolverPool::new_weighted(opt.resolvers.iter().map(|r| {
+        let w = opt.resolver_weights.iter().find(|(a, _)| a == r).map(|(_, w)| *w).unwrap_or(1.0);
+        (r.clone(), w)
+    }).collect());
+    resolver_pool.set_trusted(opt.trusted_resolvers.clone());
+    if !opt.primary_resolvers.is_empty() {
+        resolver_pool.set_primary(opt.primary_resolvers.clone());
+        info!("[resolver] primary tier: {} (fallback: {} other resolver(s))", opt.primary_resolvers.join(", "), opt.resolvers.iter().filter(|r| !opt.primary_resolvers.contains(r)).count());
+    }
     resolver_pool.set_cooldown_secs(opt.resolver_cooldown_secs);
+    if opt.consensus > opt.resolvers.len() {
+        warn!("[consensus] --consensus {} but only {} resolver(s); votes will be among {}", opt.consensus, opt.resolvers.len(), opt.resolvers.len());
+    }
+    if opt.merge_resolvers > opt.resolvers.len() {
+        warn!("[merge] --merge-resolvers {} but only {} resolver(s); merging answers from {}", opt.merge_resolvers, opt.resolvers.len(), opt.resolvers.len());
+    }
+    if let Some(ip) = opt.source_ip {
+        crate::dns::check_source_ip(ip)?;
+        info!("[source] queries are sent from {}", ip);
+    }
+    resolver_pool.set_disable_policy(DisablePolicy { min_total: opt.resolver_disable_min_total, fail_ratio: opt.resolver_fail_ratio });
+    // seeded (or entropy-backed) RNG streams: resolver picks, wildcard probes, query ids
+    resolver_pool.set_rng(shared_rng(opt.seed, STREAM_RESOLVER));
+    let wild_rng = shared_rng(opt.seed, STREAM_WILDCARD);
+    #[allow(unused_mut)]
+    let mut query_opts = QueryOptions { a_only: opt.a_only, recursion_desired: opt.recursion_desired, checking_disabled: opt.checking_disabled, authentic_data: opt.authentic_data, dnssec_ok: opt.dnssec, recv_buffer: opt.recv_buffer.clamp(512, 65535), ecs: opt.ecs.first().copied(), source_ip: opt.source_ip, ..QueryOptions::new(shared_rng(opt.seed, STREAM_QUERY_ID)) };
+    #[cfg(target_os = "linux")]
+    { query_opts.raw = open_raw(&opt); }
+    #[cfg(not(target_os = "linux"))]
+    if opt.raw { warn!("[raw] --raw is Linux-only; using per-query UDP sockets"); }
+    // --ecs 多个子网：主查询带第一个，其余子网在主机解析成功后逐个补查
+    let ecs_opts: Arc<Vec<(String, QueryOptions)>> = Arc::new(if opt.ecs.len() > 1 {
+        opt.ecs.iter().skip(1).map(|s| (s.to_string(), QueryOptions { ecs: Some(*s), ..query_opts.clone() })).collect()
+    } else { Vec::new() });
+    if !opt.ecs.is_empty() {
+        info!("[ecs] client subnet(s): {}", opt.ecs.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", "));
+    }
     let base_resolvers = opt.resolvers.clone();
     // log when a resolver gets disabled by health heuristics
-    if !opt.pure_output {
-        resolver_pool.on_disable(move |addr| {
-            eprintln!("\n[resolver] disabled {}", addr);
-        });
-    }
-    if !opt.silent && opt.progress { spawn_reporter(metrics.clone(), opt.progress_interval, opt.progress_wide, opt.progress_color, opt.progress_legacy, Some(resolver_pool.clone())); }
+    resolver_pool.on_disable(move |addr| {
+        info!("[resolver] disabled {}", addr);
+    });
+    // --progress-stream 到 stderr 时不再输出 \r 进度行，避免与 JSON 事件混在一起
+    let stream_on_stderr = opt.progress_stream == Some(ProgressTarget::Stderr);
+    if !opt.silent && opt.progress && !stream_on_stderr { spawn_reporter(metrics.clone(), opt.progress_interval, opt.progress_wide, opt.progress_color, opt.progress_legacy, Some(resolver_pool.clone()), paused.clone()); }
     // progress json reporter
     if let (Some(path), interval) = (&opt.progress_json_file, opt.progress_json_interval) {
-        if interval > 0 && !opt.pure_output { spawn_json_reporter(metrics.clone(), interval, Some(resolver_pool.clone()), path.clone()); }
+        if interval > 0 { spawn_json_reporter(metrics.clone(), interval, Some(resolver_pool.clone()), path.clone()); }
     }
+    // progress event stream: one JSON snapshot per line every progress_interval
+    let progress_stream_task = match opt.progress_stream {
+        Some(target) => {
+            let out = target.open().with_context(|| format!("--progress-stream {:?}", target))?;
+            Some(spawn_progress_stream(metrics.clone(), opt.progress_interval, Some(resolver_pool.clone()), out))
+        }
+        None => None,
+    };
+    // 心跳：纯净模式下也写 stderr，stdout 只有结果
+    let heartbeat_task = (opt.heartbeat > 0).then(|| spawn_heartbeat(metrics.clone(), opt.heartbeat));
+    // 实际发送速率长期远低于配置值时提示一次 (纯净模式不提示)
+    if !opt.pure_output { spawn_rate_gap_watch(metrics.clone(), rl.clone(), 5, opt.concurrency); }
     // adaptive rate controller
     if opt.adaptive_rate && !opt.pure_output {
         let metrics_a = metrics.clone();
         let rl_a = rl.clone();
-        let min_r = opt.adaptive_min_rate;
-        let max_r = opt.adaptive_max_rate.max(opt.rate);
-        let err_thr = opt.adaptive_error_threshold.max(0.01);
-        let dec_f = opt.adaptive_dec_factor.clamp(0.1, 0.99);
-        let inc_f = opt.adaptive_inc_factor.clamp(1.0, 1.5);
+        let mut ctl = crate::ratelimit::AdaptiveRate::new(opt.adaptive_min_rate, opt.adaptive_max_rate.max(opt.rate), opt.adaptive_error_threshold, opt.adaptive_dec_factor, opt.adaptive_inc_factor);
         tokio::spawn(async move {
             use std::sync::atomic::Ordering;
+            use crate::ratelimit::{FloorEvent, ADAPTIVE_MIN_SAMPLE};
             let mut tick = tokio::time::interval(Duration::from_secs(opt.progress_interval.max(1)*2));
             let mut last_sent = metrics_a.sent.load(Ordering::Relaxed);
             let mut last_err = metrics_a.timeouts.load(Ordering::Relaxed)
@@ -113,36 +324,35 @@ pub async fn run(opt: Options) -> Result<()> {
                 let err_now = metrics_a.timeouts.load(Ordering::Relaxed)
                     + metrics_a.servfail.load(Ordering::Relaxed)
                     + metrics_a.refused.load(Ordering::Relaxed);
-                let d_sent = sent_now.saturating_sub(last_sent) as f64;
-                let d_err = err_now.saturating_sub(last_err) as f64;
+                let d_sent = sent_now.saturating_sub(last_sent);
+                let d_err = err_now.saturating_sub(last_err);
                 last_sent = sent_now; last_err = err_now;
-                if d_sent < 100.0 { continue; } // insufficient sample
-                let err_rate = d_err / d_sent;
+                if d_sent < ADAPTIVE_MIN_SAMPLE { continue; } // insufficient sample
+                let err_rate = d_err as f64 / d_sent as f64;
                 let current = rl_a.get_rate();
-                let new_rate = if err_rate > err_thr {
-                    (current as f64 * dec_f).floor() as i64
-                } else {
-                    (current as f64 * inc_f).ceil() as i64
-                }.clamp(min_r, max_r);
+                let (new_rate, event) = ctl.step(current, err_rate);
+                match event {
+                    Some(FloorEvent::Hit) => warn!("[adaptive] rate held at floor {} pps: error rate {:.0}% persistently above threshold (check resolvers / network)", ctl.min(), err_rate * 100.0),
+                    Some(FloorEvent::Left) => info!("[adaptive] errors subsided ({:.0}%), rate recovering from floor to {} pps", err_rate * 100.0, new_rate),
+                    None => {}
+                }
                 if new_rate != current { rl_a.set_rate(new_rate); }
             }
         });
     }
     // spawn periodic flush if configured
-    let flush_task = if let (Some(path), interval) = (&opt.status_file, opt.status_flush_interval) {
+    let mut flush_task = if let (Some(path), interval) = (&opt.status_file, opt.status_flush_interval) {
         if interval > 0 {
             let db = status_db.clone();
             let p = path.clone();
-            let silent = opt.silent;
-            let pure = opt.pure_output;
             Some(tokio::spawn(async move {
                 let mut tick = tokio::time::interval(Duration::from_secs(interval));
                 loop {
                     tick.tick().await;
                     if let Err(e) = crate::state::save_to_file(&db, &p).await {
-                        if !pure { eprintln!("[statusdb] periodic save error: {}", e); }
-                    } else if !silent && !pure {
-                        eprintln!("[statusdb] periodic saved to {}", p.display());
+                        warn!("[statusdb] periodic save error: {}", e);
+                    } else {
+                        debug!("[statusdb] periodic saved to {}", p.display());
                     }
                 }
             }))
@@ -169,332 +379,1378 @@ pub async fn run(opt: Options) -> Result<()> {
         } else { None }
     } else { None };
 
+    let takeover = if opt.takeover_check {
+        let m = crate::takeover::TakeoverMatcher::load(opt.takeover_fingerprints.as_deref())?;
+        info!("[takeover] {} fingerprint(s) loaded", m.len());
+        Some(Arc::new(m))
+    } else { None };
+
+    #[cfg(feature = "asn")]
+    let asn = match (&opt.asn_db, opt.asn_allow.is_empty()) {
+        (Some(path), false) => {
+            let t = crate::asn::AsnTable::load(path)?;
+            info!("[asn] {} range(s) loaded from {}, allowlist {:?}", t.len(), path.display(), opt.asn_allow);
+            Some(Arc::new(t))
+        }
+        (None, false) => anyhow::bail!("--asn needs an IP-to-ASN table (--asn-db <PATH>)"),
+        _ => None,
+    };
+
+    let hosts = if opt.use_hosts {
+        match crate::hosts::load_system_hosts() {
+            Ok(m) => { info!("[hosts] loaded {} name(s) from {}", m.len(), crate::hosts::system_hosts_path().display()); Some(Arc::new(m)) }
+            Err(e) => { warn!("[hosts] load error: {}", e); None }
+        }
+    } else { None };
+
+    // --authoritative: 每个根域解析出权威 NS，之后直接发 RD=0 查询，不经过递归解析器
+    let auth = if opt.authoritative && !opt.reverse {
+        let mut by_zone = HashMap::new();
+        for d in opt.domains.iter() {
+            let d = d.trim().trim_end_matches('.').to_ascii_lowercase();
+            let ips: Vec<String> = crate::dns::fetch_ns_ips(&d, &opt.resolvers, opt.timeout, opt.source_ip).await
+                .into_iter().filter(|ip| ip.parse::<std::net::Ipv4Addr>().is_ok()).collect();
+            if ips.is_empty() {
+                warn!("[auth] no authoritative NS address for {}, falling back to recursive resolvers", d);
+            } else {
+                info!("[auth] {} -> {}", d, ips.join(", "));
+                by_zone.insert(d, ips);
+            }
+        }
+        Some(Arc::new(AuthServers {
+            by_zone,
+            rng: shared_rng(opt.seed, STREAM_AUTH),
+            query_opts: QueryOptions { recursion_desired: false, ..query_opts.clone() },
+        }))
+    } else { None };
+
+    // --probe-ports: TCP connect 独立令牌桶，不占 DNS 速率
+    let probe_rate = if opt.probe_ports.is_empty() { None } else {
+        let prl = RateLimiter::new(opt.probe_rate.max(1));
+        prl.spawn_refill();
+        Some(prl.handle())
+    };
+
     let mut tasks = FuturesUnordered::new();
-    let writers = std::sync::Arc::new(build_writers(opt.output.clone(), &opt.output_type, !opt.not_print, opt.detail_records, opt.gzip, opt.append)?);
+    // --output-hash: 文件 sink 写入时累计，输出链关闭后由 HashWriter 取值
+    let output_digest = opt.output_hash.then(OutputDigest::default);
+    let mut writer_list = match writers {
+        Some(w) => w,
+        None => {
+            let fmt = FormatOptions {
+                detail: opt.detail_records,
+                no_result_marker: opt.no_result_marker.clone(),
+                skip_empty: opt.skip_empty,
+                roots: opt.domains.clone(),
+                timings: opt.timings,
+                digest: output_digest.clone(),
+            };
+            let mut w = build_writers(opt.output.clone(), &opt.output_type, !opt.not_print, opt.gzip, opt.gzip_index, opt.append, &fmt)?;
+            if let Some(dir) = &opt.split_by_rtype {
+                w.push(Box::new(SplitWriter::new(dir.clone(), opt.gzip, opt.append)?));
+            }
+            if opt.max_answers > 0 {
+                // 只截断输出，去重 / 状态在外层仍看到完整应答
+                w = vec![Box::new(AnswerCapWriter::new(Box::new(w), opt.max_answers))];
+            }
+            w
+        }
+    };
+    // 结果流 socket 与文件输出并列，外层的去重 / 排序同样作用于它
+    #[cfg(feature = "result-socket")]
+    if let Some(addr) = &opt.result_socket {
+        writer_list.push(Box::new(crate::resultsock::SocketWriter::bind(addr).await?));
+    }
+    if opt.output_flush_ms == 0 {
+        writer_list = vec![Box::new(LineFlushWriter::new(Box::new(writer_list)))];
+    }
+    if opt.dedup_output {
+        // --append: 已在输出文件中的子域不再追加 (gzip 文件先解压读取)
+        let known = match (&opt.output, opt.append) {
+            (Some(path), true) => {
+                let known = existing_subdomains(path, &opt.output_type, opt.gzip, opt.no_result_marker.as_deref())?;
+                info!("[append] {} subdomain(s) already in {}, only new results will be appended", known.len(), path.display());
+                known
+            }
+            _ => HashSet::new(),
+        };
+        writer_list = vec![Box::new(DedupWriter::with_known(writer_list, known))];
+    }
+    if opt.sort_output {
+        writer_list = vec![Box::new(SortingWriter::new(Box::new(writer_list)))];
+    }
+    // --partial-flush: 排序 / 表格输出要到结束才写，期间结果另存一份旁路文件
+    if opt.partial_flush > 0 {
+        if !(opt.sort_output || opt.output_type == "table") {
+            warn!("[output] --partial-flush only applies to --sort-output / table output; results are already written as they arrive");
+        } else if let Some(out) = &opt.output {
+            let side = crate::output::partial_path(out);
+            info!("[output] partial results every {}s to {} (removed when the scan completes)", opt.partial_flush, side.display());
+            writer_list.push(Box::new(PartialWriter::new(side, Duration::from_secs(opt.partial_flush))?));
+        } else {
+            anyhow::bail!("--partial-flush needs --output: the sidecar file is named after it");
+        }
+    }
+    // 根域构成的发现树：状态库 domain_level 记录层数，--tree 时结果附带 parent / depth
+    let tree = Arc::new(DiscoveryTree::new(&opt.domains));
+    if opt.tree {
+        writer_list = vec![Box::new(TreeWriter::new(tree.clone(), Box::new(writer_list)))];
+    }
+    // 工作任务只入队，写文件 / stdout 由单独的输出线程完成
+    let mut queued: Box<dyn OutputWriter> = Box::new(QueuedWriter::spawn(Box::new(writer_list))?);
+    // --output-hash: 输出线程结束 (文件全部写完) 后写出摘要
+    if let Some(digest) = output_digest {
+        match &opt.output {
+            Some(path) => queued = Box::new(HashWriter::new(queued, path.clone(), digest)),
+            None => anyhow::bail!("--output-hash needs --output: the digest is written next to the output file"),
+        }
+    }
+    let writers = std::sync::Arc::new(vec![queued]);
+    // 文件输出带缓冲，按 --output-flush-ms 周期刷盘；close() 时再刷一次
+    let output_flush_task = (opt.output_flush_ms > 0).then(|| {
+        let w = writers.clone();
+        let every = Duration::from_millis(opt.output_flush_ms);
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(every);
+            loop {
+                tick.tick().await;
+                for ow in w.iter() {
+                    if let Err(e) = ow.flush() { warn!("[output] flush error: {}", e); }
+                }
+            }
+        })
+    });
+
+    let ctx = HostCtx {
+        opt: Arc::new(opt.clone()),
+        writers: writers.clone(),
+        pool: resolver_pool.clone(),
+        status_db: status_db.clone(),
+        rl_sem: rl_sem.clone(),
+        active: active.clone(),
+        tree,
+        metrics: metrics.clone(),
+        discovered: discovered.clone(),
+        query_opts: query_opts.clone(),
+        hosts,
+        probe_rate,
+        auth,
+        takeover: takeover.clone(),
+        #[cfg(feature = "asn")]
+        asn,
+        ecs_opts,
+        ttl_cache: opt.respect_ttl.then(|| Arc::new(crate::ttlcache::TtlCache::new())),
+        paused: paused.clone(),
+        client: client.clone(),
+    };
+
+    for ip in reverse_targets.into_iter() {
+        ctx.wait_if_paused().await;
+        let permit = admit.clone().acquire_owned().await.unwrap();
+        if ctx.over_budget() { break; }
+        let ctx = ctx.clone();
+        tasks.push(tokio::spawn(async move {
+            let _p = permit;
+            reverse_host(ctx, ip).await;
+        }));
+    }
 
     // resolver pool created above
-    for domain in opt.domains.iter() {
-        let domain = domain.trim().trim_end_matches('.').to_string();
-        // wildcard detection per root domain
-        let wild_ips = match opt.wild_filter_mode.to_lowercase().as_str() {
-            "basic" => detect_wildcard(&domain, &base_resolvers, 3, opt.timeout),
-            "advanced" => crate::wildcard::detect_wildcard_advanced(&domain, &base_resolvers, 6, opt.timeout, 0.6),
-            _ => std::collections::HashSet::new(),
-        };
-        for w in words.iter() {
-            let sub = w;
-            let mut host = String::with_capacity(sub.len() + 1 + domain.len());
-            host.push_str(sub);
-            host.push('.');
-            host.push_str(&domain);
-            let permit = sem.clone().acquire_owned().await.unwrap();
-            // show_all: 是否输出失败/空/NXDOMAIN；only_alive=true 时仅输出有记录成功项
-            let show_all = !opt.not_print && !opt.only_alive;
-
-                let writers = writers.clone();
-            let pool_local = resolver_pool.clone();
-            let status_db_task = status_db.clone();
-            let wild_ips_local = wild_ips.clone();
-            let rl_sem_task = rl_sem.clone();
-            let metrics_task = metrics.clone();
-            let discovered_local = discovered.clone();
-            tasks.push(tokio::spawn(async move {
-                let _p = permit;
-                let mut attempt = 0i32;
-                let mut success = false;
-                let smart_protect = opt.retry == 0; // --retry 0 时，临时错误智能补偿一次
-                // cache check: skip if already known OK or wildcard
-                if let Some(it) = status_db_task.get(&host).await {
-                    if it.state == EntryState::Ok || it.state == EntryState::WildFiltered {
-                        metrics_task.skipped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        return;
-                    }
+    let forward_domains: Vec<String> = if opt.reverse { Vec::new() } else {
+        opt.domains.iter().map(|d| d.trim().trim_end_matches('.').to_string()).collect()
+    };
+    // --domain-chunk: 根域分批处理 (泛解析探测、派发、预测轮次)，批间共享状态库 / 解析器池 / 输出，
+    // 每批结束后释放该批的泛解析集合
+    let wild_params = DetectParams { threshold: opt.wildcard_threshold, probes: opt.wildcard_probes, source_ip: opt.source_ip, ..DetectParams::new(&opt.wild_filter_mode) };
+    let chunk_size = if opt.domain_chunk > 0 { opt.domain_chunk } else { forward_domains.len().max(1) };
+    let chunk_count = forward_domains.len().div_ceil(chunk_size);
+    // --recheck-failed 在所有批次之后运行，需要保留各根域的泛解析集合
+    let mut recheck_wild: HashMap<String, Arc<HashSet<String>>> = HashMap::new();
+    'chunks: for (ci, chunk) in forward_domains.chunks(chunk_size).enumerate() {
+        if ci > 0 {
+            if ctx.over_budget() { break; }
+            // total grows chunk by chunk so percent/ETA describe the work actually queued
+            metrics.total.fetch_add((words.len() as u64) * (chunk.len() as u64), Ordering::Relaxed);
+            // predict seeds tried on the previous chunk have not been tried on this one
+            *word_set.lock().unwrap() = words.iter().map(|w| w.to_ascii_lowercase()).collect();
+        }
+        if chunk_count > 1 { info!("[chunk] {}/{}: {} root domain(s)", ci + 1, chunk_count, chunk.len()); }
+        // wildcard detection for the chunk's root domains up front, concurrently; reused by predict rounds
+        let wild_started = std::time::Instant::now();
+        let wild_sets = detect_all(chunk, &wild_params, &base_resolvers, opt.timeout, &wild_rng, DETECT_PARALLEL, &client).await;
+        let wild_roots = wild_sets.values().filter(|s| !s.is_empty()).count();
+        info!("[wildcard] probed {} root domain(s) in {:.1}s, {} with wildcard records", chunk.len(), wild_started.elapsed().as_secs_f64(), wild_roots);
+        if opt.wildcard_report {
+            // 审计用：直接写 stderr，不受日志级别 / 纯净模式影响；命中结果照常输出并标记 wildcard
+            for domain in chunk.iter() {
+                let mut ips: Vec<&String> = wild_sets.get(domain).map(|s| s.iter().collect()).unwrap_or_default();
+                ips.sort();
+                let shown = if ips.is_empty() { "-".to_string() } else { ips.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(",") };
+                eprintln!("[wildcard-report] {}\t{}", domain, shown);
+            }
+        }
+        'dispatch: for domain in chunk.iter() {
+            if ctx.over_budget() { break; }
+            let wild_ips = wild_sets.get(domain).cloned().unwrap_or_default();
+            for w in words.iter() {
+                let sub = w;
+                let mut host = String::with_capacity(sub.len() + 1 + domain.len());
+                host.push_str(sub);
+                host.push('.');
+                host.push_str(domain);
+                ctx.wait_if_paused().await;
+                let permit = admit.clone().acquire_owned().await.unwrap();
+                // --max-queries: 预算用尽后不再派发新主机，已在途的任务照常完成
+                if ctx.over_budget() { break 'dispatch; }
+                let ctx = ctx.clone();
+                let wild_ips_local = wild_ips.clone();
+                tasks.push(tokio::spawn(async move {
+                    let _p = permit;
+                    scan_host(ctx, host, wild_ips_local).await;
+                }));
+            }
+        }
+
+        while let Some(res) = tasks.next().await {
+            if let Err(e) = res { error!("task join error: {}", e); }
+        }
+
+        // iterative dynamic predictor expansion
+        if opt.predict && opt.predict_rounds > 0 {
+            for _round in 0..opt.predict_rounds {
+                if ctx.over_budget() { break; }
+                let snapshot = discovered.lock().unwrap().clone();
+                if snapshot.is_empty() { break; }
+                let base = discovery::basic_seeds();
+                let mut new_seeds = normalize_labels(discovery::dynamic_extend(&snapshot, &base, opt.predict_topn.max(1)), opt.preserve_case);
+                new_seeds.retain(|s| !word_set.lock().unwrap().contains(&s.to_ascii_lowercase()));
+                if !opt.allow_invalid_labels {
+                    let before = new_seeds.len();
+                    new_seeds.retain(|s| valid_candidate(s));
+                    metrics.invalid_labels.fetch_add((before - new_seeds.len()) as u64, Ordering::Relaxed);
                 }
-                while opt.retry < 0 || attempt <= opt.retry || (smart_protect && attempt < 2) {
-                    attempt += 1;
-                    // 速率控制: 消耗一个令牌
-                    // 每个查询消耗一个令牌 (Semaphore 单次 acquire)
-                    let _rp = rl_sem_task.clone().acquire_owned().await.unwrap();
-                    // Prefer raw UDP DNS query against a random resolver; fallback to system resolver
-                    metrics_task.sent.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let resolver_opt = pool_local.choose_random();
-                    if let Some(resolver) = resolver_opt {
-                        let timeout_ms = opt.timeout * 1000;
-                        let h = host.clone();
-                        let r = resolver.clone();
-                        let fut = tokio::task::spawn_blocking(move || udp_query_full(&h, &r, timeout_ms));
-                        match timeout(Duration::from_secs(opt.timeout), fut).await {
-                            Ok(Ok(Ok(ans))) => {
-                                // classify by rcode for metrics and behavior
-                                let mut penalized = false;
-                                match ans.rcode.as_str() {
-                                    "NXDomain" => { metrics_task.nxdomain.fetch_add(1, std::sync::atomic::Ordering::Relaxed); }
-                                    "ServFail" => { metrics_task.servfail.fetch_add(1, std::sync::atomic::Ordering::Relaxed); penalized = true; }
-                                    "Refused" => { metrics_task.refused.fetch_add(1, std::sync::atomic::Ordering::Relaxed); penalized = true; }
-                                    "TIMEOUT" => { metrics_task.timeouts.fetch_add(1, std::sync::atomic::Ordering::Relaxed); penalized = true; }
-                                    _ => {}
-                                }
-                                if penalized { pool_local.report_fail(&resolver); }
-                                if ans.rcode == "NXDomain" {
-                                    // definitive negative answer: don't penalize resolver; no retry
-                                    success = false; break;
-                                }
-                                if !ans.records.is_empty() {
-                                    let mut ips: Vec<String> = ans.records.iter()
-                                        .filter(|r| r.rtype == "A" || r.rtype == "AAAA")
-                                        .map(|r| r.data.clone()).collect();
-                                    ips.sort(); ips.dedup();
-                                        if !is_wildcard(&ips, &wild_ips_local) {
-                                        let typed: Vec<ScanRecord> = ans.records.into_iter().map(|r| ScanRecord { rtype: r.rtype, data: r.data }).collect();
-                                        let res = ScanResult { subdomain: host.clone(), answers: ips, records: Some(typed) };
-                                        for ow in writers.iter() { let _ = ow.write(&res); }
-                                        metrics_task.ok.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                        let item = Item { domain: host.clone(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::Ok };
-                                        status_db_task.add(host.clone(), item).await;
-                                        pool_local.report_ok(&resolver);
-                                        discovered_local.lock().unwrap().push(host.clone());
-                                        success = true; break;
-                                    } else {
-                                        metrics_task.filtered.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                        let item = Item { domain: host.clone(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::WildFiltered };
-                                        status_db_task.add(host.clone(), item).await;
-                                        // wildcard filtered: treat as definitive non-result; stop retrying
-                                        break;
-                                    }
-                                } else {
-                                    // empty answer considered failure -> retry (penalize only if not already)
-                                    if !penalized { pool_local.report_fail(&resolver); }
-                                    let item = Item { domain: host.clone(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::Failed };
-                                    status_db_task.set(host.clone(), item).await;
-                                }
-                            }
-                            _ => { pool_local.report_fail(&resolver); /* timeout or join error -> retry */ }
-                        }
-                    } else {
-                        // fallback system resolver (unlikely since we supply defaults)
-                        metrics_task.fallback.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        let target = format!("{}:0", host);
-                        let dur = Duration::from_secs(opt.timeout);
-                        match timeout(dur, lookup_host(target)).await {
-                            Ok(Ok(addrs)) => {
-                                let mut ips: Vec<String> = addrs.map(|sa| sa.ip().to_string()).collect();
-                                ips.sort(); ips.dedup();
-                                let res = ScanResult { subdomain: host.clone(), answers: ips, records: None };
-                                for ow in writers.iter() { let _ = ow.write(&res); }
-                                let item = Item { domain: host.clone(), dns: "system".into(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::Ok };
-                                status_db_task.add(host.clone(), item).await;
-                                discovered_local.lock().unwrap().push(host.clone());
-                                success = true; break; // system path success not attributed to pool
-                            }
-                            _ => {}
-                        }
-                    }
-                    if opt.retry >= 0 && attempt > opt.retry {
-                        if smart_protect && attempt == 1 { continue; } else { break; }
+                if new_seeds.is_empty() { break; }
+                let additional = (new_seeds.len() as u64) * (chunk.len() as u64);
+                metrics.total.fetch_add(additional, std::sync::atomic::Ordering::Relaxed);
+                for domain in chunk.iter() {
+                    let wild_ips = wild_sets.get(domain).cloned().unwrap_or_default();
+                    for s in new_seeds.iter() {
+                        word_set.lock().unwrap().insert(s.to_ascii_lowercase());
+                        let mut host = String::with_capacity(s.len() + 1 + domain.len());
+                        host.push_str(s);
+                        host.push('.');
+                        host.push_str(domain);
+                        ctx.wait_if_paused().await;
+                        let permit = admit.clone().acquire_owned().await.unwrap();
+                        if ctx.over_budget() { break 'chunks; }
+                        let ctx = ctx.clone();
+                        let wild_ips_local = wild_ips.clone();
+                        tasks.push(tokio::spawn(async move {
+                            let _p = permit;
+                            scan_host(ctx, host, wild_ips_local).await;
+                        }));
                     }
                 }
-                if !success && show_all {
-                    let res = ScanResult { subdomain: host.clone(), answers: vec![], records: None };
-                    for ow in writers.iter() { let _ = ow.write(&res); }
-                    metrics_task.failed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let item = Item { domain: host.clone(), dns: "".into(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::Failed };
-                    status_db_task.set(host.clone(), item).await;
+                while let Some(res) = tasks.next().await {
+                    if let Err(e) = res { error!("task join error: {}", e); }
                 }
-            }));
+            }
         }
+        if opt.recheck_failed { recheck_wild.extend(wild_sets); }
     }
 
+    // a budget break can leave predict-round tasks undrained
     while let Some(res) = tasks.next().await {
-        if let Err(e) = res { eprintln!("task join error: {}", e); }
-    }
-
-    // iterative dynamic predictor expansion
-    if opt.predict && opt.predict_rounds > 0 {
-        for _round in 0..opt.predict_rounds {
-            let snapshot = discovered.lock().unwrap().clone();
-            if snapshot.is_empty() { break; }
-            let base = discovery::basic_seeds();
-            let mut new_seeds = discovery::dynamic_extend(&snapshot, &base, opt.predict_topn.max(1));
-            new_seeds.retain(|s| !word_set.lock().unwrap().contains(s));
-            if new_seeds.is_empty() { break; }
-            let additional = (new_seeds.len() as u64) * (opt.domains.len() as u64);
-            metrics.total.fetch_add(additional, std::sync::atomic::Ordering::Relaxed);
-            for domain in opt.domains.iter() {
-                let domain = domain.trim().trim_end_matches('.').to_string();
-                let wild_ips = match opt.wild_filter_mode.to_lowercase().as_str() {
-                    "basic" => detect_wildcard(&domain, &base_resolvers, 3, opt.timeout),
-                    "advanced" => crate::wildcard::detect_wildcard_advanced(&domain, &base_resolvers, 6, opt.timeout, 0.6),
-                    _ => std::collections::HashSet::new(),
-                };
-                for s in new_seeds.iter() {
-                    word_set.lock().unwrap().insert(s.clone());
-                    let mut host = String::with_capacity(s.len() + 1 + domain.len());
-                    host.push_str(s);
-                    host.push('.');
-                    host.push_str(&domain);
-                    let permit = sem.clone().acquire_owned().await.unwrap();
-                    let show_all = !opt.not_print && !opt.only_alive;
-                    let writers = writers.clone();
-                    let pool_local = resolver_pool.clone();
-                    let status_db_task = status_db.clone();
-                    let wild_ips_local = wild_ips.clone();
-                    let rl_sem_task = rl_sem.clone();
-                    let metrics_task = metrics.clone();
-                    let discovered_local = discovered.clone();
-                    tasks.push(tokio::spawn(async move {
-                        let _p = permit;
-                        let mut attempt = 0i32;
-                        let mut success = false;
-                        let smart_protect = opt.retry == 0; // 预测阶段同样启用智能补偿
-                        if let Some(it) = status_db_task.get(&host).await {
-                            if it.state == EntryState::Ok || it.state == EntryState::WildFiltered {
-                                metrics_task.skipped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                return;
-                            }
-                        }
-                        while opt.retry < 0 || attempt <= opt.retry || (smart_protect && attempt < 2) {
-                            attempt += 1;
-                            let _rp = rl_sem_task.clone().acquire_owned().await.unwrap();
-                            metrics_task.sent.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                            let resolver_opt = pool_local.choose_random();
-                            if let Some(resolver) = resolver_opt {
-                                let timeout_ms = opt.timeout * 1000;
-                                let h = host.clone();
-                                let r = resolver.clone();
-                                let fut = tokio::task::spawn_blocking(move || udp_query_full(&h, &r, timeout_ms));
-                                match timeout(Duration::from_secs(opt.timeout), fut).await {
-                                    Ok(Ok(Ok(ans))) => {
-                                        let mut penalized = false;
-                                        match ans.rcode.as_str() {
-                                            "NXDomain" => { metrics_task.nxdomain.fetch_add(1, std::sync::atomic::Ordering::Relaxed); }
-                                            "ServFail" => { metrics_task.servfail.fetch_add(1, std::sync::atomic::Ordering::Relaxed); penalized = true; }
-                                            "Refused" => { metrics_task.refused.fetch_add(1, std::sync::atomic::Ordering::Relaxed); penalized = true; }
-                                            "TIMEOUT" => { metrics_task.timeouts.fetch_add(1, std::sync::atomic::Ordering::Relaxed); penalized = true; }
-                                            _ => {}
-                                        }
-                                        if penalized { pool_local.report_fail(&resolver); }
-                                        if ans.rcode == "NXDomain" { success = false; break; }
-                                        if !ans.records.is_empty() {
-                                            let mut ips: Vec<String> = ans.records.iter().filter(|r| r.rtype == "A" || r.rtype == "AAAA").map(|r| r.data.clone()).collect();
-                                            ips.sort(); ips.dedup();
-                                            if !is_wildcard(&ips, &wild_ips_local) {
-                                                let typed: Vec<ScanRecord> = ans.records.into_iter().map(|r| ScanRecord { rtype: r.rtype, data: r.data }).collect();
-                                                let res = ScanResult { subdomain: host.clone(), answers: ips, records: Some(typed) };
-                                                for ow in writers.iter() { let _ = ow.write(&res); }
-                                                metrics_task.ok.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                                let item = Item { domain: host.clone(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::Ok };
-                                                status_db_task.add(host.clone(), item).await;
-                                                pool_local.report_ok(&resolver);
-                                                discovered_local.lock().unwrap().push(host.clone());
-                                                success = true; break;
-                                            } else {
-                                                metrics_task.filtered.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                                let item = Item { domain: host.clone(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::WildFiltered };
-                                                status_db_task.add(host.clone(), item).await;
-                                                // wildcard filtered: stop retrying further
-                                                break;
-                                            }
-                                        } else {
-                                            if !penalized { pool_local.report_fail(&resolver); }
-                                        }
-                                    }
-                                    _ => { pool_local.report_fail(&resolver); }
-                                }
-                            }
-                            if opt.retry >= 0 && attempt > opt.retry {
-                                if smart_protect && attempt == 1 { continue; } else { break; }
-                            }
-                        }
-                        if !success && show_all {
-                            let res = ScanResult { subdomain: host.clone(), answers: vec![], records: None };
-                            for ow in writers.iter() { let _ = ow.write(&res); }
-                            metrics_task.failed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                            let item = Item { domain: host.clone(), dns: "".into(), time: std::time::SystemTime::now(), retry: attempt as i32, domain_level: 0, state: EntryState::Failed };
-                            status_db_task.set(host.clone(), item).await;
-                        }
-                    }));
-                }
-            }
-            while let Some(res) = tasks.next().await {
-                if let Err(e) = res { eprintln!("task join error: {}", e); }
-            }
+        if let Err(e) = res { error!("task join error: {}", e); }
+    }
+    let recovered = if opt.recheck_failed && !opt.reverse && !ctx.over_budget() {
+        recheck_failed(&ctx, &admit, &recheck_wild).await
+    } else { 0 };
+    if let Some(m) = &takeover {
+        let hits = m.summary();
+        if !hits.is_empty() {
+            let total: u64 = hits.iter().map(|(_, n)| n).sum();
+            let by_service: Vec<String> = hits.iter().map(|(s, n)| format!("{}={}", s, n)).collect();
+            warn!("[takeover] {} candidate host(s) across {} service(s): {}", total, hits.len(), by_service.join(", "));
         }
     }
+    if opt.respect_ttl {
+        info!("[ttl] {} revisit(s) skipped within TTL", metrics.ttl_cached.load(Ordering::Relaxed));
+    }
+    if opt.host_timeout > 0 {
+        info!("[host-timeout] {} host(s) abandoned after {}s", metrics.host_timeouts.load(Ordering::Relaxed), opt.host_timeout);
+    }
+    if opt.cname_is_alive {
+        info!("[cname] {} dangling CNAME(s) kept as results", metrics.cname_only.load(Ordering::Relaxed));
+    }
+    let bind_errors = metrics.bind_errors.load(Ordering::Relaxed);
+    if bind_errors > 0 {
+        warn!("[udp] {} quer(ies) failed to bind a local UDP socket (ephemeral ports exhausted?); not counted against resolvers — lower --concurrency or widen the local port range", bind_errors);
+    }
+    if opt.consensus > 1 {
+        info!("[consensus] {} hit(s) with disagreeing resolvers, {} dropped without a majority", metrics.consensus_split.load(Ordering::Relaxed), metrics.consensus_rejected.load(Ordering::Relaxed));
+    }
+    let budget_hit = ctx.over_budget();
+    if budget_hit {
+        warn!("[budget] stopped after {} queries (--max-queries {}); unscanned hosts remain for the next run", metrics.sent.load(Ordering::Relaxed), opt.max_queries);
+    }
 
+    if let Some(t) = output_flush_task { t.abort(); }
     // close writers to ensure flush (gzip trailers etc.)
-    for ow in writers.iter() { let _ = ow.close(); }
+    for ow in writers.iter() {
+        if let Err(e) = ow.close() { warn!("[output] close error: {:#}", e); }
+    }
+    if let (true, Some(path)) = (opt.output_hash, &opt.output) {
+        info!("[output] SHA-256 written to {}", crate::output::hash_path(path).display());
+    }
 
-    // final flush
+    // final flush; stop the periodic saver first so it cannot recreate a cleaned-up file
+    if let Some(t) = flush_task.take() { t.abort(); }
     if let Some(path) = &opt.status_file {
-        if let Err(e) = crate::state::save_to_file(&status_db, path).await {
-            if !opt.pure_output { eprintln!("[statusdb] final save error: {}", e); }
+        // a budget stop is not a completed scan: keep the state for resuming
+        if opt.clean_state_on_success && !budget_hit {
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => info!("[statusdb] scan completed, removed {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("[statusdb] remove error: {}", e),
+            }
+        } else if let Err(e) = crate::state::save_to_file(&status_db, path).await {
+            warn!("[statusdb] final save error: {}", e);
         }
     }
     // final resolver stats output
     if let Some(path) = &opt.resolver_stats_file {
         if let Err(e) = tokio::fs::write(path, serde_json::to_vec_pretty(&resolver_pool.snapshot()).unwrap_or_default()).await {
-            if !opt.pure_output { eprintln!("[resolver] write stats error: {}", e); }
+            warn!("[resolver] write stats error: {}", e);
         }
     }
     // final progress json output (single snapshot) if configured
     if let Some(path) = &opt.progress_json_file {
-        use std::sync::atomic::Ordering;
-        use crate::metrics::ProgressSnapshot;
-        let total = metrics.total.load(Ordering::Relaxed);
-        let sent = metrics.sent.load(Ordering::Relaxed);
-        let ok = metrics.ok.load(Ordering::Relaxed);
-        let filtered = metrics.filtered.load(Ordering::Relaxed);
-        let failed = metrics.failed.load(Ordering::Relaxed);
-        let skipped = metrics.skipped.load(Ordering::Relaxed);
-        let nxdomain = metrics.nxdomain.load(Ordering::Relaxed);
-        let servfail = metrics.servfail.load(Ordering::Relaxed);
-        let refused = metrics.refused.load(Ordering::Relaxed);
-        let timeouts = metrics.timeouts.load(Ordering::Relaxed);
-        let finished = ok + filtered + failed + skipped;
-        let percent = if total > 0 { (finished as f64 / total as f64) * 100.0 } else { 0.0 };
-        let inflight = sent.saturating_sub(finished);
-        let elapsed = scan_start.elapsed().as_secs();
-        let (resolvers_active, resolvers_total, resolvers_disabled_pct) = {
-            let (a,t) = resolver_pool.counts();
-            let d = t.saturating_sub(a);
-            let pct = if t>0 { (d as f64)/(t as f64)*100.0 } else { 0.0 };
-            (Some(a as u64), Some(t as u64), Some(pct))
-        };
-        // Compute final snapshot rates from totals
-        let rate_from_total = if elapsed > 0 { (sent as f64) / (elapsed as f64) } else { 0.0 };
-        let remain = if total > finished { (total - finished) as f64 } else { 0.0 };
-        let eta_calc = if rate_from_total > 0.0 { (remain / rate_from_total) as u64 } else { 0 };
-        let err_sum = timeouts + servfail + refused;
-        let err_total = if sent > 0 { (err_sum as f64)/(sent as f64) } else { 0.0 };
-        let snap = ProgressSnapshot {
-            total,
-            sent,
-            ok,
-            filtered,
-            failed,
-            skipped,
-            nxdomain,
-            servfail,
-            refused,
-            timeouts,
-            rate: rate_from_total,
-            rate_avg: rate_from_total,
-            eta_secs: eta_calc,
-            percent,
-            inflight,
-            elapsed,
-            resolvers_active,
-            resolvers_total,
-            resolvers_disabled_pct,
-            error_rate_recent: 0.0,
-            error_rate_total: err_total,
-        };
+        let snap = final_snapshot(&metrics, &resolver_pool, scan_start.elapsed().as_secs(), true);
         if let Ok(data) = serde_json::to_vec_pretty(&snap) {
-            if let Err(e) = tokio::fs::write(path, data).await { if !opt.pure_output { eprintln!("[progress] write final json error: {}", e); } }
+            if let Err(e) = tokio::fs::write(path, data).await { warn!("[progress] write final json error: {}", e); }
+        }
+    }
+    // last progress event carries done=true so wrappers can tell completion from a stall
+    if let Some(t) = progress_stream_task {
+        t.abort();
+        let snap = final_snapshot(&metrics, &resolver_pool, scan_start.elapsed().as_secs(), false);
+        if let Some(Ok(mut out)) = opt.progress_stream.map(|t| t.open()) {
+            let _ = write_progress_event(&mut *out, &snap);
         }
     }
     // cancel periodic task (drop by abort)
+    if let Some(t) = heartbeat_task { t.abort(); }
     if let Some(t) = flush_task { t.abort(); }
     if let Some(t) = stats_task { t.abort(); }
-    Ok(())
+    if let Some(t) = signal_task { t.abort(); }
+    #[cfg(all(unix, feature = "control-socket"))]
+    if let Some(t) = control_task {
+        t.abort();
+        if let Some(path) = &opt.control_socket { let _ = std::fs::remove_file(path); }
+    }
+    let summary = ScanSummary {
+        found: metrics.ok.load(Ordering::Relaxed),
+        failed: metrics.failed.load(Ordering::Relaxed),
+        servfail: metrics.servfail.load(Ordering::Relaxed),
+        servfail_recovered: metrics.servfail_recovered.load(Ordering::Relaxed),
+        recovered,
+        ipv4: metrics.ipv4.load(Ordering::Relaxed),
+        ipv6: metrics.ipv6.load(Ordering::Relaxed),
+    };
+    if opt.recheck_failed {
+        info!("[done] found={} (ipv4={} ipv6={}) failed={} servfail={} (transient, recovered on retry: {}) recovered on recheck={}", summary.found, summary.ipv4, summary.ipv6, summary.failed, summary.servfail, summary.servfail_recovered, summary.recovered);
+    } else {
+        info!("[done] found={} (ipv4={} ipv6={}) failed={} servfail={} (transient, recovered on retry: {})", summary.found, summary.ipv4, summary.ipv6, summary.failed, summary.servfail, summary.servfail_recovered);
+    }
+    if let Some((primary, fallback)) = resolver_pool.tier_picks() {
+        info!("[resolver] queries by tier: primary={} fallback={}", primary, fallback);
+    }
+    Ok(summary)
+}
+
+/// `--recheck-failed` 的速率为主扫描的 1/N：失败多因拥塞，放慢后再试
+const RECHECK_RATE_DIVISOR: i64 = 4;
+
+/// Hosts admitted by the dispatch loop per concurrency slot. Admitted tasks queue for rate
+/// tokens without holding a slot, so the backlog keeps the limiter busy while slow queries are out.
+const ADMIT_FACTOR: usize = 4;
+
+/// `--recheck-failed`: one more query for every host the status DB holds as `Failed`, at a
+/// reduced rate. Hosts that now resolve are emitted and promoted to `Ok` by `query_once`.
+async fn recheck_failed(ctx: &HostCtx, admit: &Arc<Semaphore>, wild: &HashMap<String, Arc<HashSet<String>>>) -> u64 {
+    let mut hosts: Vec<String> = ctx.status_db.snapshot().await.into_iter()
+        .filter(|it| it.state == EntryState::Failed)
+        .map(|it| it.domain)
+        .collect();
+    if hosts.is_empty() { return 0; }
+    hosts.sort();
+    let rate = (ctx.opt.rate / RECHECK_RATE_DIVISOR).max(1);
+    info!("[recheck] re-querying {} failed host(s) at {} pps", hosts.len(), rate);
+    let rl = RateLimiter::new(rate);
+    rl.set_burst(ctx.opt.burst);
+    rl.spawn_refill();
+    let ctx = HostCtx { rl_sem: rl.handle(), ..ctx.clone() };
+    ctx.metrics.total.fetch_add(hosts.len() as u64, Ordering::Relaxed);
+    let recovered = Arc::new(AtomicU64::new(0));
+    let mut tasks = FuturesUnordered::new();
+    for host in hosts {
+        ctx.wait_if_paused().await;
+        let permit = admit.clone().acquire_owned().await.unwrap();
+        if ctx.over_budget() { break; }
+        let ctx = ctx.clone();
+        let wild_ips = wild_set_for(&host, wild);
+        let recovered = recovered.clone();
+        tasks.push(tokio::spawn(async move {
+            let _p = permit;
+            let mut rs = RetryState::default();
+            if matches!(query_once(&ctx, &host, &wild_ips, 1, &mut rs).await, Attempt::Resolved) {
+                recovered.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+    }
+    while let Some(res) = tasks.next().await {
+        if let Err(e) = res { error!("task join error: {}", e); }
+    }
+    let n = recovered.load(Ordering::Relaxed);
+    info!("[recheck] {} host(s) recovered", n);
+    n
+}
+
+/// Wildcard set of the closest enclosing root domain of `host` (empty when none matches).
+fn wild_set_for(host: &str, sets: &HashMap<String, Arc<HashSet<String>>>) -> Arc<HashSet<String>> {
+    let host = host.to_ascii_lowercase();
+    let mut rest = host.as_str();
+    loop {
+        if let Some(s) = sets.get(rest) { return s.clone(); }
+        match rest.split_once('.') {
+            Some((_, parent)) => rest = parent,
+            None => return Arc::default(),
+        }
+    }
+}
+
+/// `--raw`: the shared raw-socket transport, or `None` (with the reason logged) when the
+/// scan has to fall back to per-query UDP sockets.
+#[cfg(target_os = "linux")]
+fn open_raw(opt: &Options) -> Option<Arc<crate::rawsock::RawTransport>> {
+    if !opt.raw { return None; }
+    if !crate::device::has_cap_net_raw() {
+        warn!("[raw] CAP_NET_RAW missing (run as root or setcap cap_net_raw+ep); using per-query UDP sockets");
+        return None;
+    }
+    let source = match opt.source_ip {
+        Some(std::net::IpAddr::V4(ip)) => Some(ip),
+        Some(std::net::IpAddr::V6(ip)) => {
+            warn!("[raw] raw mode is IPv4-only but --source-ip is {}; using per-query UDP sockets", ip);
+            return None;
+        }
+        None => None,
+    };
+    match crate::rawsock::RawTransport::open(source) {
+        Ok(t) => {
+            info!("[raw] queries sent through a raw socket, replies on UDP port {}", t.port());
+            Some(t)
+        }
+        Err(e) => {
+            warn!("[raw] {:#}; using per-query UDP sockets", e);
+            None
+        }
+    }
+}
+
+/// `SIGUSR1` toggles pause, `SIGUSR2` resumes; no-op where Unix signals don't exist.
+#[cfg(unix)]
+fn spawn_pause_signals(paused: Arc<AtomicBool>) -> Option<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (mut usr1, mut usr2) = match (signal(SignalKind::user_defined1()), signal(SignalKind::user_defined2())) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("[signal] cannot install SIGUSR1/SIGUSR2 handlers: {}", e);
+            return None;
+        }
+    };
+    Some(tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(()) = usr1.recv() => {
+                    let now = !paused.fetch_xor(true, Ordering::Relaxed);
+                    info!("[signal] SIGUSR1: {}", if now { "paused" } else { "resumed" });
+                }
+                Some(()) = usr2.recv() => {
+                    paused.store(false, Ordering::Relaxed);
+                    info!("[signal] SIGUSR2: resumed");
+                }
+                else => break,
+            }
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+fn spawn_pause_signals(_paused: Arc<AtomicBool>) -> Option<tokio::task::JoinHandle<()>> {
+    None
+}
+
+/// Shared handles cloned into every per-host task.
+#[derive(Clone)]
+struct HostCtx {
+    opt: Arc<Options>,
+    writers: Arc<Vec<Box<dyn OutputWriter>>>,
+    pool: Arc<ResolverPool>,
+    status_db: Arc<StatusDb>,
+    rl_sem: Arc<Semaphore>,
+    /// `-c` slots, held only while a query is in flight (after the rate token is taken)
+    active: Arc<Semaphore>,
+    /// scanned root domains; gives each host its `domain_level`
+    tree: Arc<DiscoveryTree>,
+    metrics: Arc<Metrics>,
+    discovered: Arc<Mutex<Vec<String>>>,
+    query_opts: QueryOptions,
+    hosts: Option<Arc<HostsMap>>,
+    probe_rate: Option<Arc<Semaphore>>,
+    auth: Option<Arc<AuthServers>>,
+    takeover: Option<Arc<crate::takeover::TakeoverMatcher>>,
+    #[cfg(feature = "asn")]
+    asn: Option<Arc<crate::asn::AsnTable>>,
+    /// `--ecs` subnets after the first, each with its own query options
+    ecs_opts: Arc<Vec<(String, QueryOptions)>>,
+    ttl_cache: Option<Arc<crate::ttlcache::TtlCache>>,
+    paused: Arc<AtomicBool>,
+    client: Arc<dyn DnsClient>,
+}
+
+impl HostCtx {
+    /// `--max-queries` reached; a single atomic load so the dispatch loop stays cheap.
+    fn over_budget(&self) -> bool {
+        self.opt.max_queries > 0 && self.metrics.sent.load(Ordering::Relaxed) >= self.opt.max_queries
+    }
+
+    /// Hold the dispatch loop while paused; in-flight hosts are not affected.
+    async fn wait_if_paused(&self) {
+        while self.paused.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+/// `--authoritative` routing: root domain -> its authoritative NS addresses.
+struct AuthServers {
+    by_zone: HashMap<String, Vec<String>>,
+    rng: SharedRng,
+    /// same as the scan's options with RD cleared
+    query_opts: QueryOptions,
+}
+
+impl AuthServers {
+    /// Servers of the closest enclosing root domain of `host`, if any.
+    fn for_host(&self, host: &str) -> Option<&[String]> {
+        let host = host.to_ascii_lowercase();
+        let mut rest = host.as_str();
+        loop {
+            if let Some(v) = self.by_zone.get(rest) { return Some(v); }
+            rest = rest.split_once('.')?.1;
+        }
+    }
+
+    /// Random server, avoiding `exclude` when another one exists.
+    fn choose(&self, servers: &[String], exclude: Option<&str>) -> Option<String> {
+        use rand::seq::SliceRandom;
+        let others: Vec<&String> = servers.iter().filter(|s| Some(s.as_str()) != exclude).collect();
+        let mut g = self.rng.lock().unwrap();
+        if others.is_empty() { servers.choose(&mut *g).cloned() } else { others.choose(&mut *g).map(|s| s.to_string()) }
+    }
+}
+
+/// Outcome of a single query attempt for one host.
+enum Attempt {
+    /// Host resolved and was emitted.
+    Resolved,
+    /// Definitive non-result (NXDOMAIN, NODATA, wildcard): never retried.
+    Final,
+    /// Timeout / SERVFAIL / REFUSED: may draw on the transient budget.
+    Transient,
+    /// Any other failure: only retried within the hard `--retry` budget.
+    Failed,
+}
+
+/// Resolve one host with retries.
+/// `--retry` bounds the hard attempts (1 + retry); `--retry-transient` grants extra
+/// attempts only after transient errors. NXDOMAIN/NODATA stop immediately.
+async fn scan_host(ctx: HostCtx, host: String, wild_ips: Arc<HashSet<String>>) {
+    let opt = ctx.opt.clone();
+    // show_all: 是否输出失败/空/NXDOMAIN；only_alive=true 时仅输出有记录成功项
+    let show_all = !opt.not_print && !opt.only_alive;
+    // cache check: skip if already known OK or wildcard
+    if let Some(it) = ctx.status_db.get(&host).await {
+        if it.state == EntryState::Ok || it.state == EntryState::WildFiltered {
+            ctx.metrics.skipped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+    // --respect-ttl: the status DB entry may have aged out, the record itself has not
+    if ctx.ttl_cache.as_ref().is_some_and(|c| c.is_fresh(&host)) {
+        ctx.metrics.ttl_cached.fetch_add(1, Ordering::Relaxed);
+        ctx.metrics.skipped.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    // --use-hosts: 命中 hosts 文件直接合成结果，不发网络查询
+    if let Some(ips) = ctx.hosts.as_ref().and_then(|m| m.get(&host.to_ascii_lowercase())) {
+        let typed = ips.iter().map(|ip| ScanRecord { rtype: if ip.contains(':') { "AAAA".into() } else { "A".into() }, data: ip.clone(), ttl: None }).collect();
+        let res = ScanResult { subdomain: host.clone(), answers: ips.clone(), records: Some(typed), ..Default::default() };
+        for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+        ctx.metrics.ok.fetch_add(1, Ordering::Relaxed);
+        ctx.metrics.count_families(&res);
+        let item = Item { domain: host.clone(), dns: "hosts".into(), time: std::time::SystemTime::now(), retry: 0, domain_level: ctx.tree.level(&host), state: EntryState::Ok };
+        ctx.status_db.add(host.clone(), item).await;
+        ctx.discovered.lock().unwrap().push(host);
+        return;
+    }
+    let mut attempt = 0i32;
+    let mut transient_used = 0u32;
+    let mut success = false;
+    let mut definitive = false;
+    let mut rs = RetryState::default();
+    // --host-timeout: 单个主机所有尝试 (含排队等令牌) 的总耗时上限，--timeout 仍是单次查询超时
+    let deadline = (opt.host_timeout > 0).then(|| tokio::time::Instant::now() + Duration::from_secs(opt.host_timeout));
+    loop {
+        attempt += 1;
+        let outcome = match deadline {
+            Some(d) => match tokio::time::timeout_at(d, query_once(&ctx, &host, &wild_ips, attempt, &mut rs)).await {
+                Ok(o) => o,
+                Err(_) => {
+                    ctx.metrics.host_timeouts.fetch_add(1, Ordering::Relaxed);
+                    rs.negative = Some("TIMEOUT".into());
+                    break;
+                }
+            },
+            None => query_once(&ctx, &host, &wild_ips, attempt, &mut rs).await,
+        };
+        if rs.saw_servfail && matches!(outcome, Attempt::Resolved | Attempt::Final) {
+            ctx.metrics.servfail_recovered.fetch_add(1, Ordering::Relaxed);
+        }
+        match outcome {
+            Attempt::Resolved => { success = true; break; }
+            Attempt::Final => { definitive = true; break; }
+            _ if ctx.over_budget() => break,
+            outcome => {
+                let hard_used = attempt - transient_used as i32;
+                if opt.retry < 0 || hard_used <= opt.retry { continue; }
+                if matches!(outcome, Attempt::Transient) && transient_used < opt.retry_transient {
+                    transient_used += 1;
+                    continue;
+                }
+                break;
+            }
+        }
+    }
+    // retries ran out right after a SERVFAIL: the deferred penalty applies after all
+    if let Some(r) = rs.servfail_on.take() { ctx.pool.report_fail(&r); }
+    // --emit-negative: NXDOMAIN / SERVFAIL / REFUSED / TIMEOUT 单独输出，不受 only_alive 限制
+    let negative = if opt.emit_negative { rs.negative.take() } else { None };
+    let emit = !success && (show_all || negative.is_some());
+    if emit {
+        let res = ScanResult { subdomain: host.clone(), answers: vec![], records: None, rcode: negative, ..Default::default() };
+        for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+        ctx.metrics.failed.fetch_add(1, Ordering::Relaxed);
+    }
+    // 重试用尽 (超时 / SERVFAIL 等) 的主机也记为 Failed，供 --recheck-failed 与续扫使用
+    if emit || (!success && !definitive) {
+        let item = Item { domain: host.clone(), dns: "".into(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(&host), state: EntryState::Failed };
+        ctx.status_db.set(host.clone(), item).await;
+    }
+}
+
+/// Canonical name of a negative rcode as emitted by `--emit-negative`; `None` for anything else.
+pub(crate) fn negative_rcode(rcode: &str) -> Option<&'static str> {
+    match rcode {
+        "NXDomain" => Some("NXDOMAIN"),
+        "ServFail" => Some("SERVFAIL"),
+        "Refused" => Some("REFUSED"),
+        "TIMEOUT" => Some("TIMEOUT"),
+        _ => None,
+    }
+}
+
+/// Per-host retry bookkeeping carried across `query_once` attempts.
+#[derive(Default)]
+struct RetryState {
+    /// 上一次尝试用过的解析器：重试时换一个，避免慢/死解析器吃光重试预算
+    last_resolver: Option<String>,
+    /// resolver whose SERVFAIL is not yet held against it: the next attempt goes back
+    /// to it, and only a second failure there counts (SERVFAIL is often upstream flakiness)
+    servfail_on: Option<String>,
+    saw_servfail: bool,
+    /// 最近一次尝试的否定应答 (--emit-negative 输出用)，其他结果时清空
+    negative: Option<String>,
+}
+
+async fn query_once(ctx: &HostCtx, host: &str, wild_ips: &HashSet<String>, attempt: i32, rs: &mut RetryState) -> Attempt {
+    let opt = &ctx.opt;
+    // 速率控制: 每个发出的包消耗一个令牌 (A+AAAA 成对发出时为两个)，令牌不归还
+    take_lookup_tokens(ctx, &ctx.query_opts).await;
+    // 拿到令牌后才占并发槽；--host-timeout 到期时本函数被取消，槽随之释放
+    let _slot = ctx.active.clone().acquire_owned().await.unwrap();
+    // Prefer raw UDP DNS query against a random resolver; fallback to system resolver
+    ctx.metrics.sent.fetch_add(1, Ordering::Relaxed);
+    let auth = ctx.auth.as_ref().and_then(|a| a.for_host(host).map(|servers| (a, servers)));
+    let deferred = rs.servfail_on.take();
+    let pick = match (auth, deferred.as_deref(), rs.last_resolver.as_deref()) {
+        (Some((a, servers)), _, prev) => a.choose(servers, prev),
+        (None, Some(sf), _) => Some(sf.to_string()),
+        (None, None, Some(prev)) => ctx.pool.choose_random_excluding(prev),
+        (None, None, None) => ctx.pool.choose_random(),
+    };
+    // the SERVFAIL resolver was not picked again (authoritative routing): settle its penalty now
+    if let Some(sf) = deferred.as_deref().filter(|sf| pick.as_deref() != Some(*sf)) {
+        ctx.pool.report_fail(sf);
+    }
+    let Some(resolver) = pick else {
+        // fallback system resolver (unlikely since we supply defaults)
+        ctx.metrics.fallback.fetch_add(1, Ordering::Relaxed);
+        let target = format!("{}:0", host);
+        let dur = Duration::from_secs(opt.timeout);
+        if let Ok(Ok(addrs)) = timeout(dur, lookup_host(target)).await {
+            let mut ips: Vec<String> = addrs.map(|sa| sa.ip().to_string()).collect();
+            ips.sort(); ips.dedup();
+            let res = ScanResult { subdomain: host.to_string(), answers: ips, records: None, ..Default::default() };
+            for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+            let item = Item { domain: host.to_string(), dns: "system".into(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::Ok };
+            ctx.status_db.add(host.to_string(), item).await;
+            ctx.discovered.lock().unwrap().push(host.to_string());
+            return Attempt::Resolved; // system path success not attributed to pool
+        }
+        return Attempt::Failed;
+    };
+    rs.last_resolver = Some(resolver.clone());
+    let mut resolver = resolver;
+    let mut started = std::time::Instant::now();
+    let qo = match auth { Some((a, _)) => &a.query_opts, None => &ctx.query_opts };
+    let Some(mut ans) = query_with(ctx, host, &resolver, qo).await else {
+        rs.negative = Some("TIMEOUT".into());
+        ctx.pool.report_fail(&resolver); return Attempt::Transient; // timeout or join error
+    };
+    if ans.rcode == "Referral" {
+        // 该子域被委派到其他区域，权威服务器只给出 NS：改由递归解析器查询
+        let Some(r) = ctx.pool.choose_random() else { return Attempt::Transient };
+        take_lookup_tokens(ctx, &ctx.query_opts).await;
+        ctx.metrics.sent.fetch_add(1, Ordering::Relaxed);
+        resolver = r;
+        started = std::time::Instant::now();
+        let Some(followed) = query_resolver(ctx, host, &resolver).await else {
+            ctx.pool.report_fail(&resolver); return Attempt::Transient;
+        };
+        ans = followed;
+    }
+    let mut latency_ms = started.elapsed().as_millis() as u64;
+    // local socket exhaustion: retry later without penalizing the resolver
+    if ans.rcode == BIND_ERROR { return Attempt::Transient; }
+    // --cname-is-alive: a CNAME whose target is gone (NXDOMAIN) or broken (SERVFAIL) is still a
+    // finding (dangling CNAME); take the answer as is instead of dropping or retrying it
+    if opt.cname_is_alive && ans.rcode != "NoError"
+        && ans.records.iter().any(|r| r.rtype == "CNAME")
+        && !ans.records.iter().any(|r| r.rtype == "A" || r.rtype == "AAAA")
+    {
+        ctx.metrics.cname_only.fetch_add(1, Ordering::Relaxed);
+        ans.rcode = "NoError".into();
+    }
+    rs.negative = negative_rcode(&ans.rcode).map(str::to_string);
+    // classify by rcode for metrics and behavior
+    let transient = match ans.rcode.as_str() {
+        "NXDomain" => { ctx.metrics.nxdomain.fetch_add(1, Ordering::Relaxed); false }
+        "ServFail" => { ctx.metrics.servfail.fetch_add(1, Ordering::Relaxed); rs.saw_servfail = true; true }
+        "Refused" => { ctx.metrics.refused.fetch_add(1, Ordering::Relaxed); true }
+        "TIMEOUT" => { ctx.metrics.timeouts.fetch_add(1, Ordering::Relaxed); true }
+        _ => false,
+    };
+    if ans.rcode == "ServFail" && deferred.as_deref() != Some(resolver.as_str()) {
+        // first SERVFAIL from this resolver: retry it before penalizing
+        rs.servfail_on = Some(resolver.clone());
+    } else if transient {
+        ctx.pool.report_fail(&resolver);
+    }
+    if ans.rcode == "NXDomain" {
+        // definitive negative answer: don't penalize resolver; no retry
+        return Attempt::Final;
+    }
+    if ans.records.is_empty() {
+        let item = Item { domain: host.to_string(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::Failed };
+        ctx.status_db.set(host.to_string(), item).await;
+        if transient { return Attempt::Transient; }
+        // NOERROR without records (NODATA) is definitive as well
+        if ans.rcode == "NoError" { return Attempt::Final; }
+        ctx.pool.report_fail(&resolver);
+        return Attempt::Failed;
+    }
+    // --confirm-hits: a bulk resolver's hit only counts once a trusted resolver agrees
+    if opt.confirm_hits && !ctx.pool.is_trusted(&resolver) {
+        let Some(trusted) = ctx.pool.choose_trusted() else { return Attempt::Transient; };
+        take_lookup_tokens(ctx, &ctx.query_opts).await;
+        ctx.metrics.sent.fetch_add(1, Ordering::Relaxed);
+        let started = std::time::Instant::now();
+        match query_resolver(ctx, host, &trusted).await {
+            Some(confirm) if !confirm.records.is_empty() => {
+                ctx.pool.report_ok(&resolver);
+                ans = confirm;
+                resolver = trusted;
+                latency_ms = started.elapsed().as_millis() as u64;
+            }
+            Some(confirm) if confirm.rcode == BIND_ERROR => return Attempt::Transient,
+            Some(confirm) if confirm.rcode == "NoError" || confirm.rcode == "NXDomain" => {
+                // trusted resolver denies the record: the bulk answer was bogus
+                ctx.metrics.unconfirmed.fetch_add(1, Ordering::Relaxed);
+                ctx.pool.report_fail(&resolver);
+                let item = Item { domain: host.to_string(), dns: trusted.clone(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::Failed };
+                ctx.status_db.set(host.to_string(), item).await;
+                return Attempt::Final;
+            }
+            _ => { ctx.pool.report_fail(&trusted); return Attempt::Transient; }
+        }
+    }
+    // --consensus: the hit only counts once a majority of K distinct resolvers return the same IP set
+    if opt.consensus > 1 && auth.is_none() {
+        let Some((winner, agreed)) = consensus_vote(ctx, host, resolver, ans).await else {
+            ctx.metrics.consensus_rejected.fetch_add(1, Ordering::Relaxed);
+            let item = Item { domain: host.to_string(), dns: "".into(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::Failed };
+            ctx.status_db.set(host.to_string(), item).await;
+            return Attempt::Final;
+        };
+        resolver = winner;
+        ans = agreed;
+    }
+    // --merge-resolvers: union the records of K resolvers so addresses any single one omits still show up
+    let mut merged_from = None;
+    if opt.merge_resolvers > 1 && auth.is_none() {
+        let (_, peers) = peer_answers(ctx, host, &resolver, opt.merge_resolvers - 1).await;
+        let mut contributed = 1;
+        for (_, a) in peers {
+            if merge_records(&mut ans.records, a.records) { contributed += 1; }
+        }
+        merged_from = Some(contributed);
+    }
+    let mut ips: Vec<String> = ans.records.iter()
+        .filter(|r| r.rtype == "A" || r.rtype == "AAAA")
+        .map(|r| r.data.clone()).collect();
+    ips.sort(); ips.dedup();
+    let dnssec_validated = opt.dnssec.then_some(ans.authentic_data);
+    let rrsig = opt.dnssec.then_some(ans.rrsig);
+    let wildcard = is_wildcard(&ips, wild_ips);
+    if wildcard {
+        ctx.metrics.filtered.fetch_add(1, Ordering::Relaxed);
+        if opt.include_wildcard {
+            let typed: Vec<ScanRecord> = ans.records.into_iter().map(|r| ScanRecord { rtype: r.rtype, data: r.data, ttl: Some(r.ttl) }).collect();
+            let res = ScanResult {
+                subdomain: host.to_string(), answers: ips, records: Some(typed), wildcard: true,
+                resolver: opt.record_resolver().then(|| resolver.clone()), latency_ms: opt.timings.then_some(latency_ms),
+                dnssec_validated, rrsig,
+                ..Default::default()
+            };
+            for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+        }
+        let item = Item { domain: host.to_string(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::WildFiltered };
+        ctx.status_db.add(host.to_string(), item).await;
+        // wildcard filtered: treat as definitive non-result; stop retrying
+        return Attempt::Final;
+    }
+    #[cfg(feature = "asn")]
+    let asn = match &ctx.asn {
+        Some(table) => match table.matching(&ips, &opt.asn_allow) {
+            Some(n) => Some(n),
+            None => {
+                // 已解析但不在 ASN 白名单内：记为完成，不输出
+                ctx.metrics.asn_filtered.fetch_add(1, Ordering::Relaxed);
+                let item = Item { domain: host.to_string(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::Ok };
+                ctx.status_db.add(host.to_string(), item).await;
+                ctx.pool.report_ok(&resolver);
+                ctx.discovered.lock().unwrap().push(host.to_string());
+                return Attempt::Resolved;
+            }
+        },
+        None => None,
+    };
+    #[cfg(not(feature = "asn"))]
+    let asn = None;
+    let typed: Vec<ScanRecord> = ans.records.into_iter().map(|r| ScanRecord { rtype: r.rtype, data: r.data, ttl: Some(r.ttl) }).collect();
+    let open_ports = match &ctx.probe_rate {
+        Some(rate) => Some(crate::probe::open_ports(&ips, &opt.probe_ports, rate).await),
+        None => None,
+    };
+    let takeover_candidate = ctx.takeover.as_ref().and_then(|m| m.check(&typed));
+    if let Some(c) = &ctx.ttl_cache { c.insert(host, &typed); }
+    let ecs = if ctx.ecs_opts.is_empty() { None } else {
+        Some(ecs_answers(ctx, host, &resolver, &ips).await)
+    };
+    let res = ScanResult {
+        subdomain: host.to_string(), answers: ips, records: Some(typed), open_ports,
+        resolver: opt.record_resolver().then(|| resolver.clone()), latency_ms: opt.timings.then_some(latency_ms),
+        takeover_candidate,
+        asn,
+        ecs,
+        merged_from,
+        dnssec_validated,
+        rrsig,
+        ..Default::default()
+    };
+    for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+    ctx.metrics.ok.fetch_add(1, Ordering::Relaxed);
+    ctx.metrics.count_families(&res);
+    let item = Item { domain: host.to_string(), dns: resolver.clone(), time: std::time::SystemTime::now(), retry: attempt, domain_level: ctx.tree.level(host), state: EntryState::Ok };
+    ctx.status_db.add(host.to_string(), item).await;
+    ctx.pool.report_ok(&resolver);
+    ctx.discovered.lock().unwrap().push(host.to_string());
+    Attempt::Resolved
+}
+
+/// Reverse mode: PTR lookup for one IP, emitted as `ip -> hostname(s)`.
+async fn reverse_host(ctx: HostCtx, ip: std::net::IpAddr) {
+    let opt = ctx.opt.clone();
+    let show_all = !opt.not_print && !opt.only_alive;
+    let ip_str = ip.to_string();
+    let name = crate::reverse::ptr_name(&ip);
+    let max_attempts = if opt.retry < 0 { i32::MAX } else { opt.retry + 1 };
+    let mut last_resolver: Option<String> = None;
+    for _ in 0..max_attempts {
+        // 令牌被消耗而不归还：发包节奏由令牌桶补充速度决定
+        ctx.rl_sem.acquire().await.unwrap().forget();
+        let _slot = ctx.active.clone().acquire_owned().await.unwrap();
+        ctx.metrics.sent.fetch_add(1, Ordering::Relaxed);
+        let pick = match last_resolver.as_deref() {
+            Some(prev) => ctx.pool.choose_random_excluding(prev),
+            None => ctx.pool.choose_random(),
+        };
+        let Some(resolver) = pick else { break };
+        last_resolver = Some(resolver.clone());
+        let (n, r, qo, timeout_ms, client) = (name.clone(), resolver.clone(), ctx.query_opts.clone(), opt.timeout * 1000, ctx.client.clone());
+        let started = std::time::Instant::now();
+        let fut = tokio::task::spawn_blocking(move || client.query(&n, &r, RecordType::PTR, timeout_ms, &qo));
+        let ans = match timeout(Duration::from_secs(opt.timeout), fut).await {
+            Ok(Ok(Ok(ans))) => ans,
+            Ok(Ok(Err(e))) if e.is::<BindError>() => { ctx.metrics.bind_errors.fetch_add(1, Ordering::Relaxed); continue; }
+            _ => {
+                ctx.metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+                ctx.pool.report_fail(&resolver);
+                continue;
+            }
+        };
+        match ans.rcode.as_str() {
+            "NXDomain" => { ctx.metrics.nxdomain.fetch_add(1, Ordering::Relaxed); break; }
+            "ServFail" => { ctx.metrics.servfail.fetch_add(1, Ordering::Relaxed); ctx.pool.report_fail(&resolver); continue; }
+            "Refused" => { ctx.metrics.refused.fetch_add(1, Ordering::Relaxed); ctx.pool.report_fail(&resolver); continue; }
+            "TIMEOUT" => { ctx.metrics.timeouts.fetch_add(1, Ordering::Relaxed); ctx.pool.report_fail(&resolver); continue; }
+            _ => {}
+        }
+        ctx.pool.report_ok(&resolver);
+        let names: Vec<String> = ans.records.iter().filter(|r| r.rtype == "PTR").map(|r| r.data.clone()).collect();
+        if names.is_empty() { break; }
+        let typed = ans.records.into_iter().filter(|r| r.rtype == "PTR").map(|r| ScanRecord { rtype: r.rtype, data: r.data, ttl: Some(r.ttl) }).collect();
+        let res = ScanResult {
+            subdomain: ip_str, answers: names, records: Some(typed),
+            resolver: opt.record_resolver().then_some(resolver), latency_ms: opt.timings.then(|| started.elapsed().as_millis() as u64),
+            ..Default::default()
+        };
+        for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+        ctx.metrics.ok.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    ctx.metrics.failed.fetch_add(1, Ordering::Relaxed);
+    if show_all {
+        let res = ScanResult { subdomain: ip_str, ..Default::default() };
+        for ow in ctx.writers.iter() { let _ = ow.write(&res); }
+    }
+}
+
+/// `--ecs` with several subnets: the primary answer plus one query per extra subnet on the
+/// same resolver. Subnets whose query fails are left out rather than reported as empty.
+async fn ecs_answers(ctx: &HostCtx, host: &str, resolver: &str, primary: &[String]) -> Vec<EcsAnswers> {
+    let mut out = Vec::with_capacity(ctx.ecs_opts.len() + 1);
+    if let Some(first) = ctx.opt.ecs.first() {
+        out.push(EcsAnswers { subnet: first.to_string(), answers: primary.to_vec() });
+    }
+    for (subnet, qo) in ctx.ecs_opts.iter() {
+        take_lookup_tokens(ctx, qo).await;
+        ctx.metrics.sent.fetch_add(1, Ordering::Relaxed);
+        let Some(ans) = query_with(ctx, host, resolver, qo).await else { continue };
+        if ans.rcode != "NoError" { continue; }
+        let mut ips: Vec<String> = ans.records.into_iter()
+            .filter(|r| r.rtype == "A" || r.rtype == "AAAA")
+            .map(|r| r.data).collect();
+        ips.sort(); ips.dedup();
+        out.push(EcsAnswers { subnet: subnet.clone(), answers: ips });
+    }
+    out
+}
+
+/// Sorted, deduplicated A/AAAA addresses of an answer: the unit `--consensus` votes on.
+fn answer_ips(ans: &DnsAnswer) -> Vec<String> {
+    let mut ips: Vec<String> = ans.records.iter()
+        .filter(|r| r.rtype == "A" || r.rtype == "AAAA")
+        .map(|r| r.data.clone()).collect();
+    ips.sort(); ips.dedup();
+    ips
+}
+
+/// Index of the answer set held by a strict majority of `asked` resolvers, and whether the
+/// responses were unanimous. Resolvers that did not answer count against every set.
+fn majority(votes: &[Vec<String>], asked: usize) -> (Option<usize>, bool) {
+    let mut best = (0, 0usize);
+    for (i, v) in votes.iter().enumerate() {
+        let n = votes.iter().filter(|o| *o == v).count();
+        if n > best.1 { best = (i, n); }
+    }
+    let unanimous = best.1 == asked;
+    ((best.1 * 2 > asked).then_some(best.0), unanimous)
+}
+
+/// `--consensus K`: ask up to K-1 more distinct bulk resolvers and keep the answer whose
+/// IP set a majority agrees on. With fewer resolvers available the vote is among those.
+async fn consensus_vote(ctx: &HostCtx, host: &str, resolver: String, ans: DnsAnswer) -> Option<(String, DnsAnswer)> {
+    let (asked, peers) = peer_answers(ctx, host, &resolver, ctx.opt.consensus - 1).await;
+    let asked = 1 + asked;
+    let mut voters = vec![(resolver, ans)];
+    // NXDOMAIN / NODATA is a vote for the empty set
+    voters.extend(peers);
+    let votes: Vec<Vec<String>> = voters.iter().map(|(_, a)| answer_ips(a)).collect();
+    let (winner, unanimous) = majority(&votes, asked);
+    if !unanimous { ctx.metrics.consensus_split.fetch_add(1, Ordering::Relaxed); }
+    let w = winner?;
+    // first voter of the winning set: the primary answer when it is in the majority;
+    // an empty set with no records means the majority says the name does not resolve
+    if voters[w].1.records.is_empty() { return None; }
+    Some(voters.swap_remove(w))
+}
+
+/// Query up to `n` more distinct bulk resolvers (besides `resolver`) in parallel, each taking
+/// its rate permits. Returns how many were asked and the definitive (NOERROR / NXDOMAIN) replies;
+/// the others count against their resolver.
+async fn peer_answers(ctx: &HostCtx, host: &str, resolver: &str, n: usize) -> (usize, Vec<(String, DnsAnswer)>) {
+    let peers = ctx.pool.choose_distinct(n, resolver);
+    let asked = peers.len();
+    let replies = futures::future::join_all(peers.into_iter().map(|peer| async move {
+        take_lookup_tokens(ctx, &ctx.query_opts).await;
+        ctx.metrics.sent.fetch_add(1, Ordering::Relaxed);
+        let ans = query_resolver(ctx, host, &peer).await;
+        (peer, ans)
+    })).await;
+    let mut out = Vec::with_capacity(asked);
+    for (peer, reply) in replies {
+        match reply {
+            Some(a) if a.rcode == "NoError" || a.rcode == "NXDomain" => { ctx.pool.report_ok(&peer); out.push((peer, a)); }
+            Some(a) if a.rcode == BIND_ERROR => {}
+            _ => ctx.pool.report_fail(&peer),
+        }
+    }
+    (asked, out)
+}
+
+/// `--merge-resolvers`: append `extra` records not already in `into` (same type and data);
+/// true when `extra` had any records at all, i.e. the resolver contributed to the result.
+fn merge_records(into: &mut Vec<RawRecord>, extra: Vec<RawRecord>) -> bool {
+    let contributed = !extra.is_empty();
+    for r in extra {
+        if !into.iter().any(|x| x.rtype == r.rtype && x.data.eq_ignore_ascii_case(&r.data)) { into.push(r); }
+    }
+    contributed
+}
+
+/// Take the rate tokens for one host lookup: one per packet on the wire, so two when A and
+/// AAAA are pipelined (everything but `--a-only`). Tokens are consumed, not returned, so the
+/// refill pace is the send pace; taken one at a time, since the bucket may hold just one.
+async fn take_lookup_tokens(ctx: &HostCtx, qo: &QueryOptions) {
+    let n = if qo.a_only { 1 } else { 2 };
+    for _ in 0..n {
+        ctx.rl_sem.acquire().await.unwrap().forget();
+    }
+}
+
+/// Run a host lookup (`ctx.client`) against one resolver on the blocking pool, bounded by `--timeout`.
+async fn query_resolver(ctx: &HostCtx, host: &str, resolver: &str) -> Option<DnsAnswer> {
+    query_with(ctx, host, resolver, &ctx.query_opts).await
+}
+
+async fn query_with(ctx: &HostCtx, host: &str, resolver: &str, qo: &QueryOptions) -> Option<DnsAnswer> {
+    let opt = &ctx.opt;
+    let timeout_ms = opt.timeout * 1000;
+    let h = host.to_string();
+    let r = resolver.to_string();
+    let qo = qo.clone();
+    let client = ctx.client.clone();
+    let fut = tokio::task::spawn_blocking(move || client.query(&h, &r, RecordType::A, timeout_ms, &qo));
+    match timeout(Duration::from_secs(opt.timeout), fut).await {
+        Ok(Ok(Ok(ans))) => Some(ans),
+        // the query never left this host: surface it as a pseudo-rcode so callers don't blame the resolver
+        Ok(Ok(Err(e))) if e.is::<BindError>() => {
+            ctx.metrics.bind_errors.fetch_add(1, Ordering::Relaxed);
+            Some(DnsAnswer { rcode: BIND_ERROR.into(), ..Default::default() })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn read_wordlist_merges_and_dedups_files() {
+        let mut a = tempfile::NamedTempFile::new().unwrap();
+        let mut b = tempfile::NamedTempFile::new().unwrap();
+        writeln!(a, "# cloud\nwww\n\n  api  \ns3").unwrap();
+        writeln!(b, "k8s\napi\n# comment\nwww\ningress").unwrap();
+        let words = read_wordlist(&[a.path().to_path_buf(), b.path().to_path_buf()]).await.unwrap();
+        assert_eq!(words, ["www", "api", "s3", "k8s", "ingress"]);
+    }
+
+    #[tokio::test]
+    async fn read_wordlist_decompresses_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.txt.gz");
+        let mut gz = flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+        gz.write_all(b"www
+# mail
+api
+
+www
+").unwrap();
+        gz.finish().unwrap();
+        let words = read_wordlist(&[path]).await.unwrap();
+        assert_eq!(words, ["www", "api"]);
+    }
+
+    #[test]
+    fn normalize_labels_dedups_case_insensitively() {
+        let raw: Vec<String> = ["API", "api", " Api. ", "www", ".WWW", "", "Dev"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(normalize_labels(raw.clone(), false), ["api", "www", "dev"]);
+        assert_eq!(normalize_labels(raw, true), ["API", "www", "Dev"]);
+    }
+
+    #[test]
+    fn auth_servers_route_by_closest_root() {
+        let mut by_zone = HashMap::new();
+        by_zone.insert("example.com".to_string(), vec!["192.0.2.1".to_string(), "192.0.2.2".to_string()]);
+        by_zone.insert("dev.example.com".to_string(), vec!["192.0.2.9".to_string()]);
+        let auth = AuthServers { by_zone, rng: shared_rng(Some(1), STREAM_AUTH), query_opts: QueryOptions::new(shared_rng(Some(1), STREAM_QUERY_ID)) };
+        assert_eq!(auth.for_host("WWW.example.com").unwrap().len(), 2);
+        assert_eq!(auth.for_host("api.dev.example.com").unwrap(), ["192.0.2.9".to_string()]);
+        assert!(auth.for_host("www.example.org").is_none());
+        for _ in 0..16 {
+            assert_eq!(auth.choose(auth.for_host("a.example.com").unwrap(), Some("192.0.2.1")).as_deref(), Some("192.0.2.2"));
+        }
+    }
+
+    /// Per-host context around a single resolver, with results going nowhere.
+    fn host_ctx(opt: Options, resolver: String) -> HostCtx {
+        let rl = RateLimiter::new(1000);
+        rl.spawn_refill();
+        HostCtx {
+            active: Arc::new(Semaphore::new(opt.concurrency)),
+            tree: Arc::new(DiscoveryTree::new(&opt.domains)),
+            opt: Arc::new(opt),
+            writers: Arc::new(Vec::new()),
+            pool: ResolverPool::new(vec![resolver]),
+            status_db: StatusDb::create_memory_db_no_expiry(),
+            rl_sem: rl.handle(),
+            metrics: Metrics::new(),
+            discovered: Arc::new(Mutex::new(Vec::new())),
+            query_opts: QueryOptions::new(shared_rng(Some(1), STREAM_QUERY_ID)),
+            hosts: None,
+            probe_rate: None,
+            auth: None,
+            takeover: None,
+            #[cfg(feature = "asn")]
+            asn: None,
+            ecs_opts: Arc::new(Vec::new()),
+            ttl_cache: None,
+            paused: Arc::new(AtomicBool::new(false)),
+            client: Arc::new(UdpClient),
+        }
+    }
+
+    #[tokio::test]
+    async fn host_timeout_caps_time_across_retries() {
+        // a resolver that never answers: every attempt runs into the 1s per-query timeout
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = silent.local_addr().unwrap().to_string();
+        let opt = |retry: i32, host_timeout: u64| Options { timeout: 1, retry, retry_transient: 0, host_timeout, ..Default::default() };
+
+        // within budget: both attempts run out on their own
+        let ctx = host_ctx(opt(1, 10), server.clone());
+        let started = std::time::Instant::now();
+        scan_host(ctx.clone(), "www.example.com".into(), Arc::default()).await;
+        assert!(started.elapsed() >= Duration::from_secs(2), "took {:?}", started.elapsed());
+        assert_eq!(ctx.metrics.sent.load(Ordering::Relaxed), 2);
+        assert_eq!(ctx.metrics.host_timeouts.load(Ordering::Relaxed), 0);
+        assert_eq!(ctx.status_db.get("www.example.com").await.unwrap().state, EntryState::Failed);
+
+        // over budget: six 1s attempts are cut off after 2s and the host is recorded as failed
+        let ctx = host_ctx(opt(5, 2), server);
+        let started = std::time::Instant::now();
+        scan_host(ctx.clone(), "api.example.com".into(), Arc::default()).await;
+        assert!(started.elapsed() < Duration::from_secs(3), "took {:?}", started.elapsed());
+        assert!(ctx.metrics.sent.load(Ordering::Relaxed) < 6);
+        assert_eq!(ctx.metrics.host_timeouts.load(Ordering::Relaxed), 1);
+        assert_eq!(ctx.status_db.get("api.example.com").await.unwrap().state, EntryState::Failed);
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn concurrency_slot_is_held_only_while_a_query_is_in_flight() {
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = silent.local_addr().unwrap().to_string();
+        let opt = Options { concurrency: 1, timeout: 5, retry: 0, retry_transient: 0, host_timeout: 1, ..Default::default() };
+
+        // waiting for a rate token does not take the slot
+        let starved = HostCtx { rl_sem: Arc::new(Semaphore::new(0)), ..host_ctx(opt.clone(), server.clone()) };
+        let waiting = tokio::spawn(scan_host(starved.clone(), "www.example.com".into(), Arc::default()));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(starved.active.available_permits(), 1);
+        waiting.abort();
+
+        // a stuck resolver holds the slot until --host-timeout, not for the full --timeout
+        let ctx = host_ctx(opt, server);
+        let started = std::time::Instant::now();
+        let stuck = tokio::spawn(scan_host(ctx.clone(), "api.example.com".into(), Arc::default()));
+        while ctx.metrics.sent.load(Ordering::Relaxed) == 0 { tokio::time::sleep(Duration::from_millis(20)).await; }
+        assert_eq!(ctx.active.available_permits(), 0);
+        stuck.await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3), "took {:?}", started.elapsed());
+        assert_eq!(ctx.active.available_permits(), 1);
+        assert_eq!(ctx.metrics.host_timeouts.load(Ordering::Relaxed), 1);
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn scan_over_mock_client_retries_and_filters_wildcards() {
+        use crate::mockdns::{MockDnsClient, MockReply};
+        let mut words = tempfile::NamedTempFile::new().unwrap();
+        writeln!(words, "www\nflaky\napi\nWWW").unwrap();
+        let dns = Arc::new(MockDnsClient::default()
+            .answer("www.example.com", &["192.0.2.1"])
+            .script("flaky.example.com", vec![MockReply::Timeout, MockReply::a(&["192.0.2.9"])])
+            .wildcard("example.com", &["198.51.100.7"]));
+        let opt = Options {
+            domains: vec!["example.com".into()],
+            resolvers: vec!["192.0.2.53".into()],
+            filenames: vec![words.path().to_path_buf()],
+            heuristic: false,
+            timeout: 1,
+            ..Default::default()
+        };
+        let sink = crate::output::VecWriter::new(Default::default());
+        let results = sink.results();
+        let summary = run_with_client(opt, vec![Box::new(sink)], dns.clone()).await.unwrap();
+        let mut found: Vec<(String, Vec<String>)> = results.lock().unwrap().iter().map(|r| (r.subdomain.clone(), r.answers.clone())).collect();
+        found.sort();
+        assert_eq!(found, [
+            ("flaky.example.com".to_string(), vec!["192.0.2.9".to_string()]),
+            ("www.example.com".to_string(), vec!["192.0.2.1".to_string()]),
+        ]);
+        assert_eq!(summary.found, 2);
+        // WWW folds into www; the timed-out lookup was retried once
+        assert_eq!(dns.queries("www.example.com"), 1);
+        assert_eq!(dns.queries("flaky.example.com"), 2);
+        assert_eq!(dns.queries("api.example.com"), 1);
+    }
+
+    /// Records when each lookup reaches the transport; every name is NXDOMAIN.
+    #[derive(Default)]
+    struct SendLog(Mutex<Vec<std::time::Instant>>);
+
+    impl DnsClient for SendLog {
+        fn query(&self, _: &str, _: &str, _: RecordType, _: u64, _: &QueryOptions) -> Result<DnsAnswer> {
+            self.0.lock().unwrap().push(std::time::Instant::now());
+            Ok(DnsAnswer { rcode: "NXDomain".into(), ..Default::default() })
+        }
+    }
+
+    /// Lookups per second seen by the transport over a 40-word scan at `rate` pps.
+    async fn observed_lookup_rate(rate: i64, a_only: bool) -> f64 {
+        let mut words = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..40 { writeln!(words, "w{}", i).unwrap(); }
+        let log = Arc::new(SendLog::default());
+        let opt = Options {
+            domains: vec!["example.com".into()],
+            resolvers: vec!["192.0.2.53".into()],
+            filenames: vec![words.path().to_path_buf()],
+            heuristic: false,
+            wild_filter_mode: "off".into(),
+            rate,
+            a_only,
+            timeout: 1,
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        run_with_client(opt, Vec::new(), log.clone()).await.unwrap();
+        let sent = log.0.lock().unwrap();
+        assert_eq!(sent.len(), 40);
+        // measured from the start: a pair's two tokens may be granted in different ticks
+        sent.len() as f64 / sent.iter().max().unwrap().duration_since(started).as_secs_f64()
+    }
+
+    #[tokio::test]
+    async fn lookups_are_paced_by_the_rate_limit() {
+        // one token per packet: 100 pps is 100 A-only lookups/s, or 50 pipelined A+AAAA lookups/s;
+        // the bucket starts empty but the first tick's 10 tokens come at once: allow some slack
+        let a_only = observed_lookup_rate(100, true).await;
+        assert!(a_only <= 140.0, "{:.0} A-only lookups/s at 100 pps", a_only);
+        let paired = observed_lookup_rate(100, false).await;
+        assert!(paired <= 70.0, "{:.0} A+AAAA lookups/s at 100 pps", paired);
+    }
+
+    #[test]
+    fn recheck_uses_wildcard_set_of_closest_root() {
+        let mut sets = HashMap::new();
+        sets.insert("example.com".to_string(), Arc::new(HashSet::from(["192.0.2.1".to_string()])));
+        sets.insert("dev.example.com".to_string(), Arc::new(HashSet::from(["192.0.2.9".to_string()])));
+        assert!(wild_set_for("WWW.example.com", &sets).contains("192.0.2.1"));
+        assert!(wild_set_for("api.dev.example.com", &sets).contains("192.0.2.9"));
+        assert!(wild_set_for("www.example.org", &sets).is_empty());
+    }
+
+    #[test]
+    fn shards_partition_the_candidate_list() {
+        let words: Vec<String> = (0..101).map(|i| format!("w{}", i)).collect();
+        let shards: Vec<Vec<String>> = (0..8).map(|i| shard_words(words.clone(), i, 8)).collect();
+        assert!(shards.iter().all(|s| s.len() == 12 || s.len() == 13), "{:?}", shards.iter().map(Vec::len).collect::<Vec<_>>());
+        assert_eq!(shards[3][..2], ["w3", "w11"]);
+        let mut all: Vec<String> = shards.concat();
+        all.sort();
+        let mut expect = words.clone();
+        expect.sort();
+        assert_eq!(all, expect);
+        assert_eq!(shard_words(words.clone(), 0, 1), words);
+    }
+
+    #[test]
+    fn valid_candidate_applies_label_rules() {
+        for ok in ["www", "_dmarc", "_sip._tcp", "dev-01", "a.b", &"x".repeat(63)] {
+            assert!(valid_candidate(ok), "{ok} should be valid");
+        }
+        for bad in ["-www", "www-", "foo bar", "a..b", "ex@mple", "", &"x".repeat(64)] {
+            assert!(!valid_candidate(bad), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn merge_records_unions_without_duplicates() {
+        let rr = |t: &str, d: &str| RawRecord { rtype: t.into(), data: d.into(), ttl: 60 };
+        let mut into = vec![rr("CNAME", "edge.example.net."), rr("A", "192.0.2.1")];
+        assert!(merge_records(&mut into, vec![rr("CNAME", "EDGE.example.net."), rr("A", "192.0.2.2"), rr("A", "192.0.2.1")]));
+        assert!(!merge_records(&mut into, Vec::new()), "an empty answer does not contribute");
+        let got: Vec<&str> = into.iter().map(|r| r.data.as_str()).collect();
+        assert_eq!(got, ["edge.example.net.", "192.0.2.1", "192.0.2.2"]);
+    }
+
+    #[test]
+    fn majority_needs_more_than_half_of_asked() {
+        let a = vec!["192.0.2.1".to_string()];
+        let b = vec!["198.51.100.1".to_string()];
+        assert_eq!(majority(&[a.clone(), a.clone(), a.clone()], 3), (Some(0), true));
+        assert_eq!(majority(&[b.clone(), a.clone(), a.clone()], 3), (Some(1), false));
+        // one of three timed out: 2 of 3 asked still agree
+        assert_eq!(majority(&[a.clone(), a.clone()], 3), (Some(0), false));
+        assert_eq!(majority(&[a.clone(), b.clone()], 2), (None, false));
+        assert_eq!(majority(std::slice::from_ref(&a), 3), (None, false), "a lone answer is not a majority");
+    }
 }
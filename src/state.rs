@@ -40,23 +40,11 @@ pub struct StatusDb {
 }
 
 impl StatusDb {
+    /// Entries expire after 5 minutes; a background task sweeps them every 3 minutes until
+    /// [`close`](Self::close). For long-lived use; needs a Tokio runtime.
     pub fn create_memory_db() -> Arc<Self> {
-        let shard_count = 64usize;
-        let mut shards = Vec::with_capacity(shard_count);
-        for _ in 0..shard_count {
-            shards.push(Arc::new(DbShard { items: RwLock::new(HashMap::new()) }));
-        }
-
         let (tx, mut rx) = watch::channel(false);
-
-        let db = Arc::new(StatusDb {
-            shards,
-            shard_count,
-            length: AtomicI64::new(0),
-            expiration: Duration::from_secs(5 * 60),
-            cleanup_interval: Duration::from_secs(3 * 60),
-            stop_tx: Some(tx),
-        });
+        let db = Arc::new(Self::with_shards(Some(tx)));
 
         // spawn cleanup task
         let cloned = db.clone();
@@ -83,6 +71,28 @@ impl StatusDb {
         db
     }
 
+    /// Entries are kept until the DB is dropped and no cleanup task is spawned: a single scan's
+    /// state lives exactly as long as the run, and expiry could only evict live results.
+    pub fn create_memory_db_no_expiry() -> Arc<Self> {
+        Arc::new(Self::with_shards(None))
+    }
+
+    fn with_shards(stop_tx: Option<watch::Sender<bool>>) -> Self {
+        let shard_count = 64usize;
+        let mut shards = Vec::with_capacity(shard_count);
+        for _ in 0..shard_count {
+            shards.push(Arc::new(DbShard { items: RwLock::new(HashMap::new()) }));
+        }
+        StatusDb {
+            shards,
+            shard_count,
+            length: AtomicI64::new(0),
+            expiration: Duration::from_secs(5 * 60),
+            cleanup_interval: Duration::from_secs(3 * 60),
+            stop_tx,
+        }
+    }
+
     async fn cleanup(&self) {
         let now = SystemTime::now();
         let threshold = now.checked_sub(self.expiration).unwrap_or(SystemTime::UNIX_EPOCH);
@@ -217,6 +227,16 @@ fn string_to_state(s: &str) -> EntryState {
     }
 }
 
+/// 默认状态文件名按目标集合区分：`.rusub-state-<hash>.json`，
+/// 避免扫描其他域名时误用上一次的进度。域名顺序与大小写不影响结果。
+pub fn default_state_path(domains: &[String]) -> std::path::PathBuf {
+    let mut keys: Vec<String> = domains.iter().map(|d| d.trim().trim_end_matches('.').to_ascii_lowercase()).collect();
+    keys.sort();
+    keys.dedup();
+    let hash = crate::manifest::sha256_hex(keys.join("\n").as_bytes());
+    format!(".rusub-state-{}.json", &hash[..12]).into()
+}
+
 pub async fn save_to_file(db: &StatusDb, path: &Path) -> Result<()> {
     let items = db.snapshot().await;
     let mut out: Vec<PersistItem> = Vec::with_capacity(items.len());
@@ -257,7 +277,7 @@ pub async fn load_from_file(db: &StatusDb, path: &Path) -> Result<usize> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::statusdb::{StatusDb, Item, EntryState};
+    use crate::state::{StatusDb, Item, EntryState};
     use std::time::SystemTime;
 
     #[tokio::test]
@@ -278,4 +298,26 @@ mod tests {
         assert!(matches!(b.state, EntryState::WildFiltered));
         let _ = std::fs::remove_file(&path);
     }
+
+    #[test]
+    fn no_expiry_db_spawns_nothing_and_keeps_old_entries() {
+        // built outside any runtime: create_memory_db would panic on tokio::spawn here
+        let db = StatusDb::create_memory_db_no_expiry();
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let old = SystemTime::now() - std::time::Duration::from_secs(3600);
+            db.add("a.example".into(), Item { domain: "a.example".into(), dns: "8.8.8.8".into(), time: old, retry: 0, domain_level: 0, state: EntryState::Ok }).await;
+            assert!(db.get("a.example").await.is_some());
+        });
+        assert_eq!(db.length(), 1);
+    }
+
+    #[test]
+    fn default_state_path_keyed_by_targets() {
+        let a = default_state_path(&["b.com".into(), "A.com".into()]);
+        let b = default_state_path(&["a.com".into(), "b.com.".into()]);
+        assert_eq!(a, b);
+        assert_ne!(a, default_state_path(&["c.com".into()]));
+        assert!(a.to_string_lossy().starts_with(".rusub-state-"));
+    }
 }
@@ -0,0 +1,98 @@
+//! `--takeover-check`: flag hosts whose CNAME points at a third-party service
+//! that is prone to subdomain takeover (github.io, s3, herokudns, ...).
+//! A match is only a hint; the target still has to be checked by hand.
+
+use crate::output::ScanRecord;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct TakeoverMatcher {
+    /// lowercase, no trailing dot; longest first so the most specific suffix wins
+    suffixes: Vec<String>,
+    /// matched suffix -> hosts flagged
+    hits: Mutex<BTreeMap<String, u64>>,
+}
+
+/// One suffix per line, `#` comments.
+pub fn parse_fingerprints(text: &str) -> Vec<String> {
+    let mut v: Vec<String> = text.lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim().trim_matches('.').to_ascii_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect();
+    v.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    v.dedup();
+    v
+}
+
+impl TakeoverMatcher {
+    pub fn new(suffixes: Vec<String>) -> Self {
+        let text = suffixes.join("\n");
+        Self { suffixes: parse_fingerprints(&text), hits: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Built-in list, or `path` (same format) instead of it.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let text = match path {
+            Some(p) => std::fs::read_to_string(p)
+                .with_context(|| format!("cannot read takeover fingerprints {}", p.display()))?,
+            None => crate::dicts::EMBEDDED_TAKEOVER_TXT.to_string(),
+        };
+        Ok(Self { suffixes: parse_fingerprints(&text), hits: Mutex::new(BTreeMap::new()) })
+    }
+
+    pub fn len(&self) -> usize { self.suffixes.len() }
+
+    pub fn is_empty(&self) -> bool { self.suffixes.is_empty() }
+
+    /// Fingerprint suffix matching `target` on a label boundary.
+    pub fn match_target(&self, target: &str) -> Option<&str> {
+        let t = target.trim_end_matches('.').to_ascii_lowercase();
+        self.suffixes.iter()
+            .find(|s| t == **s || (t.len() > s.len() && t.ends_with(s.as_str()) && t.as_bytes()[t.len() - s.len() - 1] == b'.'))
+            .map(|s| s.as_str())
+    }
+
+    /// First CNAME record in `records` that matches; counted for `summary`.
+    pub fn check(&self, records: &[ScanRecord]) -> Option<String> {
+        let hit = records.iter()
+            .filter(|r| r.rtype == "CNAME")
+            .find_map(|r| self.match_target(&r.data))?
+            .to_string();
+        *self.hits.lock().unwrap().entry(hit.clone()).or_insert(0) += 1;
+        Some(hit)
+    }
+
+    /// Flagged host count per service, sorted by service.
+    pub fn summary(&self) -> Vec<(String, u64)> {
+        self.hits.lock().unwrap().iter().map(|(k, v)| (k.clone(), *v)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cname(d: &str) -> ScanRecord { ScanRecord { rtype: "CNAME".into(), data: d.into(), ttl: None } }
+
+    #[test]
+    fn matches_on_label_boundary() {
+        let m = TakeoverMatcher::new(vec!["github.io".into(), "s3.amazonaws.com".into(), "amazonaws.com".into()]);
+        assert_eq!(m.match_target("acme.github.io."), Some("github.io"));
+        assert_eq!(m.match_target("bucket.S3.amazonaws.com"), Some("s3.amazonaws.com"));
+        assert_eq!(m.match_target("notgithub.io"), None);
+        let recs = [ScanRecord { rtype: "A".into(), data: "1.2.3.4".into(), ttl: None }, cname("x.github.io")];
+        assert_eq!(m.check(&recs).as_deref(), Some("github.io"));
+        assert_eq!(m.check(&[cname("y.github.io"), cname("z.s3.amazonaws.com")]).as_deref(), Some("github.io"));
+        assert_eq!(m.check(&[cname("cdn.example.net")]), None);
+        assert_eq!(m.summary(), [("github.io".to_string(), 2)]);
+    }
+
+    #[test]
+    fn embedded_list_parses() {
+        let m = TakeoverMatcher::load(None).unwrap();
+        assert!(m.len() > 20);
+        assert_eq!(m.match_target("foo.herokudns.com"), Some("herokudns.com"));
+    }
+}
@@ -0,0 +1,70 @@
+//! `--respect-ttl`: in-run positive cache keyed by FQDN.
+//!
+//! A host resolved earlier in the run is not queried again (predict rounds, repeated
+//! inputs) while the smallest TTL among its records has not run out. Unlike the status
+//! DB, whose entries expire on a fixed timer, freshness here follows the zone's own TTLs.
+
+use crate::output::ScanRecord;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub struct TtlCache {
+    expires: Mutex<HashMap<String, Instant>>,
+}
+
+impl TtlCache {
+    pub fn new() -> Self { Self::default() }
+
+    /// Remember `host` until the lowest record TTL elapses; TTL 0 or no TTL is not cached.
+    pub fn insert(&self, host: &str, records: &[ScanRecord]) {
+        let Some(ttl) = records.iter().filter_map(|r| r.ttl).min() else { return };
+        self.insert_for(host, Duration::from_secs(ttl as u64));
+    }
+
+    fn insert_for(&self, host: &str, ttl: Duration) {
+        if ttl.is_zero() { return; }
+        self.expires.lock().unwrap().insert(host.to_ascii_lowercase(), Instant::now() + ttl);
+    }
+
+    /// Still within TTL; expired entries are dropped on lookup.
+    pub fn is_fresh(&self, host: &str) -> bool {
+        let key = host.to_ascii_lowercase();
+        let mut map = self.expires.lock().unwrap();
+        match map.get(&key) {
+            Some(exp) if *exp > Instant::now() => true,
+            Some(_) => { map.remove(&key); false }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize { self.expires.lock().unwrap().len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(ttl: Option<u32>) -> ScanRecord {
+        ScanRecord { rtype: "A".into(), data: "192.0.2.1".into(), ttl }
+    }
+
+    #[test]
+    fn freshness_follows_lowest_ttl() {
+        let c = TtlCache::new();
+        c.insert("WWW.example.com", &[rec(Some(300)), rec(Some(60))]);
+        assert!(c.is_fresh("www.example.com"));
+        c.insert("zero.example.com", &[rec(Some(0))]);
+        c.insert("hosts.example.com", &[rec(None)]);
+        assert!(!c.is_fresh("zero.example.com"));
+        assert!(!c.is_fresh("hosts.example.com"));
+        c.insert_for("short.example.com", Duration::from_millis(20));
+        assert!(c.is_fresh("short.example.com"));
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(!c.is_fresh("short.example.com"));
+        assert_eq!(c.len(), 1, "expired entries are evicted on lookup");
+    }
+}
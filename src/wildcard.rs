@@ -1,21 +1,61 @@
 use std::collections::{HashMap, HashSet};
-use crate::dns;
+use std::net::IpAddr;
+use std::sync::Arc;
+use crate::dns::{DnsClient, QueryOptions, RecordType};
+use crate::rng::{shared_rng, SharedRng, STREAM_WILDCARD};
+use futures::stream::{self, StreamExt};
+use rand::Rng;
 use rand::seq::SliceRandom;
 
+/// Root domains probed at the same time by `detect_all`; each runs its probes sequentially.
+pub const DETECT_PARALLEL: usize = 16;
+
+/// Advanced mode: share of probes an IP has to show up in to count as wildcard.
+pub const DEFAULT_THRESHOLD: f64 = 0.6;
+
+/// How `detect_all` probes each root domain (`--wildcard`, `--wildcard-threshold`, `--wildcard-probes`).
+#[derive(Debug, Clone)]
+pub struct DetectParams {
+    /// `basic`, `advanced`, or anything else (`off`) for no detection
+    pub mode: String,
+    pub threshold: f64,
+    /// random-label probes per root domain; `None` = mode default (basic 3, advanced 6)
+    pub probes: Option<usize>,
+    /// `--source-ip`: probes leave from the same address as the scan queries
+    pub source_ip: Option<IpAddr>,
+}
+
+impl DetectParams {
+    pub fn new(mode: &str) -> Self {
+        Self { mode: mode.to_lowercase(), threshold: DEFAULT_THRESHOLD, probes: None, source_ip: None }
+    }
+
+    pub fn probe_count(&self) -> usize {
+        self.probes.unwrap_or(if self.mode == "basic" { 3 } else { 6 })
+    }
+}
+
+/// `--wildcard-threshold`: a fraction in (0, 1].
+pub fn parse_threshold(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(v) if v > 0.0 && v <= 1.0 => Ok(v),
+        _ => Err(format!("invalid wildcard threshold {:?} (expected 0 < t <= 1)", s)),
+    }
+}
+
 /// Basic wildcard detection: send a few random label queries and collect any returned IPs.
 /// If we consistently get answers for random labels, treat the union of IPs as wildcard set.
-pub fn detect_wildcard(domain: &str, resolvers: &Vec<String>, attempts: usize, timeout_secs: u64) -> HashSet<String> {
+pub fn detect_wildcard(domain: &str, resolvers: &Vec<String>, attempts: usize, timeout_secs: u64, rng: &SharedRng, source: Option<IpAddr>, client: &dyn DnsClient) -> HashSet<String> {
     let mut ips: HashSet<String> = HashSet::new();
     if resolvers.is_empty() { return ips; }
     let timeout_ms = timeout_secs * 1000;
     for i in 0..attempts {        
-        let label = format!("{}_{}", rand::random::<u32>(), i);
+        // '-' rather than '_': Name::from_utf8 rejects underscores, which made every probe fail
+        let label = format!("{}-{}", rng.lock().unwrap().gen::<u32>(), i);
         let host = format!("{}.{}", label, domain);
         // Use a random resolver each time
         if let Some(resolver) = resolvers.get(i % resolvers.len()) {
-            if let Ok(ans) = dns::udp_query(&host, resolver, timeout_ms) {
-                for a in ans { ips.insert(a); }
-            }
+            for a in probe(client, &host, resolver, timeout_ms, rng, source) { ips.insert(a); }
         }
     }
     ips
@@ -25,18 +65,19 @@ pub fn detect_wildcard(domain: &str, resolvers: &Vec<String>, attempts: usize, t
 /// - Perform more random probes
 /// - Count frequency of each answered IP
 /// - Return IPs whose frequency >= attempts * threshold (e.g., 0.6)
-pub fn detect_wildcard_advanced(domain: &str, resolvers: &Vec<String>, attempts: usize, timeout_secs: u64, threshold: f64) -> HashSet<String> {
+#[allow(clippy::too_many_arguments)]
+pub fn detect_wildcard_advanced(domain: &str, resolvers: &Vec<String>, attempts: usize, timeout_secs: u64, threshold: f64, rng: &SharedRng, source: Option<IpAddr>, client: &dyn DnsClient) -> HashSet<String> {
     let mut freq: HashMap<String, u32> = HashMap::new();
     if resolvers.is_empty() || attempts == 0 { return HashSet::new(); }
     let timeout_ms = timeout_secs * 1000;
-    let mut rng = rand::thread_rng();
     for i in 0..attempts {
-        let label = format!("adv{}_{}", rand::random::<u32>(), i);
+        let (label, pick) = {
+            let mut g = rng.lock().unwrap();
+            (format!("adv{}-{}", g.gen::<u32>(), i), resolvers.choose(&mut *g))
+        };
         let host = format!("{}.{}", label, domain);
-        if let Some(resolver) = resolvers.choose(&mut rng) {
-            if let Ok(ans) = dns::udp_query(&host, resolver, timeout_ms) {
-                for a in ans { *freq.entry(a).or_insert(0) += 1; }
-            }
+        if let Some(resolver) = pick {
+            for a in probe(client, &host, resolver, timeout_ms, rng, source) { *freq.entry(a).or_insert(0) += 1; }
         }
     }
     let mut ips: HashSet<String> = HashSet::new();
@@ -47,6 +88,51 @@ pub fn detect_wildcard_advanced(domain: &str, resolvers: &Vec<String>, attempts:
     ips
 }
 
+/// One A query for a random label: addresses as is, CNAME / TXT answers tagged with their type.
+/// Unanswered or failed probes yield nothing.
+fn probe(client: &dyn DnsClient, host: &str, resolver: &str, timeout_ms: u64, rng: &SharedRng, source: Option<IpAddr>) -> Vec<String> {
+    let qo = QueryOptions { a_only: true, source_ip: source, ..QueryOptions::new(rng.clone()) };
+    let Ok(ans) = client.query(host, resolver, RecordType::A, timeout_ms, &qo) else { return Vec::new() };
+    ans.records.into_iter().filter_map(|r| match r.rtype.as_str() {
+        "A" | "AAAA" => Some(r.data),
+        "CNAME" | "TXT" => Some(format!("{} {}", r.rtype, r.data)),
+        _ => None,
+    }).collect()
+}
+
+/// Wildcard sets for every root domain, detected up front and concurrently (at most
+/// `parallel` domains in flight) instead of one blocking detection per domain in the
+/// dispatch loop.
+///
+/// Each domain gets its own RNG seeded from `rng` in input order, so `--seed` stays
+/// reproducible regardless of completion order.
+pub async fn detect_all(domains: &[String], params: &DetectParams, resolvers: &[String], timeout_secs: u64, rng: &SharedRng, parallel: usize, client: &Arc<dyn DnsClient>) -> HashMap<String, Arc<HashSet<String>>> {
+    let resolvers = Arc::new(resolvers.to_vec());
+    let jobs: Vec<(String, u64)> = domains.iter().map(|d| (d.clone(), rng.lock().unwrap().gen::<u64>())).collect();
+    stream::iter(jobs)
+        .map(|(domain, seed)| {
+            let params = params.clone();
+            let resolvers = resolvers.clone();
+            let client = client.clone();
+            async move {
+                let d = domain.clone();
+                let set = tokio::task::spawn_blocking(move || {
+                    let rng = shared_rng(Some(seed), STREAM_WILDCARD);
+                    let n = params.probe_count();
+                    match params.mode.as_str() {
+                        "basic" => detect_wildcard(&d, &resolvers, n, timeout_secs, &rng, params.source_ip, client.as_ref()),
+                        "advanced" => detect_wildcard_advanced(&d, &resolvers, n, timeout_secs, params.threshold, &rng, params.source_ip, client.as_ref()),
+                        _ => HashSet::new(),
+                    }
+                }).await.unwrap_or_default();
+                (domain, Arc::new(set))
+            }
+        })
+        .buffer_unordered(parallel.max(1))
+        .collect()
+        .await
+}
+
 /// Check if answers are considered wildcard (subset of wildcard ip set)
 pub fn is_wildcard(answers: &[String], wild_ips: &HashSet<String>) -> bool {
     if wild_ips.is_empty() { return false; }
@@ -67,4 +153,61 @@ mod tests {
         let empty: HashSet<String> = HashSet::new();
         assert!(!is_wildcard(&["1.2.3.4".into()], &empty));
     }
+
+    /// Answers every A query with 192.0.2.77 after `delay_ms`, one thread per packet.
+    fn slow_wildcard_server(delay_ms: u64) -> String {
+        use trust_dns_proto::op::{Message, MessageType};
+        use trust_dns_proto::rr::{RData, Record};
+        use trust_dns_proto::serialize::binary::{BinDecodable, BinEncodable};
+        let sock = Arc::new(std::net::UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = sock.local_addr().unwrap().to_string();
+        std::thread::spawn(move || loop {
+            let mut buf = [0u8; 512];
+            let Ok((n, peer)) = sock.recv_from(&mut buf) else { break };
+            let sock = sock.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+                let q = Message::from_bytes(&buf[..n]).unwrap();
+                let mut r = Message::new();
+                r.set_id(q.id());
+                r.set_message_type(MessageType::Response);
+                r.add_queries(q.queries().to_vec());
+                let name = q.queries()[0].name().clone();
+                r.add_answer(Record::from_rdata(name, 60, RData::A("192.0.2.77".parse().unwrap())));
+                let _ = sock.send_to(&r.to_bytes().unwrap(), peer);
+            });
+        });
+        addr
+    }
+
+    #[test]
+    fn threshold_must_be_a_fraction() {
+        assert_eq!(parse_threshold("0.3"), Ok(0.3));
+        assert_eq!(parse_threshold("1"), Ok(1.0));
+        assert!(parse_threshold("0").is_err());
+        assert!(parse_threshold("1.5").is_err());
+        assert!(parse_threshold("half").is_err());
+    }
+
+    #[test]
+    fn probe_count_defaults_per_mode() {
+        assert_eq!(DetectParams::new("basic").probe_count(), 3);
+        assert_eq!(DetectParams::new("Advanced").probe_count(), 6);
+        assert_eq!(DetectParams { probes: Some(20), ..DetectParams::new("advanced") }.probe_count(), 20);
+    }
+
+    #[tokio::test]
+    async fn detect_all_runs_domains_concurrently() {
+        let server = slow_wildcard_server(100);
+        let domains: Vec<String> = (0..24).map(|i| format!("d{}.example.com", i)).collect();
+        let udp: Arc<dyn DnsClient> = Arc::new(crate::dns::UdpClient);
+        let started = std::time::Instant::now();
+        let sets = detect_all(&domains, &DetectParams::new("advanced"), &[server], 2, &shared_rng(Some(7), STREAM_WILDCARD), DETECT_PARALLEL, &udp).await;
+        // sequential: 24 domains x 6 probes x 100ms = 14.4s; two waves of 16 take ~1.2s
+        assert!(started.elapsed() < std::time::Duration::from_secs(5), "took {:?}", started.elapsed());
+        assert_eq!(sets.len(), 24);
+        assert!(sets.values().all(|s| s.len() == 1 && s.contains("192.0.2.77")), "{:?}", sets);
+        let none = detect_all(&domains, &DetectParams::new("off"), &[], 2, &shared_rng(Some(7), STREAM_WILDCARD), DETECT_PARALLEL, &udp).await;
+        assert!(none.values().all(|s| s.is_empty()));
+    }
 }